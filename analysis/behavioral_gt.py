@@ -55,6 +55,9 @@ class FeatEvent:
     f4: float     # burst length
     f5: float     # pause count
     f6: float     # pause-after-delete rate
+    f7: float     # hold time median (ms)
+    f8: float     # hold time variance
+    f9: float     # digraph slowdown (1.0 = usual speed for the same key pairs)
     p_flow: float
     p_inc: float
     p_stuck: float
@@ -95,6 +98,8 @@ def load_ndjson(path: Path) -> tuple[list[KeyEvent], list[FeatEvent]]:
                     press=obj["press"],
                 ))
             elif t == "feat":
+                # 新形式は "probs": {"flow": .., ...}、旧形式は p_flow / p_inc / p_stuck
+                probs = obj.get("probs", {})
                 feats.append(FeatEvent(
                     t=obj["t"],
                     f1=obj.get("f1", 0.0),
@@ -103,9 +108,12 @@ def load_ndjson(path: Path) -> tuple[list[KeyEvent], list[FeatEvent]]:
                     f4=obj.get("f4", 0.0),
                     f5=obj.get("f5", 0.0),
                     f6=obj.get("f6", 0.0),
-                    p_flow=obj.get("p_flow", 0.0),
-                    p_inc=obj.get("p_inc", 0.0),
-                    p_stuck=obj.get("p_stuck", 0.0),
+                    f7=obj.get("f7", 0.0),
+                    f8=obj.get("f8", 0.0),
+                    f9=obj.get("f9", 1.0),
+                    p_flow=obj.get("p_flow", probs.get("flow", 0.0)),
+                    p_inc=obj.get("p_inc", probs.get("incubation", 0.0)),
+                    p_stuck=obj.get("p_stuck", probs.get("stuck", 0.0)),
                 ))
 
     return keys, feats
@@ -0,0 +1,293 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::features::Features;
+use crate::analysis::params::{BaselineParams, BaselineSource, CalibrationParams};
+
+/// 確率的近似による頑健な移動中央値の 1 ステップ。
+///
+/// ```text
+/// m ← m + η · m · sign(x − m)
+/// ```
+///
+/// 外れ値は大きさに関わらず 1 ステップ分しか動かさない。相対ステップなので、
+/// 単位の異なる特徴量 (ms・率・回数) に同じ η を使える。
+fn step_median(m: f64, x: f64, rate: f64) -> f64 {
+    if x > m {
+        m * (1.0 + rate)
+    } else if x < m {
+        m * (1.0 - rate)
+    } else {
+        m
+    }
+}
+
+/// 1 ユーザー分の個人ベースライン (プロファイルとして保存する)。
+///
+/// 確信度の高い Flow 中の打鍵 (`flow_threshold` 以上) ごとに、F1・F3・F4・F5・F6 の
+/// 移動中央値を 1 ステップ進める。F3・F5・F6 は 0 を除いた値の中央値とする。
+/// `min_samples` に達するまではキャリブレーション期間で、
+/// `baseline = "personal"` でも母集団の β を用いる。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CalibrationProfile {
+    pub profile: String,
+    /// 学習に用いた打鍵数
+    pub samples: u32,
+    /// 学習中の β
+    pub beta: BaselineParams,
+    /// 最終更新時刻 (Unix ms)
+    pub updated_at: u64,
+}
+
+impl CalibrationProfile {
+    /// 母集団の β から学習を始める
+    pub fn new(profile: &str, population: &BaselineParams) -> Self {
+        Self {
+            profile: profile.to_string(),
+            samples: 0,
+            beta: population.clone(),
+            updated_at: 0,
+        }
+    }
+
+    /// 保存済みのプロファイルを読み込む。無い・不正な場合は新しいプロファイルを返す
+    pub fn load_or_new(profile: &str, population: &BaselineParams) -> Self {
+        let path = profile_path(profile);
+        if !path.exists() {
+            tracing::info!(
+                "Calibration profile '{}' not found, starting fresh",
+                profile
+            );
+            return Self::new(profile, population);
+        }
+        match Self::load(&path) {
+            Ok(p) => {
+                tracing::info!(
+                    "Calibration profile '{}' loaded ({} samples)",
+                    profile,
+                    p.samples
+                );
+                p
+            }
+            Err(e) => {
+                tracing::warn!("Calibration profile: {}. Starting fresh", e);
+                Self::new(profile, population)
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+        let profile: Self = serde_json::from_str(&text)
+            .map_err(|e| format!("failed to parse {:?}: {}", path, e))?;
+        let b = &profile.beta;
+        if [b.f1, b.f2, b.f3, b.f4, b.f5, b.f6]
+            .iter()
+            .any(|v| !v.is_finite() || *v <= 0.0)
+        {
+            return Err(format!("invalid baseline in {:?}: β must be > 0", path));
+        }
+        Ok(profile)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("failed to write {:?}: {}", path, e))
+    }
+
+    /// キャリブレーション期間を終えたか
+    pub fn is_ready(&self, params: &CalibrationParams) -> bool {
+        self.samples >= params.min_samples
+    }
+
+    /// Flow 中の打鍵 1 回分の特徴量で β を更新する。
+    /// F1 が得られない更新は呼び出し側で除外済みとする。
+    pub fn observe(
+        &mut self,
+        params: &CalibrationParams,
+        population: &BaselineParams,
+        features: &Features,
+        timestamp: u64,
+    ) {
+        let rate = params.learning_rate;
+        let ratio = params.max_ratio;
+        let step =
+            |m: f64, x: f64, pop: f64| step_median(m, x, rate).clamp(pop / ratio, pop * ratio);
+        // F3・F5・F6 は Flow 中の窓ではほとんど 0 (修正・ポーズが無い)。0 でも 1 ステップ進めると
+        // β が毎回縮み、キャリブレーション期間のうちに下限 (pop / max_ratio) に張り付くため、
+        // 0 の標本は読み飛ばし、0 以外の値の中央値を学習する
+        let step_nonzero = |m: f64, x: f64, pop: f64| if x > 0.0 { step(m, x, pop) } else { m };
+        let b = &mut self.beta;
+        b.f1 = step(b.f1, features.f1_flight_time_median, population.f1);
+        b.f3 = step_nonzero(b.f3, features.f3_correction_rate, population.f3);
+        b.f4 = step(b.f4, features.f4_burst_length, population.f4);
+        b.f5 = step_nonzero(b.f5, features.f5_pause_count, population.f5);
+        b.f6 = step_nonzero(b.f6, features.f6_pause_after_del_rate, population.f6);
+        self.samples = self.samples.saturating_add(1);
+        self.updated_at = timestamp;
+    }
+
+    /// 実際に φ に用いる β の出所 (personal 指定でもキャリブレーション中は population)
+    pub fn active_source(&self, params: &CalibrationParams) -> BaselineSource {
+        match params.baseline {
+            BaselineSource::Personal if self.is_ready(params) => BaselineSource::Personal,
+            _ => BaselineSource::Population,
+        }
+    }
+}
+
+/// キャリブレーションの段階
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CalibrationPhase {
+    /// `min_samples` 未満 (個人 β はまだ使わない)
+    Calibrating,
+    Ready,
+}
+
+/// 個人ベースラインの学習状況 (`get_calibration_status` 用)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CalibrationStatus {
+    pub profile: String,
+    pub phase: CalibrationPhase,
+    pub samples: u32,
+    pub min_samples: u32,
+    /// 設定で選ばれた β の出所
+    pub requested: BaselineSource,
+    /// 現在 φ に用いている β の出所
+    pub active: BaselineSource,
+    pub population: BaselineParams,
+    pub personal: BaselineParams,
+}
+
+/// プロファイルの保存先 Documents/GSE-sessions/profiles/<profile>.json
+pub fn profile_path(profile: &str) -> PathBuf {
+    crate::logger::default_session_dir()
+        .join("profiles")
+        .join(format!("{}.json", profile))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 線形合同法による [0, 1) の乱数 (外部クレートを使わず再現可能にする)
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self) -> f64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 11) as f64 / (1u64 << 53) as f64
+        }
+    }
+
+    /// 確信度の高い Flow 中の 30 秒窓を模した特徴量。
+    /// F3・F5・F6 は大半の窓で 0 (0 以外の中央値: F3 0.06、F5 1、F6 0.5)
+    fn flow_features(rng: &mut Lcg) -> Features {
+        let nonzero = |rng: &mut Lcg, p: f64| rng.next() < p;
+        Features {
+            f1_flight_time_median: 150.0 + 60.0 * rng.next(),
+            f3_correction_rate: if nonzero(rng, 0.2) {
+                0.04 + 0.04 * rng.next()
+            } else {
+                0.0
+            },
+            f4_burst_length: 3.0 + 4.0 * rng.next(),
+            f5_pause_count: if nonzero(rng, 0.25) {
+                if rng.next() < 2.0 / 3.0 {
+                    1.0
+                } else {
+                    2.0
+                }
+            } else {
+                0.0
+            },
+            f6_pause_after_del_rate: if nonzero(rng, 0.1) { 0.5 } else { 0.0 },
+            ..Features::default()
+        }
+    }
+
+    fn calibrate(samples: u32, seed: u64) -> (CalibrationProfile, BaselineParams) {
+        let params = CalibrationParams::default();
+        let population = BaselineParams::default();
+        let mut profile = CalibrationProfile::new("test", &population);
+        let mut rng = Lcg(seed);
+        for i in 0..samples {
+            profile.observe(&params, &population, &flow_features(&mut rng), i as u64);
+        }
+        (profile, population)
+    }
+
+    fn assert_near(name: &str, value: f64, target: f64, tolerance: f64) {
+        assert!(
+            (value - target).abs() <= tolerance * target,
+            "{}: β = {} (expected {} ± {:.0}%)",
+            name,
+            value,
+            target,
+            tolerance * 100.0
+        );
+    }
+
+    #[test]
+    fn zero_inflated_features_stay_off_the_floor_after_calibration() {
+        let params = CalibrationParams::default();
+        let (profile, population) = calibrate(params.min_samples, 1);
+        assert!(profile.is_ready(&params));
+        let b = &profile.beta;
+        for (name, beta, pop) in [
+            ("f3", b.f3, population.f3),
+            ("f5", b.f5, population.f5),
+            ("f6", b.f6, population.f6),
+        ] {
+            let floor = pop / params.max_ratio;
+            assert!(
+                beta > floor * 1.05,
+                "{}: β = {} is pinned at the floor {}",
+                name,
+                beta,
+                floor
+            );
+        }
+    }
+
+    #[test]
+    fn converges_to_the_flow_medians() {
+        for seed in [1, 2, 3] {
+            let (profile, _) = calibrate(3000, seed);
+            let b = &profile.beta;
+            assert_near("f1", b.f1, 180.0, 0.1);
+            assert_near("f3", b.f3, 0.06, 0.15);
+            assert_near("f4", b.f4, 5.0, 0.1);
+            assert_near("f5", b.f5, 1.0, 0.1);
+            assert_near("f6", b.f6, 0.5, 0.1);
+        }
+    }
+
+    #[test]
+    fn all_zero_feature_keeps_the_population_beta() {
+        let params = CalibrationParams::default();
+        let population = BaselineParams::default();
+        let mut profile = CalibrationProfile::new("test", &population);
+        let features = Features {
+            f1_flight_time_median: 180.0,
+            f4_burst_length: 5.0,
+            ..Features::default()
+        };
+        for i in 0..1000 {
+            profile.observe(&params, &population, &features, i);
+        }
+        assert_eq!(profile.beta.f3, population.f3);
+        assert_eq!(profile.beta.f5, population.f5);
+        assert_eq!(profile.beta.f6, population.f6);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::analysis::engine::CognitiveState;
+use crate::analysis::hmm::{viterbi_observations, ForwardBackward};
+use crate::analysis::params::HmmParams;
+use crate::analysis::replay::ReplayedSession;
+
+/// 同一状態が連続する区間。`behavioral_gt.py` の LabeledSegment と同じく
+/// [start_ms, end_ms) の半開区間で表す。
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateSegment {
+    pub state: CognitiveState,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// 区間に含まれる観測 (HMM 更新) の数
+    pub ticks: usize,
+}
+
+impl StateSegment {
+    pub fn duration_ms(&self) -> u64 {
+        self.end_ms.saturating_sub(self.start_ms)
+    }
+}
+
+/// セッション全体の Viterbi 復号結果
+#[derive(Debug, Clone)]
+pub struct DecodedSession {
+    /// 観測ごとの (タイムスタンプ, 最尤状態)
+    pub path: Vec<(u64, CognitiveState)>,
+    pub segments: Vec<StateSegment>,
+    pub log_probability: f64,
+}
+
+/// 再生済みセッションを Viterbi で復号し、状態区間のリストにまとめる。
+///
+/// HMM を進めなかった更新 (F1 データ不足) は系列に含めない。区間の終端は次の区間の
+/// 最初の観測時刻、最後の区間のみ最後の観測時刻とする。
+pub fn decode_session(session: &ReplayedSession, params: &HmmParams) -> DecodedSession {
+    let timestamps: Vec<u64> = session
+        .ticks
+        .iter()
+        .filter(|t| t.observation.is_some())
+        .map(|t| t.timestamp)
+        .collect();
+    let result = viterbi_observations(params, &session.observations());
+    let path: Vec<(u64, CognitiveState)> = timestamps
+        .iter()
+        .zip(result.states.iter())
+        .map(|(&t, &s)| (t, CognitiveState(s)))
+        .collect();
+
+    DecodedSession {
+        segments: segments_from_path(&path),
+        path,
+        log_probability: result.log_probability,
+    }
+}
+
+/// (タイムスタンプ, 状態) の系列を連続区間にまとめる
+pub fn segments_from_path(path: &[(u64, CognitiveState)]) -> Vec<StateSegment> {
+    let mut segments: Vec<StateSegment> = Vec::new();
+    for &(t, state) in path {
+        match segments.last_mut() {
+            Some(seg) if seg.state == state => {
+                seg.end_ms = t;
+                seg.ticks += 1;
+            }
+            _ => {
+                if let Some(prev) = segments.last_mut() {
+                    prev.end_ms = t;
+                }
+                segments.push(StateSegment {
+                    state,
+                    start_ms: t,
+                    end_ms: t,
+                    ticks: 1,
+                });
+            }
+        }
+    }
+    segments
+}
+
+/// 1 ティック分のオフライン事後確率 (ライブの値と並べて比較するためのもの)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PosteriorTick {
+    pub timestamp: u64,
+    /// ログに記録されたライブの display_probs
+    pub live: Vec<f64>,
+    /// 前向きフィルタ P(s_t | o_1..o_t) (ライブの current_state_probs に相当、ヒステリシス前)
+    pub filtered: Vec<f64>,
+    /// 平滑化事後確率 P(s_t | o_1..o_T)
+    pub smoothed: Vec<f64>,
+}
+
+/// 再生済みセッションに Forward-Backward をかけ、各ティックの平滑化事後確率を求める。
+///
+/// HMM を進めなかったティック (F1 データ不足) は直前の観測ステップの値を引き継ぐ
+/// (ライブの `update()` がその間信念を変えないのと同じ)。最初の観測より前は π の事後分布。
+pub fn smooth_session(session: &ReplayedSession, params: &HmmParams) -> Vec<PosteriorTick> {
+    let fb = ForwardBackward::run_observations(params, &session.observations());
+
+    let mut step = 0;
+    session
+        .ticks
+        .iter()
+        .map(|tick| {
+            if tick.observation.is_some() {
+                step += 1;
+            }
+            PosteriorTick {
+                timestamp: tick.timestamp,
+                live: tick.live_probs.clone(),
+                filtered: fb.alpha[step].clone(),
+                smoothed: fb.gamma(step),
+            }
+        })
+        .collect()
+}
+
+/// 2 つの分布の全変動距離 ½ Σ |p_i − q_i|
+pub fn total_variation(p: &[f64], q: &[f64]) -> f64 {
+    0.5 * p
+        .iter()
+        .zip(q.iter())
+        .map(|(a, b)| (a - b).abs())
+        .sum::<f64>()
+}
+
+pub fn argmax(p: &[f64]) -> usize {
+    p.iter()
+        .enumerate()
+        .fold(
+            (0, f64::NEG_INFINITY),
+            |acc, (i, &v)| if v > acc.1 { (i, v) } else { acc },
+        )
+        .0
+}
+
+/// ライブ推定と平滑化事後確率の比較指標
+#[derive(Debug, Clone, PartialEq)]
+pub struct LagReport {
+    /// 同時刻での平均全変動距離
+    pub mean_tv: f64,
+    /// 同時刻で argmax が一致したティックの割合
+    pub argmax_agreement: f64,
+    /// live[t + k] と smoothed[t] の平均全変動距離が最小となる k (ティック数)
+    pub best_lag_ticks: usize,
+    /// best_lag_ticks における平均全変動距離
+    pub best_lag_tv: f64,
+}
+
+/// ライブ推定が平滑化事後確率に対して何ティック遅れているかを推定する。
+/// `max_lag` までのずれ k を試し、live[t + k] が smoothed[t] に最も近くなる k を返す。
+pub fn lag_report(ticks: &[PosteriorTick], max_lag: usize) -> LagReport {
+    let mean_tv_at = |k: usize| -> f64 {
+        let n = ticks.len().saturating_sub(k);
+        if n == 0 {
+            return f64::INFINITY;
+        }
+        (0..n)
+            .map(|t| total_variation(&ticks[t + k].live, &ticks[t].smoothed))
+            .sum::<f64>()
+            / n as f64
+    };
+
+    let agree = ticks
+        .iter()
+        .filter(|t| argmax(&t.live) == argmax(&t.smoothed))
+        .count();
+
+    let (best_lag_ticks, best_lag_tv) =
+        (0..=max_lag)
+            .map(|k| (k, mean_tv_at(k)))
+            .fold(
+                (0, f64::INFINITY),
+                |acc, x| if x.1 < acc.1 { x } else { acc },
+            );
+
+    LagReport {
+        mean_tv: mean_tv_at(0),
+        argmax_agreement: if ticks.is_empty() {
+            0.0
+        } else {
+            agree as f64 / ticks.len() as f64
+        },
+        best_lag_ticks,
+        best_lag_tv,
+    }
+}
@@ -1,171 +1,884 @@
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 
-use crate::analysis::features::{phi, Features};
+use arc_swap::ArcSwap;
+use serde::Serialize;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum CognitiveState {
-    Flow,
-    Incubation,
-    Stuck,
+use crate::analysis::calibration::{self, CalibrationPhase, CalibrationProfile, CalibrationStatus};
+use crate::analysis::decoding::{argmax, total_variation};
+use crate::analysis::estimator::{build_estimator, EstimatorInput, HmmEstimator, StateEstimator};
+use crate::analysis::explain::UpdateExplanation;
+use crate::analysis::features::Features;
+use crate::analysis::hsmm::DwellEstimate;
+use crate::analysis::hysteresis::{build_policy, HysteresisInput, HysteresisPolicy};
+use crate::analysis::normalize::FeatureNormalizers;
+use crate::analysis::overrides::{self, KeyHistory};
+use crate::analysis::params::{
+    AxisParams, BaselineParams, BaselineSource, EngineParams, HmmParams, LinearAxis,
+    NormalizationParams, OverrideRule, TransitionMatrix,
+};
+use crate::analysis::persistence::EngineCheckpoint;
+use crate::analysis::presence::{Presence, PresenceStatus};
+
+/// 認知状態。`HmmParams::states` のインデックスで表し、名前・GT ラベルは `StateDef` から引く
+/// (既定の並びは 0 = flow, 1 = incubation, 2 = stuck)。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(transparent)]
+pub struct CognitiveState(pub usize);
+
+impl CognitiveState {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// 証拠不足と判定した理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LowEvidence {
+    /// IME ポーズ中 (確率は強制 Flow の値)
+    Paused,
+    /// 離席中 (確率は離席前の信念のまま)
+    Away,
+    /// 直近の更新で F1 (Flight Time) が得られず HMM を進められなかった
+    NoFlightTime,
+    /// 起動・リセット後の更新回数が `cold_start_updates` 未満
+    ColdStart,
+}
+
+/// 状態確率に添える不確かさ指標。
+/// 0.45/0.45/0.10 のような拮抗と安定した分布を UI・介入側で区別するために用いる。
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct StateUncertainty {
+    /// display_probs の正規化エントロピー H / ln N ∈ [0, 1] (N = 状態数、1 = 一様)
+    pub entropy: f64,
+    /// current_state_probs (ヒステリシス前) の正規化エントロピー
+    pub raw_entropy: f64,
+    /// display_probs の上位 2 状態の確率差 ∈ [0, 1]
+    pub margin: f64,
+    /// current_state_probs と display_probs の全変動距離 ∈ [0, 1]
+    /// (大きいほど表示がヒステリシスで遅れている)
+    pub divergence: f64,
+    pub low_evidence: bool,
+    /// low_evidence の理由 (該当なしは None)
+    pub low_evidence_reason: Option<LowEvidence>,
+    /// 起動・リセット後に HMM を進めた回数
+    pub evidence_updates: u32,
+}
+
+/// 正規化エントロピー H(p) / ln N
+pub fn normalized_entropy(p: &[f64]) -> f64 {
+    let h: f64 = p.iter().filter(|&&v| v > 0.0).map(|&v| -v * v.ln()).sum();
+    (h / (p.len() as f64).ln()).clamp(0.0, 1.0)
+}
+
+/// 上位 2 状態の確率差
+pub fn top2_margin(p: &[f64]) -> f64 {
+    let mut sorted = p.to_vec();
+    sorted.sort_by(|a, b| b.total_cmp(a));
+    match sorted[..] {
+        [first, second, ..] => first - second,
+        [only] => only,
+        [] => 0.0,
+    }
+}
+
+/// `index` の状態に確率 `p` を、残りを他の状態へ均等に割り当てた分布
+pub fn concentrated_probs(n: usize, index: usize, p: f64) -> Vec<f64> {
+    let rest = if n > 1 {
+        (1.0 - p) / (n - 1) as f64
+    } else {
+        0.0
+    };
+    (0..n).map(|j| if j == index { p } else { rest }).collect()
+}
+
+/// 表示状態 (display_probs の argmax) の確定した切り替わり
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StateTransition {
+    /// 遷移元・遷移先の状態名 (`states[].name`)
+    pub from: String,
+    pub to: String,
+    /// `to` が argmax になった時刻 (Unix ms)。確定はその `min_dwell_ms` 後
+    pub at: u64,
+    /// `from` に滞在していた時間 [ms]
+    pub dwell_ms: u64,
+    /// 確定時点の display_probs[to]
+    pub confidence: f64,
+}
+
+/// display_probs の argmax の変化をデバウンスし、`min_dwell_ms` 以上続いたものだけを
+/// `StateTransition` として確定させる。途中で元の状態に戻った場合は何も出さない。
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TransitionDetector {
+    // 確定済みの状態とその開始時刻
+    current: Option<(CognitiveState, u64)>,
+    // argmax が変わったが未確定の状態とその開始時刻
+    candidate: Option<(CognitiveState, u64)>,
+}
+
+impl TransitionDetector {
+    pub fn observe(
+        &mut self,
+        probs: &[f64],
+        hmm: &HmmParams,
+        timestamp: u64,
+        min_dwell_ms: u64,
+    ) -> Option<StateTransition> {
+        if probs.is_empty() {
+            return None;
+        }
+        let top = CognitiveState(argmax(probs));
+        let (state, since) = match self.current {
+            Some(c) => c,
+            None => {
+                // 最初の更新: 遷移元が無いので状態を記録するだけ
+                self.current = Some((top, timestamp));
+                return None;
+            }
+        };
+
+        if top == state {
+            self.candidate = None;
+            return None;
+        }
+        let onset = match self.candidate {
+            Some((s, t)) if s == top => t,
+            _ => timestamp,
+        };
+        self.candidate = Some((top, onset));
+        if timestamp.saturating_sub(onset) < min_dwell_ms {
+            return None;
+        }
+
+        self.current = Some((top, onset));
+        self.candidate = None;
+        Some(StateTransition {
+            from: hmm.state_name(state.index()).to_string(),
+            to: hmm.state_name(top.index()).to_string(),
+            at: onset,
+            dwell_ms: onset.saturating_sub(since),
+            confidence: probs[top.index()],
+        })
+    }
+}
+
+// 証拠量の追跡 (update の成否)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct EvidenceState {
+    updates: u32,
+    f1_missing: bool,
+}
+
+/// 特徴量から得られる 1 ステップ分の観測。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Observation {
+    /// EWMA 前の (X = Friction, Y = Engagement)
+    pub raw_axes: (f64, f64),
+    /// EWMA 後の (X, Y)
+    pub axes: (f64, f64),
+    pub x_bin: usize,
+    pub y_bin: usize,
+    /// 観測ビン [0..25] (25 = ペナルティビン)
+    pub index: usize,
+    /// 一致したオーバーライド規則 (`EngineParams::overrides` のインデックス)
+    pub override_rule: Option<usize>,
+    /// 直前の観測からの経過時間 [秒] (最初の観測は None)。連続時間遷移モデルで用いる。
+    pub dt_secs: Option<f64>,
+}
+
+/// 特徴量 → 観測ビン変換の内部状態 (キー押下履歴 + 2軸 EWMA + 直前の観測時刻)。
+///
+/// `update()` とオフライン解析 (replay / Baum-Welch) で同一の変換を行うため、
+/// HMM の信念とは独立した構造体として切り出している。
+#[derive(Debug)]
+pub struct ObservationEncoder {
+    // Recent key presses for the override rules (fixed-size ring buffer)
+    pub key_history: KeyHistory,
+    // Per-feature normalizers (φ by default); rebuilt by set_normalization()
+    pub normalizers: FeatureNormalizers,
+    // 2-axis EWMA: (X = Friction, Y = Engagement)
+    // α = 0.3 (既定): 新値30%、前値70%のブレンド
+    pub axes_ewma: (f64, f64),
+    /// 直前に観測を生成した時刻 (Unix ms)
+    pub last_timestamp: Option<u64>,
+}
+
+impl Default for ObservationEncoder {
+    fn default() -> Self {
+        Self {
+            key_history: KeyHistory::default(),
+            normalizers: FeatureNormalizers::default(),
+            // (0.3, 0.5) = 中立領域で初期化 (obs=7; Flow/Inc/Stuck がほぼ均等な観測ビン)
+            // (0.0, 1.0) で開始すると初回更新で p_flow=1.0 に固定されるため変更
+            axes_ewma: (0.3, 0.5),
+            last_timestamp: None,
+        }
+    }
+}
+
+impl ObservationEncoder {
+    /// キーイベント (押下・解放) をオーバーライド規則用の履歴に取り込む
+    pub fn observe_key(&mut self, vk_code: u32, is_press: bool, timestamp: u64) {
+        self.key_history.observe(vk_code, is_press, timestamp);
+    }
+
+    /// 正規化方式を作り直す (z-score の移動統計は捨てる)
+    pub fn set_normalization(&mut self, params: &NormalizationParams) {
+        self.normalizers = FeatureNormalizers::from_params(params);
+    }
+
+    /// 打鍵による特徴量で正規化の内部統計 (z-score の移動平均・分散) を進める。
+    /// `encode()` の後に呼ぶ。沈黙ティックの合成値では呼ばない。
+    pub fn observe_features(&mut self, beta: &BaselineParams, features: &Features) {
+        self.normalizers.observe(beta, features);
+    }
+
+    /// EWMA を 1 ステップ進め、オーバーライド規則を評価して観測ビンを返す。
+    /// `timestamp` (Unix ms) は直前の観測からの経過時間 `dt_secs` の算出に用いる。
+    pub fn encode(
+        &mut self,
+        params: &AxisParams,
+        overrides: &[OverrideRule],
+        features: &Features,
+        timestamp: u64,
+    ) -> Observation {
+        let dt_secs = self
+            .last_timestamp
+            .map(|prev| timestamp.saturating_sub(prev) as f64 / 1000.0);
+        self.last_timestamp = Some(timestamp);
+
+        let (raw_x, raw_y) = calculate_latent_axes(params, &self.normalizers, features);
+
+        // EWMA平滑化 (既定 α = 0.3): 各軸を独立に平滑化
+        // s_t = α * raw_t + (1 − α) * s_{t-1}
+        let a = params.ewma_alpha;
+        self.axes_ewma.0 = a * raw_x + (1.0 - a) * self.axes_ewma.0;
+        self.axes_ewma.1 = a * raw_y + (1.0 - a) * self.axes_ewma.1;
+        let (x, y) = self.axes_ewma;
+
+        // (X, Y) → 5×5 グリッド → 観測インデックス [0..24]
+        let x_bin = (x * 5.0).floor().min(4.0) as usize;
+        let y_bin = (y * 5.0).floor().min(4.0) as usize;
+        let mut index = x_bin * 5 + y_bin;
+
+        // Override rules (既定: 連続 Backspace 等 → ペナルティビン obs=25)
+        // 最初に一致した規則の観測ビンへ強制マップ
+        let override_rule = overrides::evaluate(overrides, &self.key_history, timestamp);
+        if let Some(i) = override_rule {
+            index = overrides[i].obs;
+        }
+
+        Observation {
+            raw_axes: (raw_x, raw_y),
+            axes: (x, y),
+            x_bin,
+            y_bin,
+            index,
+            override_rule,
+            dt_secs,
+        }
+    }
+}
+
+/// X軸 (Friction / 摩擦) と Y軸 (Engagement / 没入度) を算出する。
+/// 返値はそれぞれ [0.0, 1.0] にクランプ済み。重み・β は `AxisParams` から取る (以下は既定値)。
+/// φ は特徴量ごとに選んだ正規化関数 (`axes.normalization`、既定は φ) に読み替える。
+///
+/// X (Friction) — 高いほど「つまずき」を表す。重み合計 = 1.0
+///   0.30 × φ(F3: 修正率)
+///   0.25 × φ(F6: 削除後停止率)
+///   0.25 × φ(F1: Flight Time)
+///   0.20 × φ(F5: ポーズ回数)
+///
+/// Y (Engagement) — 高いほど「滑らかな出力」を表す。重み合計 = 1.0
+///   0.40 × φ(F4: バースト長)
+///   0.35 × (1 − φ(F1))   … 短いFT = 高エンゲージ
+///   0.25 × (1 − φ(F5))   … 少ないポーズ = 高エンゲージ
+pub fn calculate_latent_axes(
+    params: &AxisParams,
+    normalizers: &FeatureNormalizers,
+    features: &Features,
+) -> (f64, f64) {
+    AxisBreakdown::compute(params, normalizers, features).axes()
+}
+
+/// 正規化した F1–F6 (既定は φ)。F2 は学習した射影 (`axes.projection`) でのみ用いる
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PhiFeatures {
+    pub f1: f64,
+    pub f2: f64,
+    pub f3: f64,
+    pub f4: f64,
+    pub f5: f64,
+    pub f6: f64,
+}
+
+/// 1 軸への寄与。手設定の重みでは bias = 0 で、Y の F1・F5 は w · (1 − φ)、
+/// 学習した射影では bias と wᵢ · φ(Fᵢ)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct AxisTerms {
+    pub bias: f64,
+    pub f1: f64,
+    pub f2: f64,
+    pub f3: f64,
+    pub f4: f64,
+    pub f5: f64,
+    pub f6: f64,
+}
+
+impl AxisTerms {
+    fn linear(axis: &LinearAxis, phi: &PhiFeatures) -> Self {
+        let w = &axis.weights;
+        Self {
+            bias: axis.bias,
+            f1: w.f1 * phi.f1,
+            f2: w.f2 * phi.f2,
+            f3: w.f3 * phi.f3,
+            f4: w.f4 * phi.f4,
+            f5: w.f5 * phi.f5,
+            f6: w.f6 * phi.f6,
+        }
+    }
+
+    fn sum(&self) -> f64 {
+        self.bias + self.f1 + self.f2 + self.f3 + self.f4 + self.f5 + self.f6
+    }
+}
+
+/// 潜在軸の内訳: 正規化した特徴量と各軸への寄与 (クランプ前)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct AxisBreakdown {
+    pub phi: PhiFeatures,
+    pub friction: AxisTerms,
+    pub engagement: AxisTerms,
+}
+
+impl AxisBreakdown {
+    pub fn compute(
+        params: &AxisParams,
+        normalizers: &FeatureNormalizers,
+        features: &Features,
+    ) -> Self {
+        let phi = normalizers.normalize(&params.beta, features);
+
+        if let Some(p) = &params.projection {
+            return Self {
+                phi,
+                friction: AxisTerms::linear(&p.friction, &phi),
+                engagement: AxisTerms::linear(&p.engagement, &phi),
+            };
+        }
+
+        // X: Friction (高いほど「つまずき」)
+        let w = &params.friction;
+        let friction = AxisTerms {
+            f3: w.f3 * phi.f3,
+            f6: w.f6 * phi.f6,
+            f1: w.f1 * phi.f1,
+            f5: w.f5 * phi.f5,
+            ..AxisTerms::default()
+        };
+
+        // Y: Engagement (高いほど「滑らかな出力」)
+        let w = &params.engagement;
+        let engagement = AxisTerms {
+            f4: w.f4 * phi.f4,
+            f1: w.f1 * (1.0 - phi.f1),
+            f5: w.f5 * (1.0 - phi.f5),
+            ..AxisTerms::default()
+        };
+
+        Self {
+            phi,
+            friction,
+            engagement,
+        }
+    }
+
+    /// 寄与の和を [0.0, 1.0] にクランプした (X, Y)
+    pub fn axes(&self) -> (f64, f64) {
+        let x = self.friction.sum().clamp(0.0, 1.0);
+        let y = self.engagement.sum().clamp(0.0, 1.0);
+        (x, y)
+    }
+}
+
+/// `update()` の結果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UpdateOutcome {
+    /// この更新で確定した表示状態の遷移
+    pub transition: Option<StateTransition>,
+    /// `debug.explain` 有効時の判断根拠
+    pub explanation: Option<UpdateExplanation>,
+    /// 個人ベースラインで観測を作った場合の β (母集団 β の場合は None)。
+    /// 再生で同じ観測を作れるよう `feat` 行に記録する
+    pub personal_beta: Option<BaselineParams>,
+}
+
+/// エンジンの可変状態の不変スナップショット。
+///
+/// 分析スレッド (唯一の書き手) が更新ごとに新しいスナップショットを作って丸ごと差し替え、
+/// UI コマンド・IME モニター・ロガーなどの読み手はロックを取らずに一貫した値を読む。
+/// raw と display が別々の更新に由来するような「ちぎれた」状態は観測されない。
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineState {
+    /// 推定器の出力 (ヒステリシス前の瞬間値、`states` の並び)
+    pub current_state_probs: Vec<f64>,
+    /// ヒステリシス層の出力 (UI・ログ用)
+    pub display_probs: Vec<f64>,
+    /// 2軸 EWMA 後の (X = Friction, Y = Engagement)
+    pub axes_ewma: (f64, f64),
+    /// 直前の観測で一致したオーバーライド規則 (`EngineParams::overrides` のインデックス)
+    pub override_rule: Option<usize>,
+    /// IME ポーズ中か
+    pub is_paused: bool,
+    /// 在席状態。離席中は IME ポーズ中と同じく更新を止める
+    pub presence: PresenceStatus,
+    /// HSMM 有効時の滞在時間の推定値
+    pub dwell: Option<DwellEstimate>,
+    /// 直前の更新の判断根拠 (`debug.explain` 有効時のみ)
+    pub last_explanation: Option<UpdateExplanation>,
+    /// 学習中の個人ベースライン β とその学習サンプル数
+    pub personal_beta: BaselineParams,
+    pub calibration_samples: u32,
+    evidence: EvidenceState,
+    // force_flow_state() のたびに進む世代番号。書き手はこれが変わったら内部状態を捨てる
+    reset_epoch: u64,
+    // set_params() で状態の定義 (`states`) が変わるたびに進む世代番号。
+    // ロックを取らない force_flow_state()・restore() が古い定義の確率を書き込まないよう照合する
+    states_epoch: u64,
+}
+
+impl EngineState {
+    fn initial(probs: Vec<f64>, calibration: &CalibrationProfile) -> Self {
+        Self {
+            current_state_probs: probs.clone(),
+            // display_probs は initial_probs と同値で初期化
+            display_probs: probs,
+            axes_ewma: ObservationEncoder::default().axes_ewma,
+            override_rule: None,
+            is_paused: false,
+            presence: PresenceStatus::default(),
+            dwell: None,
+            last_explanation: None,
+            personal_beta: calibration.beta.clone(),
+            calibration_samples: calibration.samples,
+            evidence: EvidenceState::default(),
+            reset_epoch: 0,
+            states_epoch: 0,
+        }
+    }
+}
+
+// 分析スレッド (書き手) だけが進める内部状態。読み手はスナップショットだけを見るため、
+// ここのロックを待つことはない (set_params() による差し替えの一瞬を除く)。
+struct WriterState {
+    // Key history for the override rules + 2-axis EWMA (Friction, Engagement)
+    encoder: ObservationEncoder,
+
+    // Observation → state probabilities (HMM/HSMM filter or ONNX model) — see estimator.rs
+    // Rebuilt by set_params() only when params.estimator changes.
+    estimator: Box<dyn StateEstimator>,
+
+    // Hysteresis layer: derives display_probs from the raw belief with the configured
+    // HysteresisPolicy (default: slow EMA, α = 0.25 / 0.50 on an override match).
+    // Prevents instant state flips (e.g. Cold-Start after window reset).
+    // Rebuilt by set_params() only when params.hysteresis.policy changes.
+    hysteresis: Box<dyn HysteresisPolicy>,
+
+    // Debounced argmax of display_probs → StateTransition events
+    transitions: TransitionDetector,
+
+    // Personal φ baselines learned from confident Flow keystrokes — see calibration.rs
+    calibration: CalibrationProfile,
+
+    // reset_epoch of the last snapshot this state was synchronized with
+    reset_epoch: u64,
+}
+
+impl WriterState {
+    /// force_flow_state() / restore() で差し替えられたスナップショットに内部状態を合わせる
+    fn sync_reset(&mut self, state: &EngineState) {
+        if self.reset_epoch == state.reset_epoch {
+            return;
+        }
+        self.reset_epoch = state.reset_epoch;
+        self.encoder.axes_ewma = state.axes_ewma;
+        self.hysteresis.reset();
+        // 推定器の内部状態 (HSMM の滞在時間など) もクリア (次回更新時に差し替え後の値から再シード)
+        self.estimator.reset(&state.current_state_probs);
+    }
 }
 
 #[derive(Clone)]
 pub struct CognitiveStateEngine {
-    // Manual HMM parameters
-    transitions: Arc<[f64; 9]>,
-
-    // 3 states × 26 observation bins
+    // Tunable parameters (HMM A/B/π, axes, hysteresis) — see params.rs
+    // Hot-swappable: set_params() replaces the Arc atomically;
+    // update() takes one snapshot per call so a swap never mixes old and new values.
+    // HMM: A = N×N, B = N states × 26 observation bins (N = params.hmm.states)
     //   obs = x_bin * 5 + y_bin  (0..24 natural bins)
-    //   obs = 25                  (backspace streak penalty bin)
+    //   obs = 25                  (override-rule penalty bin)
     //
     // X-axis (Friction):   0 = low friction  … 4 = high friction
     // Y-axis (Engagement): 0 = low engagement … 4 = high engagement
-    emissions: Arc<[f64; 78]>,
+    params: Arc<ArcSwap<EngineParams>>,
 
-    current_state_probs: Arc<Mutex<[f64; 3]>>,
-    pub is_paused: Arc<Mutex<bool>>,
-    pub backspace_streak: Arc<Mutex<u32>>,
+    // Published state (probabilities, EWMA axes, pause flag, evidence, …) — see EngineState.
+    // Single writer (analysis thread) swaps in a new snapshot per update; readers never lock.
+    state: Arc<ArcSwap<EngineState>>,
 
-    // 2-axis EWMA: (X = Friction, Y = Engagement)
-    // α = 0.3: 新値30%、前値70%のブレンド
-    axes_ewma: Arc<Mutex<(f64, f64)>>,
+    // Writer-side state (encoder, estimator, hysteresis policy, transition detector)
+    writer: Arc<Mutex<WriterState>>,
 
-    // Hysteresis layer: slow EMA of reported probabilities.
-    // Prevents instant state flips (e.g. Cold-Start after window reset).
-    // α = 0.25 for normal updates (~4s time-constant).
-    // α = 0.50 for backspace-penalty bin (faster Stuck response).
-    display_probs: Arc<Mutex<[f64; 3]>>,
+    // Serializes set_params() callers (file watcher, Tauri command). Held while the new
+    // estimator is built, so the writer lock is only taken for the swap itself.
+    reload: Arc<Mutex<()>>,
+}
+
+impl Default for CognitiveStateEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CognitiveStateEngine {
     pub fn new() -> Self {
-        // Transition probabilities
-        // FLOW -> FLOW: 0.80  (escape time 1/(1-0.80)=5s; reduced from 0.92 to prevent saturation)
-        // FLOW -> INCUBATION: 0.13
-        // FLOW -> STUCK: 0.07
-        // INCUBATION -> FLOW: 0.12
-        // INCUBATION -> INCUBATION: 0.80  (Sio & Ormerod 2009)
-        // INCUBATION -> STUCK: 0.08
-        // STUCK -> FLOW: 0.06
-        // STUCK -> INCUBATION: 0.18
-        // STUCK -> STUCK: 0.76  (Hall et al. 2024)
-        let transitions = [0.80, 0.13, 0.07, 0.12, 0.80, 0.08, 0.06, 0.18, 0.76];
-
-        // Emissions B: 3 states × 26 bins
-        //
-        // Grid layout: obs = x_bin * 5 + y_bin
-        //
-        //        x→  0(lo F)  1      2      3      4(hi F)
-        //  y↓
-        //  0(lo E)   [0]     [5]   [10]   [15]   [20]
-        //  1         [1]     [6]   [11]   [16]   [21]
-        //  2         [2]     [7]   [12]   [17]   [22]
-        //  3         [3]     [8]   [13]   [18]   [23]
-        //  4(hi E)   [4]     [9]   [14]   [19]   [24]
-        //  penalty   [25]
-        //
-        // Flow:       peaks at low Friction (x=0,1) × high Engagement (y=3,4)
-        // Incubation: peaks at low-mid Friction (x=0,1,2) × low Engagement (y=0,1)
-        // Stuck:      peaks at high Friction (x=3,4) × low Engagement (y=0,1)
-        //
-        // Penalty bin (obs=25): backspace_streak ≥ 5 → near-certain Stuck.
-        // Each state's non-penalty bins sum to ≈1.0; HMM normalizes anyway.
-        #[rustfmt::skip]
-        let emissions: [f64; 78] = [
-            // ── Flow (State 0) ─────────────────────────── non-penalty sum = 1.00
-            //  x=0 (low F)    y: 0     1     2     3     4
-                               0.01, 0.02, 0.05, 0.16, 0.20,
-            //  x=1            y: 0     1     2     3     4
-                               0.01, 0.02, 0.05, 0.14, 0.16,
-            //  x=2            y: 0     1     2     3     4
-                               0.00, 0.01, 0.03, 0.06, 0.08,
-            //  x=3            y: 0     1     2     3     4
-                               0.00, 0.00, 0.00, 0.00, 0.00,
-            //  x=4 (high F)   y: 0     1     2     3     4
-                               0.00, 0.00, 0.00, 0.00, 0.00,
-            //  penalty bin
-                               0.00,
-
-            // ── Incubation (State 1) ──────────────────── non-penalty sum = 1.00
-            //  x=0 (low F)    y: 0     1     2     3     4
-                               0.15, 0.10, 0.04, 0.01, 0.00,
-            //  x=1            y: 0     1     2     3     4
-                               0.14, 0.10, 0.04, 0.01, 0.00,
-            //  x=2            y: 0     1     2     3     4
-                               0.10, 0.08, 0.03, 0.01, 0.00,
-            //  x=3            y: 0     1     2     3     4
-                               0.05, 0.04, 0.01, 0.00, 0.00,
-            //  x=4 (high F)   y: 0     1     2     3     4
-                               0.04, 0.03, 0.01, 0.00, 0.00,
-            //  penalty bin
-                               0.01,
-
-            // ── Stuck (State 2) ───────────────── non-penalty sum = 1.00 (+0.99)
-            //  x=0 (low F)    y: 0     1     2     3     4
-                               0.00, 0.00, 0.00, 0.00, 0.00,
-            //  x=1            y: 0     1     2     3     4
-                               0.00, 0.00, 0.00, 0.00, 0.00,
-            //  x=2            y: 0     1     2     3     4
-                               0.02, 0.04, 0.02, 0.00, 0.00,
-            //  x=3            y: 0     1     2     3     4
-                               0.10, 0.16, 0.07, 0.02, 0.00,
-            //  x=4 (high F)   y: 0     1     2     3     4
-                               0.16, 0.22, 0.12, 0.05, 0.02,
-            //  penalty bin  (backspace streak ≥5 → near-certain Stuck)
-                               0.99,
-        ];
-
-        // 初期事前確率: バランス型で開始 (Flow偏重を排除)
-        let initial_probs = [0.5, 0.3, 0.2];
+        Self::from_params(EngineParams::default())
+    }
+
+    /// 外部パラメータ (例: 設定ファイル・`train_hmm` の出力) でエンジンを構築する。
+    pub fn from_params(params: EngineParams) -> Self {
+        let initial_probs = params.hmm.initial.clone();
+        let estimator =
+            build_estimator(&params.estimator, &params.hmm.states).unwrap_or_else(|e| {
+                tracing::warn!("Estimator unavailable, falling back to HMM: {}", e);
+                Box::new(HmmEstimator::default())
+            });
+        tracing::info!("State estimator: {}", estimator.name());
+        let hysteresis = build_policy(params.hysteresis.policy);
+        let mut encoder = ObservationEncoder::default();
+        encoder.set_normalization(&params.axes.normalization);
+        let calibration = CalibrationProfile::new(&params.calibration.profile, &params.axes.beta);
+        let state = EngineState::initial(initial_probs, &calibration);
 
         Self {
-            transitions: Arc::new(transitions),
-            emissions: Arc::new(emissions),
-            current_state_probs: Arc::new(Mutex::new(initial_probs)),
-            is_paused: Arc::new(Mutex::new(false)),
-            backspace_streak: Arc::new(Mutex::new(0)),
-            // (0.3, 0.5) = 中立領域で初期化 (obs=7; Flow/Inc/Stuck がほぼ均等な観測ビン)
-            // (0.0, 1.0) で開始すると初回更新で p_flow=1.0 に固定されるため変更
-            axes_ewma: Arc::new(Mutex::new((0.3, 0.5))),
-            // display_probs は initial_probs と同値で初期化
-            display_probs: Arc::new(Mutex::new(initial_probs)),
+            params: Arc::new(ArcSwap::from_pointee(params)),
+            state: Arc::new(ArcSwap::from_pointee(state)),
+            writer: Arc::new(Mutex::new(WriterState {
+                encoder,
+                estimator,
+                hysteresis,
+                transitions: TransitionDetector::default(),
+                calibration,
+                reset_epoch: 0,
+            })),
+            reload: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// 現在使用中のパラメータ (スナップショット)
+    pub fn params(&self) -> Arc<EngineParams> {
+        self.params.load_full()
+    }
+
+    /// 現在の状態のスナップショット (ロックを取らない)
+    pub fn snapshot(&self) -> Arc<EngineState> {
+        self.state.load_full()
+    }
+
+    fn writer(&self) -> MutexGuard<'_, WriterState> {
+        match self.writer.lock() {
+            Ok(g) => g,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// パラメータを差し替える (ホットリロード)。
+    /// 検証に失敗した場合は何も変更しない。信念・EWMA・ヒステリシス層はそのまま引き継ぐ。
+    /// `estimator` 設定が変わった場合のみ推定器を作り直す (モデルの読み込みに失敗した場合も何も変更しない)。
+    ///
+    /// 状態の定義 (`states`) が変わった場合は確率ベクトルの次元・意味が変わるため、
+    /// 推定器・ヒステリシス層を作り直し、信念を新しい `initial` からやり直す。
+    ///
+    /// 推定器の構築・プロファイルの読み込みは書き手のロックの外で行い、ロックは
+    /// 差し替えとパラメータの公開の間だけ保持する。そのため分析スレッドはモデルの読み込みを待たず、
+    /// `update()` が新しい状態の信念と古いパラメータを組み合わせることもない。
+    pub fn set_params(&self, params: EngineParams) -> Result<(), String> {
+        params.validate()?;
+        let _reload = self.reload.lock().unwrap_or_else(PoisonError::into_inner);
+        let current = self.params();
+        let states_changed = params.hmm.states != current.hmm.states;
+        let estimator = if params.estimator != current.estimator || states_changed {
+            let estimator = build_estimator(&params.estimator, &params.hmm.states)?;
+            tracing::info!("State estimator: {}", estimator.name());
+            Some(estimator)
+        } else {
+            None
+        };
+        let hysteresis = if states_changed {
+            Some(build_policy(params.hysteresis.policy))
+        } else if params.hysteresis.policy != current.hysteresis.policy {
+            let policy = build_policy(params.hysteresis.policy);
+            tracing::info!("Hysteresis policy: {}", policy.name());
+            Some(policy)
+        } else {
+            None
+        };
+        let profile = (params.calibration.profile != current.calibration.profile).then(|| {
+            CalibrationProfile::load_or_new(&params.calibration.profile, &params.axes.beta)
+        });
+
+        let mut writer = self.writer();
+        if let Some(estimator) = estimator {
+            writer.estimator = estimator;
+        }
+        if let Some(hysteresis) = hysteresis {
+            writer.hysteresis = hysteresis;
+        }
+        if params.axes.normalization != current.axes.normalization {
+            writer.encoder.set_normalization(&params.axes.normalization);
+        }
+        let previous = profile.map(|profile| self.swap_calibration(&mut writer, profile));
+        let explain = params.debug.explain;
+        // パラメータを先に公開し、状態の定義の世代番号はその後で進める。
+        // force_flow_state()・restore() は世代番号 → パラメータの順に読むため、
+        // 古い世代番号のまま新しい定義の確率を書き込んでも、直後のリセットで上書きされる
+        let params = Arc::new(params);
+        self.params.store(Arc::clone(&params));
+        if states_changed {
+            writer.transitions = TransitionDetector::default();
+            let initial = &params.hmm.initial;
+            self.state.rcu(|s| EngineState {
+                current_state_probs: initial.clone(),
+                display_probs: initial.clone(),
+                dwell: None,
+                last_explanation: None,
+                evidence: EvidenceState::default(),
+                reset_epoch: s.reset_epoch.wrapping_add(1),
+                states_epoch: s.states_epoch.wrapping_add(1),
+                ..EngineState::clone(s)
+            });
+            tracing::info!(
+                "States changed: {}",
+                params
+                    .hmm
+                    .states
+                    .iter()
+                    .map(|s| s.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        drop(writer);
+        // 切り替え前のプロファイルはロックを放してから保存する
+        if let Some(previous) = previous {
+            save_profile(&previous);
+        }
+        if !explain && self.state.load().last_explanation.is_some() {
+            self.state.rcu(|s| EngineState {
+                last_explanation: None,
+                ..EngineState::clone(s)
+            });
+        }
+        Ok(())
+    }
+
+    /// 個人ベースラインのプロファイルを差し替える (起動時の読み込み・プロファイル切り替え)
+    pub fn load_calibration(&self, profile: CalibrationProfile) {
+        let mut writer = self.writer();
+        self.swap_calibration(&mut writer, profile);
+    }
+
+    /// プロファイルを差し替え、切り替え前のプロファイルを返す
+    fn swap_calibration(
+        &self,
+        writer: &mut WriterState,
+        profile: CalibrationProfile,
+    ) -> CalibrationProfile {
+        let (beta, samples) = (profile.beta.clone(), profile.samples);
+        let previous = std::mem::replace(&mut writer.calibration, profile);
+        self.state.rcu(|s| EngineState {
+            personal_beta: beta.clone(),
+            calibration_samples: samples,
+            ..EngineState::clone(s)
+        });
+        previous
+    }
+
+    /// 学習中の個人ベースラインをプロファイルファイルへ保存する
+    pub fn save_calibration(&self) {
+        let profile = self.writer().calibration.clone();
+        save_profile(&profile);
+    }
+
+    /// 個人ベースラインの学習状況 (ロックを取らない)
+    pub fn calibration_status(&self) -> CalibrationStatus {
+        let params = self.params();
+        let state = self.snapshot();
+        let c = &params.calibration;
+        let ready = state.calibration_samples >= c.min_samples;
+        CalibrationStatus {
+            profile: c.profile.clone(),
+            phase: if ready {
+                CalibrationPhase::Ready
+            } else {
+                CalibrationPhase::Calibrating
+            },
+            samples: state.calibration_samples,
+            min_samples: c.min_samples,
+            requested: c.baseline,
+            active: if ready {
+                c.baseline
+            } else {
+                BaselineSource::Population
+            },
+            population: params.axes.beta.clone(),
+            personal: state.personal_beta.clone(),
         }
     }
 
     pub fn set_paused(&self, paused: bool) {
-        match self.is_paused.lock() {
-            Ok(mut p) => *p = paused,
-            Err(poisoned) => *poisoned.into_inner() = paused,
+        // IME モニターは 100ms ごとに呼ぶため、値が変わるときだけ差し替える
+        if self.state.load().is_paused == paused {
+            return;
         }
+        self.state.rcu(|s| EngineState {
+            is_paused: paused,
+            ..EngineState::clone(s)
+        });
     }
 
     /// IME active時に強制的にFlow状態にする (Stuck表示を消す)
     /// EWMA もリセットして誤った蓄積値が残らないようにする
+    ///
+    /// 対象は名前が "flow" の状態 (無ければ先頭の状態)。
+    /// ロックを取らないため分析スレッドを待たせない。計算中の `update()` の結果は、
+    /// 書き手が公開時に世代番号の変化を見て捨て、推定器・ヒステリシス層の内部状態も次の更新でクリアする。
+    /// 読んだパラメータの後に `set_params()` が状態の定義を変えた場合は何もしない
+    /// (IME モニターは次の周期に新しい定義でやり直す)。
     pub fn force_flow_state(&self) {
-        let flow_probs = [0.98, 0.01, 0.01];
-        match self.current_state_probs.lock() {
-            Ok(mut p) => *p = flow_probs,
-            Err(poisoned) => *poisoned.into_inner() = flow_probs,
+        let states_epoch = self.state.load().states_epoch;
+        let hmm = &self.params().hmm;
+        let flow = hmm.state_index("flow").unwrap_or(0);
+        let flow_probs = concentrated_probs(hmm.n_states(), flow, 0.98);
+        self.state.rcu(|s| {
+            if s.states_epoch != states_epoch {
+                return EngineState::clone(s);
+            }
+            EngineState {
+                current_state_probs: flow_probs.clone(),
+                // display_probs も即座にリセット (ヒステリシス層もクリア)
+                display_probs: flow_probs.clone(),
+                // IME切り替え時にEWMAをリセット: (低Friction, 高Engagement) = Flow領域
+                axes_ewma: (0.0, 1.0),
+                dwell: None,
+                // 強制値は観測に基づかないため cold start からやり直す
+                evidence: EvidenceState::default(),
+                reset_epoch: s.reset_epoch.wrapping_add(1),
+                ..EngineState::clone(s)
+            }
+        });
+    }
+
+    /// キーイベント (押下・解放) をオーバーライド規則用の履歴に記録する。
+    /// `update()` より前に、修飾キーを含むすべてのキーイベントについて呼ぶ。
+    pub fn observe_key(&self, vk_code: u32, is_press: bool, timestamp: u64) {
+        self.writer()
+            .encoder
+            .observe_key(vk_code, is_press, timestamp);
+    }
+
+    /// 保存用のチェックポイント (現在のスナップショットから作る)
+    pub fn checkpoint(&self) -> EngineCheckpoint {
+        let s = self.snapshot();
+        EngineCheckpoint {
+            states: self.state_names(),
+            current_state_probs: s.current_state_probs.clone(),
+            display_probs: s.display_probs.clone(),
+            axes_ewma: s.axes_ewma,
+        }
+    }
+
+    /// 起動直後の既定状態 (initial_probs・EWMA 初期値)。ウォームスタートの減衰先
+    pub fn prior_checkpoint(&self) -> EngineCheckpoint {
+        let initial = self.params().hmm.initial.clone();
+        EngineCheckpoint {
+            states: self.state_names(),
+            current_state_probs: initial.clone(),
+            display_probs: initial,
+            axes_ewma: ObservationEncoder::default().axes_ewma,
+        }
+    }
+
+    /// 保存した状態を復元する (ウォームスタート)。
+    ///
+    /// force_flow_state() と同じく世代番号を進め、推定器・ヒステリシス層は次の更新で
+    /// 復元値から再シードする。証拠量は今回のセッションの観測ではないため cold start のまま。
+    /// 保存時と状態の定義が異なる場合は復元しない。ロックは取らない。
+    pub fn restore(&self, checkpoint: &EngineCheckpoint) -> Result<(), String> {
+        let states_epoch = self.state.load().states_epoch;
+        let names = self.state_names();
+        if checkpoint.states != names {
+            return Err(format!(
+                "saved states [{}] do not match the current states [{}]",
+                checkpoint.states.join(", "),
+                names.join(", ")
+            ));
         }
-        // display_probs も即座にリセット (ヒステリシス層もクリア)
-        match self.display_probs.lock() {
-            Ok(mut p) => *p = flow_probs,
-            Err(poisoned) => *poisoned.into_inner() = flow_probs,
+        let n = names.len();
+        if checkpoint.current_state_probs.len() != n || checkpoint.display_probs.len() != n {
+            return Err(format!("state probabilities must have {} entries", n));
         }
-        // IME切り替え時にEWMAをリセット: (低Friction, 高Engagement) = Flow領域
-        match self.axes_ewma.lock() {
-            Ok(mut e) => *e = (0.0, 1.0),
-            Err(poisoned) => *poisoned.into_inner() = (0.0, 1.0),
+        // 照合後に set_params() が状態の定義を変えた場合は復元しない
+        let mut superseded = false;
+        self.state.rcu(|s| {
+            superseded = s.states_epoch != states_epoch;
+            if superseded {
+                return EngineState::clone(s);
+            }
+            EngineState {
+                current_state_probs: checkpoint.current_state_probs.clone(),
+                display_probs: checkpoint.display_probs.clone(),
+                axes_ewma: checkpoint.axes_ewma,
+                dwell: None,
+                evidence: EvidenceState::default(),
+                reset_epoch: s.reset_epoch.wrapping_add(1),
+                ..EngineState::clone(s)
+            }
+        });
+        if superseded {
+            return Err("states changed while restoring".to_string());
         }
+        Ok(())
+    }
+
+    /// 状態名の並び (確率ベクトルのインデックス順)
+    pub fn state_names(&self) -> Vec<String> {
+        self.params()
+            .hmm
+            .states
+            .iter()
+            .map(|s| s.name.clone())
+            .collect()
+    }
+
+    /// 在席状態を差し替える (`PresenceTracker` が離席・復帰時に呼ぶ)
+    pub fn set_presence(&self, presence: PresenceStatus) {
+        self.state.rcu(|s| EngineState {
+            presence,
+            ..EngineState::clone(s)
+        });
+    }
+
+    /// 現在の在席状態 (ロックを取らない)
+    pub fn presence(&self) -> PresenceStatus {
+        self.state.load().presence
     }
 
     /// IMEポーズ中かどうかを安全に取得する
     pub fn get_paused(&self) -> bool {
-        match self.is_paused.lock() {
-            Ok(g) => *g,
-            Err(poisoned) => *poisoned.into_inner(),
-        }
+        self.state.load().is_paused
     }
 
     pub fn discretize_flight_time(&self, ft: f64) -> usize {
@@ -184,182 +897,296 @@ impl CognitiveStateEngine {
         }
     }
 
-    /// X軸 (Friction / 摩擦) と Y軸 (Engagement / 没入度) を算出する。
-    /// 返値はそれぞれ [0.0, 1.0] にクランプ済み。
-    ///
-    /// X (Friction) — 高いほど「つまずき」を表す。重み合計 = 1.0
-    ///   0.30 × φ(F3: 修正率)
-    ///   0.25 × φ(F6: 削除後停止率)
-    ///   0.25 × φ(F1: Flight Time)
-    ///   0.20 × φ(F5: ポーズ回数)
-    ///
-    /// Y (Engagement) — 高いほど「滑らかな出力」を表す。重み合計 = 1.0
-    ///   0.40 × φ(F4: バースト長)
-    ///   0.35 × (1 − φ(F1))   … 短いFT = 高エンゲージ
-    ///   0.25 × (1 − φ(F5))   … 少ないポーズ = 高エンゲージ
-    fn calculate_latent_axes(&self, features: &Features) -> (f64, f64) {
-        const BETA_F1: f64 = 250.0; // 標準FT中央値 (ms)
-        const BETA_F3: f64 = 0.10; // 標準修正率 (10%)
-        const BETA_F4: f64 = 2.0; // 標準バースト長 (文字数)
-        const BETA_F5: f64 = 3.0; // 標準ポーズ回数 (3回/30s)
-        const BETA_F6: f64 = 0.15; // 標準削除後停止率 (15%)
-
-        let phi1 = phi(features.f1_flight_time_median, BETA_F1);
-        let phi3 = phi(features.f3_correction_rate, BETA_F3);
-        let phi4 = phi(features.f4_burst_length, BETA_F4);
-        let phi5 = phi(features.f5_pause_count, BETA_F5);
-        let phi6 = phi(features.f6_pause_after_del_rate, BETA_F6);
-
-        // X: Friction (高いほど「つまずき」)  重み合計 = 1.0
-        let x = (0.30 * phi3 + 0.25 * phi6 + 0.25 * phi1 + 0.20 * phi5).clamp(0.0, 1.0);
-
-        // Y: Engagement (高いほど「滑らかな出力」)  重み合計 = 1.0
-        let y = (0.40 * phi4 + 0.35 * (1.0 - phi1) + 0.25 * (1.0 - phi5)).clamp(0.0, 1.0);
-
-        (x, y)
+    /// 書き手が計算した次のスナップショットを公開する。
+    /// 計算中に force_flow_state() が割り込んだ場合 (世代番号が変わった場合) は強制値を優先して
+    /// この更新を捨てる。set_paused()・set_presence() による変更は保持する。
+    fn publish(&self, base: &EngineState, next: EngineState) {
+        self.state.rcu(|s| {
+            if s.reset_epoch != base.reset_epoch {
+                EngineState::clone(s)
+            } else {
+                EngineState {
+                    is_paused: s.is_paused,
+                    presence: s.presence,
+                    ..next.clone()
+                }
+            }
+        });
     }
 
     /// B-5: HMM Update (2軸 Friction × Engagement、25+1ビン モデル)
-    pub fn update(&self, features: &Features, vk_code: Option<u32>) {
-        if self.get_paused() {
-            return;
+    ///
+    /// `timestamp` (Unix ms) はキー押下または沈黙ティックの時刻。連続時間遷移モデルでは
+    /// 直前の更新からの経過時間で A(Δt) = exp(Q·Δt) を求める。
+    ///
+    /// display_probs の argmax が `state_events.min_dwell_ms` 以上変わったままになった
+    /// 更新では、その遷移を返す。`debug.explain` 有効時は判断根拠も返す。
+    ///
+    /// 直前のスナップショットから次のスナップショットを作り、1 回の差し替えで公開する。
+    pub fn update(
+        &self,
+        features: &Features,
+        vk_code: Option<u32>,
+        timestamp: u64,
+    ) -> UpdateOutcome {
+        // スナップショットとパラメータはロックを取ってから読む (set_params() と同じ世代になる)
+        let mut writer = self.writer();
+        let state = self.snapshot();
+        if state.is_paused || state.presence.presence == Presence::Away {
+            return UpdateOutcome::default();
         }
+        writer.sync_reset(&state);
 
         // F1がゼロの場合はデータ不足のためスキップ
-        if features.f1_flight_time_median <= 0.0 {
-            return;
+        let f1_missing = features.f1_flight_time_median <= 0.0;
+        let mut evidence = state.evidence;
+        evidence.record(f1_missing);
+        if f1_missing {
+            self.publish(
+                &state,
+                EngineState {
+                    evidence,
+                    ..EngineState::clone(&state)
+                },
+            );
+            return UpdateOutcome::default();
         }
 
-        // --- Backspace Streak Logic ---
-        // 5回以上の連続Backspaceを「大きな修正＝Stuck」とする安全装置
-        let streak = match self.backspace_streak.lock() {
-            Ok(mut s) => {
-                if vk_code == Some(0x08) {
-                    // VK_BACK
-                    *s += 1;
-                } else if vk_code.is_some() {
-                    *s = 0;
-                }
-                *s
-            }
-            Err(poisoned) => {
-                let mut s = poisoned.into_inner();
-                if vk_code == Some(0x08) {
-                    *s += 1;
-                } else if vk_code.is_some() {
-                    *s = 0;
-                }
-                *s
-            }
-        };
-        let apply_backspace_penalty = streak >= 5;
-
-        let (raw_x, raw_y) = self.calculate_latent_axes(features);
+        let params = self.params();
+        let w = &mut *writer;
 
-        // EWMA平滑化 (α = 0.3): 各軸を独立に平滑化
-        // s_t = 0.3 * raw_t + 0.7 * s_{t-1}
-        let (x, y) = match self.axes_ewma.lock() {
-            Ok(mut ewma) => {
-                ewma.0 = 0.3 * raw_x + 0.7 * ewma.0;
-                ewma.1 = 0.3 * raw_y + 0.7 * ewma.1;
-                (ewma.0, ewma.1)
-            }
-            Err(poisoned) => {
-                let mut ewma = poisoned.into_inner();
-                ewma.0 = 0.3 * raw_x + 0.7 * ewma.0;
-                ewma.1 = 0.3 * raw_y + 0.7 * ewma.1;
-                (ewma.0, ewma.1)
-            }
+        // φ の β: personal 指定かつキャリブレーション完了後のみ個人ベースライン
+        let personal_beta = match w.calibration.active_source(&params.calibration) {
+            BaselineSource::Personal => Some(w.calibration.beta.clone()),
+            BaselineSource::Population => None,
+        };
+        let axes = match &personal_beta {
+            Some(beta) => Cow::Owned(AxisParams {
+                beta: beta.clone(),
+                ..params.axes.clone()
+            }),
+            None => Cow::Borrowed(&params.axes),
         };
 
-        // (X, Y) → 5×5 グリッド → 観測インデックス [0..24]
-        let x_bin = (x * 5.0).floor().min(4.0) as usize;
-        let y_bin = (y * 5.0).floor().min(4.0) as usize;
-        let mut obs = x_bin * 5 + y_bin;
+        let obs = w
+            .encoder
+            .encode(&axes, &params.overrides, features, timestamp);
+        let override_rule = obs.override_rule.map(|i| &params.overrides[i]);
 
-        // Backspace Streak Override → ペナルティビン (obs=25)
-        // 高Friction・低Engagementの極端ケースとして強制マップ
-        if apply_backspace_penalty {
-            obs = 25;
-        }
+        let input = EstimatorInput {
+            params: &params,
+            features,
+            observation: &obs,
+            prior: &state.current_state_probs,
+        };
+        let new_probs = w.estimator.estimate(&input);
+        let explained = params
+            .debug
+            .explain
+            .then(|| (w.estimator.name(), w.estimator.last_terms()));
 
-        // A-3: Mutex ポイズニング対策
-        let mut current = match self.current_state_probs.lock() {
-            Ok(g) => g,
-            Err(poisoned) => poisoned.into_inner(),
+        // ── Hysteresis Layer ──────────────────────────────────────────────
+        // display_probs をヒステリシス方式 (既定: 遅い EMA) で更新し、
+        // ウィンドウリセット時の Cold-Start 瞬間遷移 (Stuck→Flow in 1ms) を防ぐ。
+        let input = HysteresisInput {
+            params: &params.hysteresis,
+            raw: &new_probs,
+            display: &state.display_probs,
+            override_alpha: override_rule
+                .map(|r| r.display_alpha.unwrap_or(params.hysteresis.penalty_alpha)),
+            timestamp,
         };
+        let display = w.hysteresis.apply(&input);
 
-        let old_probs = *current;
-        let mut new_probs = [0.0; 3];
-        let n_states = 3;
+        let transition = w.transitions.observe(
+            &display,
+            &params.hmm,
+            timestamp,
+            params.state_events.min_dwell_ms,
+        );
 
-        // Forward Algorithm Step
-        // ε-floor: 放射確率の最小値を保証し、単一観測で状態確率が完全に0になることを防ぐ。
-        // 0.04 (旧: 0.01) に引き上げることで「段階的天井」クラスタリングを緩和する。
-        // 最大 p ≈ 0.88–0.90 程度に収まり、状態間の確率変化が滑らかになる。
-        const EMISSION_FLOOR: f64 = 0.04;
+        // 確信度の高い Flow (calibration.state) 中の打鍵で個人ベースラインを学習する
+        // (沈黙ティックは合成値なので除外)
+        let calibrating = params
+            .hmm
+            .state_index(&params.calibration.state)
+            .is_some_and(|i| display[i] >= params.calibration.flow_threshold);
+        if vk_code.is_some() && calibrating {
+            w.calibration
+                .observe(&params.calibration, &params.axes.beta, features, timestamp);
+        }
 
-        let mut sum_prob = 0.0;
+        let explanation = explained.map(|(estimator, terms)| UpdateExplanation {
+            timestamp,
+            vk_code,
+            contributions: AxisBreakdown::compute(&axes, &w.encoder.normalizers, features),
+            raw_axes: obs.raw_axes,
+            axes: obs.axes,
+            x_bin: obs.x_bin,
+            y_bin: obs.y_bin,
+            obs: obs.index,
+            override_rule: override_rule.map(|r| r.name.clone()),
+            estimator,
+            transition_mass: terms.as_ref().map(|t| t.transition_mass.clone()),
+            emission_likelihood: terms.map(|t| t.emission_likelihood),
+            raw_probs: new_probs.clone(),
+            display_probs: display.clone(),
+        });
 
-        for j in 0..n_states {
-            let mut trans_sum = 0.0;
-            for i in 0..n_states {
-                let t_prob = self.transitions[i * n_states + j];
-                trans_sum += old_probs[i] * t_prob;
-            }
+        // 正規化の移動統計 (z-score) は打鍵による特徴量だけで進める
+        if vk_code.is_some() {
+            w.encoder.observe_features(&axes.beta, features);
+        }
 
-            // 3 states × 26 bins: index = j * 26 + obs
-            let e_prob = self.emissions[j * 26 + obs] + EMISSION_FLOOR;
-            new_probs[j] = trans_sum * e_prob;
-            sum_prob += new_probs[j];
+        self.publish(
+            &state,
+            EngineState {
+                current_state_probs: new_probs,
+                display_probs: display,
+                axes_ewma: obs.axes,
+                override_rule: obs.override_rule,
+                is_paused: state.is_paused,
+                presence: state.presence,
+                dwell: w.estimator.dwell_estimate(&params),
+                last_explanation: explanation.clone(),
+                personal_beta: w.calibration.beta.clone(),
+                calibration_samples: w.calibration.samples,
+                evidence,
+                reset_epoch: state.reset_epoch,
+                states_epoch: state.states_epoch,
+            },
+        );
+
+        UpdateOutcome {
+            transition,
+            explanation,
+            personal_beta,
         }
+    }
 
-        // Normalize
-        if sum_prob > 0.0 {
-            for j in 0..n_states {
-                new_probs[j] /= sum_prob;
-            }
+    /// `debug.explain` 有効時、直前の更新の判断根拠を返す (デバッグパネル用)
+    pub fn last_explanation(&self) -> Option<UpdateExplanation> {
+        self.state.load().last_explanation.clone()
+    }
+
+    /// HSMM 有効時、現在最も確率の高い状態の期待経過時間・期待残り時間を返す。
+    /// HSMM 無効時・更新前・HMM 以外の推定器では None。
+    pub fn dwell_estimate(&self) -> Option<DwellEstimate> {
+        if !self.params.load().hsmm.enabled {
+            return None;
         }
-        // 合計が0になった場合は以前の確率を維持する (フォールバック)
+        self.state.load().dwell.clone()
+    }
 
-        *current = new_probs;
+    /// 現在の信念のエントロピー・上位 2 状態の差・raw と display の乖離・証拠不足フラグ
+    pub fn uncertainty(&self) -> StateUncertainty {
+        let params = self.params();
+        // raw・display・証拠量は同じスナップショットから読む
+        let state = self.snapshot();
+        let raw = &state.current_state_probs;
+        let display = &state.display_probs;
+        let evidence = state.evidence;
 
-        // ── Hysteresis Layer ──────────────────────────────────────────────
-        // display_probs に遅い EMA を適用し、ウィンドウリセット時の
-        // Cold-Start 瞬間遷移 (Stuck→Flow in 1ms) を防ぐ。
-        //
-        // α=0.25 (通常): 時定数 ≈ 4 更新 ≈ 4 秒
-        // α=0.50 (ペナルティ): Backspace 連続時は素早く Stuck に収束
-        let display_alpha = if apply_backspace_penalty { 0.50 } else { 0.25 };
-
-        let mut display = match self.display_probs.lock() {
-            Ok(g) => g,
-            Err(poisoned) => poisoned.into_inner(),
+        let low_evidence_reason = if state.is_paused {
+            Some(LowEvidence::Paused)
+        } else if state.presence.presence == Presence::Away {
+            Some(LowEvidence::Away)
+        } else if evidence.f1_missing {
+            Some(LowEvidence::NoFlightTime)
+        } else if evidence.updates < params.uncertainty.cold_start_updates {
+            Some(LowEvidence::ColdStart)
+        } else {
+            None
         };
-        let mut disp_sum = 0.0;
-        for i in 0..n_states {
-            display[i] = display_alpha * new_probs[i] + (1.0 - display_alpha) * display[i];
-            disp_sum += display[i];
-        }
-        if disp_sum > 0.0 {
-            for v in display.iter_mut() {
-                *v /= disp_sum;
-            }
+
+        StateUncertainty {
+            entropy: normalized_entropy(display),
+            raw_entropy: normalized_entropy(raw),
+            margin: top2_margin(display),
+            divergence: total_variation(raw, display),
+            low_evidence: low_evidence_reason.is_some(),
+            low_evidence_reason,
+            evidence_updates: evidence.updates,
         }
     }
 
-    pub fn get_current_state(&self) -> HashMap<CognitiveState, f64> {
+    /// (状態名, 確率) の組 (`states` の並び)
+    pub fn get_current_state(&self) -> Vec<(String, f64)> {
         // display_probs (ヒステリシス層) を返す。
         // 生の current_state_probs は瞬間値; display_probs は遅い EMA により
         // 短期スパイクを平滑化した値。UI・ログはこちらを使用する。
-        let probs = match self.display_probs.lock() {
-            Ok(g) => g,
-            Err(poisoned) => poisoned.into_inner(),
-        };
-        let mut map = HashMap::new();
-        map.insert(CognitiveState::Flow, probs[0]);
-        map.insert(CognitiveState::Incubation, probs[1]);
-        map.insert(CognitiveState::Stuck, probs[2]);
-        map
+        let params = self.params();
+        let state = self.snapshot();
+        params
+            .hmm
+            .states
+            .iter()
+            .zip(state.display_probs.iter())
+            .map(|(s, &p)| (s.name.clone(), p))
+            .collect()
+    }
+}
+
+/// 個人ベースラインをプロファイルファイルへ保存する (学習前のプロファイルは書き出さない)
+fn save_profile(profile: &CalibrationProfile) {
+    if profile.samples == 0 {
+        return;
+    }
+    let path = calibration::profile_path(&profile.profile);
+    match profile.save(&path) {
+        Ok(()) => tracing::info!(
+            "Calibration profile '{}' saved ({} samples)",
+            profile.profile,
+            profile.samples
+        ),
+        Err(e) => tracing::warn!("Calibration profile not saved: {}", e),
+    }
+}
+
+impl EvidenceState {
+    fn record(&mut self, f1_missing: bool) {
+        self.f1_missing = f1_missing;
+        if !f1_missing {
+            self.updates = self.updates.saturating_add(1);
+        }
+    }
+}
+
+/// Forward Algorithm の 1 ステップ (予測 + 観測更新 + 正規化)。
+///
+/// ```text
+/// π'_j = ( Σ_i π_i · A[i,j] ) · e_j        e_j = B[j, obs] + ε  (離散モデル)
+/// π'   ← π' / Σ_j π'_j
+/// ```
+///
+/// `transitions` は `hmm::transition_matrix()`、`likelihoods` は `hmm::emission_likelihoods()`
+/// の出力 (遷移・放射モデルに依存しない)。
+/// 合計が 0 になった場合は以前の確率を維持する (フォールバック)。
+pub fn forward_step(
+    transitions: &TransitionMatrix,
+    probs: &[f64],
+    likelihoods: &[f64],
+) -> Vec<f64> {
+    let mut new_probs = vec![0.0; probs.len()];
+    let mut sum_prob = 0.0;
+
+    for (j, p) in new_probs.iter_mut().enumerate() {
+        let trans_sum: f64 = probs
+            .iter()
+            .zip(transitions.iter())
+            .map(|(pi, row)| pi * row[j])
+            .sum();
+
+        *p = trans_sum * likelihoods[j];
+        sum_prob += *p;
+    }
+
+    if sum_prob > 0.0 {
+        for p in new_probs.iter_mut() {
+            *p /= sum_prob;
+        }
+        new_probs
+    } else {
+        probs.to_vec()
     }
 }
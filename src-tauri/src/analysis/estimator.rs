@@ -0,0 +1,420 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use ort::session::Session;
+use ort::value::Tensor;
+use serde::Serialize;
+
+use crate::analysis::engine::{forward_step, Observation};
+use crate::analysis::features::Features;
+use crate::analysis::hmm::{emission_likelihoods, transition_matrix};
+use crate::analysis::hsmm::{DwellEstimate, HsmmFilter};
+use crate::analysis::params::{
+    EngineParams, EnsembleParams, EstimatorBackend, EstimatorParams, FusionRule, OnnxOutput,
+    OnnxParams, StateDef,
+};
+
+/// `StateEstimator::estimate()` への 1 更新分の入力
+pub struct EstimatorInput<'a> {
+    pub params: &'a EngineParams,
+    pub features: &'a Features,
+    /// `ObservationEncoder` が生成した観測 (軸・ビン・ペナルティ・Δt)
+    pub observation: &'a Observation,
+    /// 直前の状態確率 (ヒステリシス前の current_state_probs)
+    pub prior: &'a [f64],
+}
+
+/// 直前の推定ステップの内訳 (`UpdateExplanation` 用)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepTerms {
+    /// 観測更新前の予測確率 Σ_i π_i · A[i,j] (HSMM は滞在時間ハザード込み)
+    pub transition_mass: Vec<f64>,
+    /// 放射尤度 e_j
+    pub emission_likelihood: Vec<f64>,
+}
+
+/// 特徴量・潜在軸から状態確率を推定するバックエンド。
+///
+/// `CognitiveStateEngine::update()` が観測の生成とヒステリシス層を受け持ち、
+/// その間の「観測 → 状態確率」だけを差し替えられるようにする。
+/// 実装は分析スレッドから `Mutex` 越しに呼ばれるため `Send` が必要。
+pub trait StateEstimator: Send {
+    fn name(&self) -> &'static str;
+
+    /// 正規化済みの状態確率 (`states` の並び、prior と同じ長さ) を返す
+    fn estimate(&mut self, input: &EstimatorInput) -> Vec<f64>;
+
+    /// 信念が外部から `probs` に上書きされたとき (IME 切り替え等) に内部状態を捨てる
+    fn reset(&mut self, _probs: &[f64]) {}
+
+    /// 直前の `estimate()` の予測確率・放射尤度 (対応しないバックエンドは None)
+    fn last_terms(&self) -> Option<StepTerms> {
+        None
+    }
+
+    /// 現在状態の滞在時間推定 (対応しないバックエンドは None)
+    fn dwell_estimate(&self, _params: &EngineParams) -> Option<DwellEstimate> {
+        None
+    }
+}
+
+/// 設定に従って推定器を構築する。`states` はエンジン本体の状態の定義
+/// (アンサンブルのメンバーが別のパラメータファイルを使う場合の整合性検査に用いる)
+pub fn build_estimator(
+    params: &EstimatorParams,
+    states: &[StateDef],
+) -> Result<Box<dyn StateEstimator>, String> {
+    match params.backend {
+        EstimatorBackend::Hmm => Ok(Box::new(HmmEstimator::default())),
+        EstimatorBackend::Onnx => Ok(Box::new(OnnxEstimator::load(&params.onnx)?)),
+        EstimatorBackend::Ensemble => Ok(Box::new(EnsembleEstimator::build(
+            &params.ensemble,
+            states,
+        )?)),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// HMM / HSMM
+// ---------------------------------------------------------------------------
+
+/// 既定の推定器: HMM 前向きフィルタ (`hsmm.enabled` 時は HSMM フィルタ)
+#[derive(Debug, Clone, Default)]
+pub struct HmmEstimator {
+    // HSMM belief over (state, time-in-state). Empty while the HSMM is disabled;
+    // re-seeded from the prior when it is (re-)enabled.
+    hsmm: HsmmFilter,
+    last: Option<StepTerms>,
+}
+
+impl StateEstimator for HmmEstimator {
+    fn name(&self) -> &'static str {
+        "hmm"
+    }
+
+    fn estimate(&mut self, input: &EstimatorInput) -> Vec<f64> {
+        let params = input.params;
+        let obs = input.observation;
+        let likelihoods = emission_likelihoods(&params.hmm, obs);
+
+        let (probs, transition_mass) = if params.hsmm.enabled {
+            // HSMM: 状態ごとの滞在時間分布によるハザードで遷移させる
+            if self.hsmm.is_empty() {
+                self.hsmm = HsmmFilter::from_probs(input.prior);
+            }
+            let dt = obs.dt_secs.unwrap_or(params.hmm.initial_step_secs);
+            let probs = self.hsmm.step(&params.hmm, &params.hsmm, dt, &likelihoods);
+            // 事後確率 ∝ 予測確率 · e_j より、予測確率を逆算する
+            let predicted = probs
+                .iter()
+                .zip(likelihoods.iter())
+                .map(|(&p, &e)| if e > 0.0 { p / e } else { 0.0 })
+                .collect();
+            (probs, normalized(predicted))
+        } else {
+            if !self.hsmm.is_empty() {
+                self.hsmm = HsmmFilter::default();
+            }
+            let transitions = transition_matrix(&params.hmm, obs.dt_secs);
+            let mut predicted = vec![0.0; input.prior.len()];
+            for (pi, row) in input.prior.iter().zip(transitions.iter()) {
+                for (p, a) in predicted.iter_mut().zip(row.iter()) {
+                    *p += pi * a;
+                }
+            }
+            (
+                forward_step(&transitions, input.prior, &likelihoods),
+                predicted,
+            )
+        };
+
+        self.last = Some(StepTerms {
+            transition_mass,
+            emission_likelihood: likelihoods,
+        });
+        probs
+    }
+
+    fn last_terms(&self) -> Option<StepTerms> {
+        self.last.clone()
+    }
+
+    fn reset(&mut self, _probs: &[f64]) {
+        self.hsmm = HsmmFilter::default();
+    }
+
+    fn dwell_estimate(&self, params: &EngineParams) -> Option<DwellEstimate> {
+        if !params.hsmm.enabled {
+            return None;
+        }
+        self.hsmm.dwell_estimate(&params.hmm, &params.hsmm)
+    }
+}
+
+fn normalized(mut p: Vec<f64>) -> Vec<f64> {
+    let sum: f64 = p.iter().sum();
+    if sum > 0.0 {
+        for v in p.iter_mut() {
+            *v /= sum;
+        }
+    }
+    p
+}
+
+// ---------------------------------------------------------------------------
+// ONNX Runtime
+// ---------------------------------------------------------------------------
+
+/// ONNX モデルへの入力次元
+pub const ONNX_INPUT_DIM: usize = 10;
+
+/// 学習済み分類器を ONNX Runtime (ort, load-dynamic) で実行する推定器。
+/// 入出力の形式は `OnnxParams` を参照。推論に失敗した更新では直前の確率を維持する。
+pub struct OnnxEstimator {
+    session: Session,
+    output: OnnxOutput,
+}
+
+impl OnnxEstimator {
+    pub fn load(params: &OnnxParams) -> Result<Self, String> {
+        if !params.runtime_path.is_empty() {
+            // 2 回目以降の呼び出しでは既存の環境がそのまま使われる
+            ort::init_from(&params.runtime_path)
+                .map_err(|e| format!("failed to load ONNX Runtime: {}", e))?
+                .commit();
+        }
+
+        let path = Path::new(&params.model_path);
+        let session = Session::builder()
+            .and_then(|b| b.commit_from_file(path))
+            .map_err(|e| format!("failed to load ONNX model {:?}: {}", path, e))?;
+
+        if session.inputs().len() != 1 || session.outputs().is_empty() {
+            return Err(format!(
+                "ONNX model {:?} must have exactly one input and at least one output",
+                path
+            ));
+        }
+        tracing::info!("ONNX estimator loaded from {:?}", path);
+
+        Ok(Self {
+            session,
+            output: params.output,
+        })
+    }
+
+    fn run(&mut self, input: &EstimatorInput) -> Result<Vec<f64>, String> {
+        let f = input.features;
+        let obs = input.observation;
+        let data: Vec<f32> = [
+            f.f1_flight_time_median,
+            f.f2_flight_time_variance,
+            f.f3_correction_rate,
+            f.f4_burst_length,
+            f.f5_pause_count,
+            f.f6_pause_after_del_rate,
+            obs.raw_axes.0,
+            obs.raw_axes.1,
+            obs.axes.0,
+            obs.axes.1,
+        ]
+        .iter()
+        .map(|&v| v as f32)
+        .collect();
+
+        let tensor =
+            Tensor::from_array(([1usize, ONNX_INPUT_DIM], data)).map_err(|e| e.to_string())?;
+        let outputs = self
+            .session
+            .run(ort::inputs![tensor])
+            .map_err(|e| e.to_string())?;
+        let (_, values) = outputs[0]
+            .try_extract_tensor::<f32>()
+            .map_err(|e| e.to_string())?;
+        // 出力は状態数 N 以上 (先頭 N 個を `states` の並びとして用いる)
+        let n = input.prior.len();
+        if values.len() < n {
+            return Err(format!("expected {} outputs, got {}", n, values.len()));
+        }
+
+        let mut probs: Vec<f64> = values.iter().take(n).map(|&v| v as f64).collect();
+        if self.output == OnnxOutput::Logits {
+            let max = probs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            for p in probs.iter_mut() {
+                *p = (*p - max).exp();
+            }
+        }
+
+        let sum: f64 = probs.iter().map(|p| p.max(0.0)).sum();
+        if !sum.is_finite() || sum <= 0.0 {
+            return Err("model output is not a valid distribution".to_string());
+        }
+        for p in probs.iter_mut() {
+            *p = p.max(0.0) / sum;
+        }
+        Ok(probs)
+    }
+}
+
+impl StateEstimator for OnnxEstimator {
+    fn name(&self) -> &'static str {
+        "onnx"
+    }
+
+    fn estimate(&mut self, input: &EstimatorInput) -> Vec<f64> {
+        match self.run(input) {
+            Ok(probs) => probs,
+            Err(e) => {
+                tracing::warn!("ONNX inference failed: {}", e);
+                input.prior.to_vec()
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Ensemble
+// ---------------------------------------------------------------------------
+
+struct EnsembleMember {
+    estimator: Box<dyn StateEstimator>,
+    /// `params_path` から読み込んだパラメータ (None = エンジン本体のパラメータ)
+    params: Option<Arc<EngineParams>>,
+    weight: f64,
+    /// メンバー自身の信念 (最初の更新で融合前の prior から初期化)
+    probs: Option<Vec<f64>>,
+}
+
+/// 複数の推定器を同じ特徴量・観測で並走させ、`FusionRule` で出力を融合する推定器。
+/// 各メンバーは融合結果ではなく自身の直前の出力を prior として進む。
+pub struct EnsembleEstimator {
+    fusion: FusionRule,
+    members: Vec<EnsembleMember>,
+}
+
+impl EnsembleEstimator {
+    pub fn build(params: &EnsembleParams, states: &[StateDef]) -> Result<Self, String> {
+        let mut members = Vec::with_capacity(params.members.len());
+        for (i, m) in params.members.iter().enumerate() {
+            if m.backend == EstimatorBackend::Ensemble {
+                return Err(format!("ensemble member {} must not be an ensemble", i));
+            }
+            let estimator = build_estimator(
+                &EstimatorParams {
+                    backend: m.backend,
+                    onnx: m.onnx.clone(),
+                    ensemble: EnsembleParams::default(),
+                },
+                states,
+            )
+            .map_err(|e| format!("ensemble member {}: {}", i, e))?;
+            let member_params = if m.params_path.is_empty() {
+                None
+            } else {
+                let p = EngineParams::load(Path::new(&m.params_path))
+                    .map_err(|e| format!("ensemble member {}: {}", i, e))?;
+                // 融合は状態ごとに行うため、状態の定義 (数・並び) が一致している必要がある
+                if p.hmm.states != states {
+                    return Err(format!(
+                        "ensemble member {}: states in {:?} differ from the engine's states",
+                        i, m.params_path
+                    ));
+                }
+                Some(Arc::new(p))
+            };
+            members.push(EnsembleMember {
+                estimator,
+                params: member_params,
+                weight: m.weight,
+                probs: None,
+            });
+        }
+        if members.is_empty() {
+            return Err("ensemble has no members".to_string());
+        }
+        Ok(Self {
+            fusion: params.fusion,
+            members,
+        })
+    }
+}
+
+impl StateEstimator for EnsembleEstimator {
+    fn name(&self) -> &'static str {
+        "ensemble"
+    }
+
+    fn estimate(&mut self, input: &EstimatorInput) -> Vec<f64> {
+        let mut outputs = Vec::with_capacity(self.members.len());
+        for m in self.members.iter_mut() {
+            let prior = m.probs.take().unwrap_or_else(|| input.prior.to_vec());
+            let member_input = EstimatorInput {
+                params: m.params.as_deref().unwrap_or(input.params),
+                features: input.features,
+                observation: input.observation,
+                prior: &prior,
+            };
+            let probs = m.estimator.estimate(&member_input);
+            m.probs = Some(probs.clone());
+            outputs.push((m.weight, probs));
+        }
+        fuse(self.fusion, &outputs).unwrap_or_else(|| input.prior.to_vec())
+    }
+
+    fn reset(&mut self, probs: &[f64]) {
+        for m in self.members.iter_mut() {
+            m.estimator.reset(probs);
+            m.probs = Some(probs.to_vec());
+        }
+    }
+
+    /// 滞在時間推定を返せる最初のメンバーの値
+    fn dwell_estimate(&self, params: &EngineParams) -> Option<DwellEstimate> {
+        self.members.iter().find_map(|m| {
+            m.estimator
+                .dwell_estimate(m.params.as_deref().unwrap_or(params))
+        })
+    }
+}
+
+// PoE で 1 つのメンバーの 0 が他の全メンバーを打ち消さないための下限
+const POE_FLOOR: f64 = 1e-6;
+
+/// (信頼度, 確率) の組を融合する。重みの合計が 0 の場合・状態数が揃わない場合などは None。
+pub fn fuse(rule: FusionRule, outputs: &[(f64, Vec<f64>)]) -> Option<Vec<f64>> {
+    let n = outputs.first()?.1.len();
+    if outputs.iter().any(|(_, p)| p.len() != n) {
+        return None;
+    }
+    let mut fused = vec![0.0; n];
+    match rule {
+        FusionRule::WeightedAverage => {
+            for (w, p) in outputs {
+                for (f, v) in fused.iter_mut().zip(p.iter()) {
+                    *f += w * v;
+                }
+            }
+        }
+        FusionRule::ProductOfExperts => {
+            // 対数領域で Σ_k w_k · ln p_k,j を求め、最大値を引いてから exp
+            for (w, p) in outputs {
+                for (f, v) in fused.iter_mut().zip(p.iter()) {
+                    *f += w * v.max(POE_FLOOR).ln();
+                }
+            }
+            let max = fused.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            for f in fused.iter_mut() {
+                *f = (*f - max).exp();
+            }
+        }
+    }
+
+    let sum: f64 = fused.iter().sum();
+    if !sum.is_finite() || sum <= 0.0 {
+        return None;
+    }
+    for f in fused.iter_mut() {
+        *f /= sum;
+    }
+    Some(fused)
+}
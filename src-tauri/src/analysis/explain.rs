@@ -0,0 +1,36 @@
+use serde::Serialize;
+
+use crate::analysis::engine::AxisBreakdown;
+
+/// 1 回の `update()` の判断根拠 (`debug.explain` 有効時のみ生成)。
+///
+/// 特徴量 → φ → 軸への寄与 → EWMA → ビン → 推定器 → ヒステリシスの各段の値を並べ、
+/// 予想外の Stuck スパイクなどがどの段で生じたかを後から追えるようにする。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UpdateExplanation {
+    pub timestamp: u64,
+    /// キー押下の仮想キーコード (沈黙ティックは None)
+    pub vk_code: Option<u32>,
+    /// φ 正規化後の特徴量と、X / Y への重み付き寄与
+    pub contributions: AxisBreakdown,
+    /// EWMA 前の (X, Y)
+    pub raw_axes: (f64, f64),
+    /// EWMA 後の (X, Y)
+    pub axes: (f64, f64),
+    pub x_bin: usize,
+    pub y_bin: usize,
+    /// 観測ビン [0..25]
+    pub obs: usize,
+    /// 一致したオーバーライド規則の名前
+    pub override_rule: Option<String>,
+    /// 使用した推定器 ("hmm" | "onnx" | "ensemble")
+    pub estimator: &'static str,
+    /// 状態ごとの予測確率 Σ_i π_i · A[i,j] (HMM / HSMM のみ)。状態ごとの配列は `states` の並び
+    pub transition_mass: Option<Vec<f64>>,
+    /// 状態ごとの放射尤度 e_j (HMM / HSMM のみ)
+    pub emission_likelihood: Option<Vec<f64>>,
+    /// 推定器の出力 (ヒステリシス前)
+    pub raw_probs: Vec<f64>,
+    /// ヒステリシス後の display_probs
+    pub display_probs: Vec<f64>,
+}
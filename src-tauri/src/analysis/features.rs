@@ -1,4 +1,10 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+
+use crate::analysis::normalize::{Normalizer, PhiNormalizer};
+use crate::analysis::overrides::is_modifier;
+use crate::analysis::params::{FeatureParams, DIGRAPH_HISTORY};
+use crate::analysis::persistence::{DigraphCheckpoint, ExtractorCheckpoint};
+use crate::analysis::rolling::{RollingWindow, WINDOW_MS};
 
 /// B-1: 6特徴量を格納する構造体
 #[derive(Debug, Clone)]
@@ -15,6 +21,12 @@ pub struct Features {
     pub f5_pause_count: f64,
     /// F6: 削除後停止率 = BS/Del直後2秒以上停止する割合
     pub f6_pause_after_del_rate: f64,
+    /// F7: Hold Time 中央値 (ms) = 同じキーの押下→解放 (直近15打鍵、修飾キーを除く)
+    pub f7_hold_time_median: f64,
+    /// F8: Hold Time 分散 (直近30秒)
+    pub f8_hold_time_variance: f64,
+    /// F9: ダイグラフ減速率 = 直近30秒の押下→押下間隔 / 同じキー対の本人の通常値 (中央値、1.0 = 普段どおり)
+    pub f9_digraph_slowdown: f64,
 }
 
 impl Default for Features {
@@ -26,23 +38,68 @@ impl Default for Features {
             f4_burst_length: 0.0,
             f5_pause_count: 0.0,
             f6_pause_after_del_rate: 0.0,
+            f7_hold_time_median: 0.0,
+            f8_hold_time_variance: 0.0,
+            f9_digraph_slowdown: 1.0,
         }
     }
 }
 
 /// B-2: 個人ベースライン正規化関数 φ(x, β) = clamp((x − β) / (κ · β), 0.0, 1.0)
 /// κ = 2.0
+/// (特徴量ごとの正規化方式の既定。他の方式は `normalize.rs`)
 pub fn phi(x: f64, beta: f64) -> f64 {
-    const KAPPA: f64 = 2.0;
-    if beta <= 0.0 {
-        return 0.0;
-    }
-    ((x - beta) / (KAPPA * beta)).clamp(0.0, 1.0)
+    PhiNormalizer::default().normalize(x, beta)
 }
 
-// Virtual key codes
-const VK_BACK: u32 = 0x08;
-const VK_DELETE: u32 = 0x2E;
+/// F7 の中央値に用いる直近のホールド時間の数
+const HOLD_MEDIAN_WINDOW: usize = 15;
+
+/// 保持するダイグラフの最大数。超えた場合は最も長く打たれていないキー対を追い出す
+const MAX_DIGRAPHS: usize = 1024;
+
+/// F9 の中央値に用いる直近のダイグラフ減速率の最大数 (30 秒で超えるのは 8 打鍵/秒以上の場合のみ)
+const MAX_DIGRAPH_RATIOS: usize = 256;
+
+/// 1 つのキー対の直近 `DIGRAPH_HISTORY` 回の押下→押下間隔 (固定長のリングバッファ)
+#[derive(Debug, Clone, Copy)]
+struct DigraphHistory {
+    key: (u32, u32),
+    latencies: [u64; DIGRAPH_HISTORY],
+    len: usize,
+    // Index of the oldest latency
+    start: usize,
+    // `digraph_clock` when this pair was last typed (least recently used pair is evicted)
+    last_used: u64,
+}
+
+impl DigraphHistory {
+    fn new(key: (u32, u32)) -> Self {
+        Self {
+            key,
+            latencies: [0; DIGRAPH_HISTORY],
+            len: 0,
+            start: 0,
+            last_used: 0,
+        }
+    }
+
+    /// 古い順の間隔
+    fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..self.len).map(move |i| self.latencies[(self.start + i) % DIGRAPH_HISTORY])
+    }
+
+    /// 間隔を追加する。満杯なら最も古い間隔を上書きする
+    fn push(&mut self, latency: u64) {
+        if self.len < DIGRAPH_HISTORY {
+            self.latencies[(self.start + self.len) % DIGRAPH_HISTORY] = latency;
+            self.len += 1;
+        } else {
+            self.latencies[self.start] = latency;
+            self.start = (self.start + 1) % DIGRAPH_HISTORY;
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct InputEvent {
@@ -52,27 +109,63 @@ pub struct InputEvent {
 }
 
 pub struct FeatureExtractor {
-    buffer: VecDeque<InputEvent>,
+    window: RollingWindow,
     capacity: usize,
     last_release_time: Option<u64>,
     flight_times: VecDeque<u64>, // Store recent flight times for median calc
+    // vk → press timestamp of keys currently held down (first press; auto-repeat keeps it)
+    pending_presses: HashMap<u32, u64>,
+    // Hold times of recent press→release pairs, oldest first (F7; F8 is kept by the window)
+    hold_times: VecDeque<u64>,
+    // (vk, timestamp) of the last non-modifier press (auto-repeat excluded)
+    last_press: Option<(u32, u64)>,
+    // (vk_a, vk_b) → slot in `digraphs`
+    digraph_slots: HashMap<(u32, u32), usize>,
+    // Recent press-to-press latencies per digraph; at most MAX_DIGRAPHS, allocated up front
+    digraphs: Vec<DigraphHistory>,
+    // Advanced on every recorded digraph; orders DigraphHistory::last_used
+    digraph_clock: u64,
+    // (press timestamp, latency / digraph baseline) of recent digraphs with enough history
+    digraph_ratios: VecDeque<(u64, f64)>,
+    params: FeatureParams,
 }
 
 impl FeatureExtractor {
     pub fn new(capacity: usize) -> Self {
+        Self::with_params(capacity, FeatureParams::default())
+    }
+
+    /// 閾値を外部パラメータで指定して構築する
+    pub fn with_params(capacity: usize, params: FeatureParams) -> Self {
         Self {
-            buffer: VecDeque::with_capacity(capacity),
+            window: RollingWindow::new(
+                capacity,
+                params.pause_threshold_ms,
+                params.burst_threshold_ms,
+            ),
             capacity,
             last_release_time: None,
             flight_times: VecDeque::with_capacity(capacity), // Keep same size roughly
+            pending_presses: HashMap::new(),
+            hold_times: VecDeque::with_capacity(capacity),
+            last_press: None,
+            digraph_slots: HashMap::with_capacity(MAX_DIGRAPHS),
+            digraphs: Vec::with_capacity(MAX_DIGRAPHS),
+            digraph_clock: 0,
+            digraph_ratios: VecDeque::with_capacity(MAX_DIGRAPH_RATIOS),
+            params,
         }
     }
 
+    /// 閾値を差し替える (ホットリロード)。バッファ・フライトタイム履歴は保持する
+    pub fn set_params(&mut self, params: FeatureParams) {
+        self.window
+            .set_thresholds(params.pause_threshold_ms, params.burst_threshold_ms);
+        self.params = params;
+    }
+
     pub fn process_event(&mut self, event: InputEvent) {
-        if self.buffer.len() >= self.capacity {
-            self.buffer.pop_front();
-        }
-        self.buffer.push_back(event);
+        self.window.push(event);
 
         if event.is_press {
             if let Some(release_time) = self.last_release_time {
@@ -80,14 +173,84 @@ impl FeatureExtractor {
                     let flight_time = event.timestamp - release_time;
                     // Do NOT filter outliers for Stuck detection.
                     // Long pauses (>2000ms) are critical for detecting Stuck.
-                    if flight_time < 2000 {
+                    if flight_time < self.params.pause_threshold_ms {
                         self.add_flight_time(flight_time);
                     }
                 }
             }
+            // 押しっぱなしによるオートリピートは最初の押下時刻を保ち、ダイグラフにも数えない。
+            // max_hold_ms より古い押下は離しを取りこぼした残骸 (IME 変換中に離した等) とみなし、
+            // 新しい押下で置き換える
+            if !is_modifier(event.vk_code) {
+                let held = self
+                    .pending_presses
+                    .get(&event.vk_code)
+                    .is_some_and(|&pressed_at| {
+                        event.timestamp.saturating_sub(pressed_at) <= self.params.max_hold_ms
+                    });
+                if !held {
+                    self.pending_presses.insert(event.vk_code, event.timestamp);
+                    self.add_digraph(event.vk_code, event.timestamp);
+                }
+            }
         } else {
             self.last_release_time = Some(event.timestamp);
+            // 同じキーの押下と対にしてホールド時間を求める
+            if let Some(pressed_at) = self.pending_presses.remove(&event.vk_code) {
+                if event.timestamp >= pressed_at {
+                    let hold_time = event.timestamp - pressed_at;
+                    if hold_time <= self.params.max_hold_ms {
+                        self.add_hold_time(hold_time);
+                        self.window.set_hold_time(hold_time);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 保存用のチェックポイント (フライトタイム・ホールド時間・ダイグラフごとの間隔の履歴)
+    pub fn checkpoint(&self) -> ExtractorCheckpoint {
+        let mut digraphs: Vec<DigraphCheckpoint> = self
+            .digraphs
+            .iter()
+            .map(|d| DigraphCheckpoint {
+                from: d.key.0,
+                to: d.key.1,
+                latencies: d.iter().collect(),
+            })
+            .collect();
+        // 状態ファイルの差分が読めるようキー対の順に並べる
+        digraphs.sort_unstable_by_key(|d| (d.from, d.to));
+        ExtractorCheckpoint {
+            flight_times: self.flight_times.iter().copied().collect(),
+            hold_times: self.hold_times.iter().copied().collect(),
+            digraphs,
+        }
+    }
+
+    /// 保存したフライトタイム・ホールド時間・ダイグラフの履歴を復元する (ウォームスタート)。
+    /// キーバッファは復元しないため、F2–F6・F8・F9 は新しい入力から計算される
+    /// (復元したホールド時間は F7 にだけ用い、F8 の 30 秒ウィンドウには入らない)
+    pub fn restore(&mut self, checkpoint: &ExtractorCheckpoint) {
+        self.flight_times.clear();
+        for &ft in &checkpoint.flight_times {
+            self.add_flight_time(ft);
+        }
+        self.hold_times.clear();
+        for &hold in &checkpoint.hold_times {
+            self.add_hold_time(hold);
         }
+        self.digraph_slots.clear();
+        self.digraphs.clear();
+        for d in checkpoint.digraphs.iter().take(MAX_DIGRAPHS) {
+            let slot = self.digraph_slot((d.from, d.to));
+            let skip = d.latencies.len().saturating_sub(DIGRAPH_HISTORY);
+            for &latency in &d.latencies[skip..] {
+                self.digraphs[slot].push(latency);
+            }
+        }
+        self.digraph_ratios.clear();
+        self.last_press = None;
     }
 
     fn add_flight_time(&mut self, ft: u64) {
@@ -97,6 +260,110 @@ impl FeatureExtractor {
         self.flight_times.push_back(ft);
     }
 
+    fn add_hold_time(&mut self, hold_time: u64) {
+        if self.hold_times.len() >= self.capacity {
+            self.hold_times.pop_front();
+        }
+        self.hold_times.push_back(hold_time);
+    }
+
+    /// 直前の押下とのキー対に押下→押下間隔を記録する。
+    ///
+    /// 本人の通常値 (そのキー対の履歴の中央値) が `digraph_min_samples` 件以上から
+    /// 求まる場合は、記録する前に 間隔 / 通常値 を F9 用に残す。
+    /// ポーズ (`pause_threshold_ms` 以上) を挟んだ対は打鍵速度を表さないため数えない
+    fn add_digraph(&mut self, vk_code: u32, timestamp: u64) {
+        let previous = self.last_press.replace((vk_code, timestamp));
+        let Some((prev_vk, prev_ts)) = previous else {
+            return;
+        };
+        if timestamp < prev_ts {
+            return;
+        }
+        let latency = timestamp - prev_ts;
+        if latency >= self.params.pause_threshold_ms {
+            return;
+        }
+        let slot = self.digraph_slot((prev_vk, vk_code));
+        let history = &mut self.digraphs[slot];
+        if history.len >= self.params.digraph_min_samples {
+            let baseline = median(history.iter());
+            if baseline > 0.0 {
+                if self.digraph_ratios.len() >= MAX_DIGRAPH_RATIOS {
+                    self.digraph_ratios.pop_front();
+                }
+                self.digraph_ratios
+                    .push_back((timestamp, latency as f64 / baseline));
+            }
+        }
+        history.push(latency);
+    }
+
+    /// キー対の履歴のスロットを返し、最後に打たれた時刻を進める。
+    /// 無ければ作る。表が満杯なら最も長く打たれていないキー対のスロットを空にして使い回す
+    /// (割り当ては構築時に済ませてあり、打鍵ごとには行わない)
+    fn digraph_slot(&mut self, key: (u32, u32)) -> usize {
+        self.digraph_clock += 1;
+        let slot = match self.digraph_slots.get(&key) {
+            Some(&slot) => slot,
+            None if self.digraphs.len() < MAX_DIGRAPHS => {
+                self.digraphs.push(DigraphHistory::new(key));
+                self.digraph_slots.insert(key, self.digraphs.len() - 1);
+                self.digraphs.len() - 1
+            }
+            None => {
+                let slot = self
+                    .digraphs
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, d)| d.last_used)
+                    .map_or(0, |(i, _)| i);
+                self.digraph_slots.remove(&self.digraphs[slot].key);
+                self.digraphs[slot] = DigraphHistory::new(key);
+                self.digraph_slots.insert(key, slot);
+                slot
+            }
+        };
+        self.digraphs[slot].last_used = self.digraph_clock;
+        slot
+    }
+
+    /// F7: 直近 `HOLD_MEDIAN_WINDOW` 回のホールド時間の中央値 (データなしは 0)
+    pub fn calculate_hold_time_median(&self) -> f64 {
+        median(
+            self.hold_times
+                .iter()
+                .rev()
+                .take(HOLD_MEDIAN_WINDOW)
+                .copied(),
+        )
+    }
+
+    /// F9: 直近30秒 (`cutoff` 以降) のダイグラフ減速率の中央値 (データなしは 1.0)
+    fn calculate_digraph_slowdown(&self, cutoff: u64) -> f64 {
+        let mut buf = [0.0f64; MAX_DIGRAPH_RATIOS];
+        let mut n = 0;
+        let recent = self
+            .digraph_ratios
+            .iter()
+            .filter(|&&(ts, _)| ts >= cutoff)
+            .map(|&(_, ratio)| ratio);
+        for (slot, ratio) in buf.iter_mut().zip(recent) {
+            *slot = ratio;
+            n += 1;
+        }
+        if n == 0 {
+            return 1.0;
+        }
+        let ratios = &mut buf[..n];
+        ratios.sort_unstable_by(|a, b| a.total_cmp(b));
+        if n % 2 == 1 {
+            ratios[n / 2]
+        } else {
+            (ratios[n / 2 - 1] + ratios[n / 2]) / 2.0
+        }
+    }
+
     // Changed to EMA (Exponential Moving Average) for better responsiveness
     // or simply use the most recent value if we want instant reaction,
     // but a short average is smoother.
@@ -123,8 +390,10 @@ impl FeatureExtractor {
     /// イベント駆動の calculate_features() は無入力中に呼ばれないため、
     /// タイマーから呼び出してHMMを継続更新するために使用する。
     ///
-    /// # 設計方針
+    /// # 設計方針 (数値は `SilenceParams` の既定値)
     /// - F1: 直近の既知フライトタイムをそのまま使用 (データなしは None)
+    /// - F7: 直近の既知ホールド時間をそのまま使用、F8: 0.0 (F2 と同じ)
+    /// - F9: 1.0 (打鍵しているダイグラフがない = 普段どおりとみなす)
     /// - F4: 0.0 (バーストなし = 低Engagement シグナル)
     /// - F5: silence_secs / 2.0 (2秒ごとに1ポーズとして換算)
     /// - F3: 0→0.40 (30s超で線形増加。出力できない = 摩擦の代理変数)
@@ -139,7 +408,8 @@ impl FeatureExtractor {
     ///
     /// silence_secs が 2 未満の場合は None を返す (短すぎる無音はスキップ)。
     pub fn make_silence_observation(&self, silence_secs: f64) -> Option<Features> {
-        if silence_secs < 2.0 {
+        let sp = &self.params.silence;
+        if silence_secs < sp.min_silence_secs {
             return None;
         }
 
@@ -150,20 +420,22 @@ impl FeatureExtractor {
         }
 
         // サイレンス時間 → F5 (ポーズ回数): 2秒ごとに1カウント、最大20
-        let f5 = (silence_secs / 2.0).floor().min(20.0);
+        let f5 = (silence_secs / sp.secs_per_pause)
+            .floor()
+            .min(sp.max_pause_count);
 
         // F6 合成: 20s超で増加開始 → 50s で 0.50 に到達
         // phi(0.50, 0.15) = (0.50-0.15)/0.30 = 1.17 → clamped 1.0 (高Friction寄与)
-        let f6 = if silence_secs > 20.0 {
-            ((silence_secs - 20.0) / 60.0).min(0.50)
+        let f6 = if silence_secs > sp.f6_onset_secs {
+            ((silence_secs - sp.f6_onset_secs) / sp.f6_ramp_secs).min(sp.f6_max)
         } else {
             0.0
         };
 
         // F3 合成: 30s超で増加開始 → 70s で 0.40 に到達
         // phi(0.40, 0.10) = (0.40-0.10)/0.20 = 1.50 → clamped 1.0 (高Friction寄与)
-        let f3 = if silence_secs > 30.0 {
-            ((silence_secs - 30.0) / 100.0).min(0.40)
+        let f3 = if silence_secs > sp.f3_onset_secs {
+            ((silence_secs - sp.f3_onset_secs) / sp.f3_ramp_secs).min(sp.f3_max)
         } else {
             0.0
         };
@@ -175,151 +447,54 @@ impl FeatureExtractor {
             f4_burst_length: 0.0,
             f5_pause_count: f5,
             f6_pause_after_del_rate: f6,
+            f7_hold_time_median: self.calculate_hold_time_median(),
+            f8_hold_time_variance: 0.0,
+            f9_digraph_slowdown: 1.0,
         })
     }
 
-    /// B-1: 直近30秒のバッファから6特徴量を算出する
+    /// B-1: 直近30秒のウィンドウから特徴量を算出する。
+    ///
+    /// F2–F6・F8 は `RollingWindow` がイベントの出入りごとに更新した集計値を読むだけで、
+    /// ウィンドウの走査も割り当ても行わない (F7・F9 は直近の固定個数の中央値)
     pub fn calculate_features(&self) -> Features {
-        if self.buffer.is_empty() {
-            return Features::default();
-        }
-
-        let last_ts = self.buffer.back().unwrap().timestamp;
-        let cutoff = last_ts.saturating_sub(30_000);
-
-        // 直近30秒のイベントを収集
-        let events: Vec<&InputEvent> = self
-            .buffer
-            .iter()
-            .filter(|e| e.timestamp >= cutoff)
-            .collect();
-
-        if events.is_empty() {
+        let Some(last_ts) = self.window.last_timestamp() else {
             return Features::default();
-        }
-
-        // --- F1: Flight Time 中央値 (既存メソッドを利用) ---
-        let f1 = self.calculate_flight_time_median();
-
-        // --- ウィンドウ内のフライトタイムを再計算 (F2用) ---
-        let mut window_fts: Vec<f64> = Vec::new();
-        let mut last_release: Option<u64> = None;
-        for event in &events {
-            if event.is_press {
-                if let Some(rel) = last_release {
-                    if event.timestamp >= rel {
-                        let ft = (event.timestamp - rel) as f64;
-                        if ft < 2000.0 {
-                            window_fts.push(ft);
-                        }
-                    }
-                }
-            } else {
-                last_release = Some(event.timestamp);
-            }
-        }
-
-        // --- F2: Flight Time 分散 ---
-        let f2 = if window_fts.len() > 1 {
-            let mean = window_fts.iter().sum::<f64>() / window_fts.len() as f64;
-            window_fts.iter().map(|ft| (ft - mean).powi(2)).sum::<f64>() / window_fts.len() as f64
-        } else {
-            0.0
-        };
-
-        // --- キー押下イベントのみ抽出 ---
-        let press_events: Vec<&&InputEvent> = events.iter().filter(|e| e.is_press).collect();
-
-        let total_keys = press_events.len();
-
-        // --- F3: 修正率 = (BS + Del) / 全キー押下数 ---
-        let correction_keys = press_events
-            .iter()
-            .filter(|e| e.vk_code == VK_BACK || e.vk_code == VK_DELETE)
-            .count();
-
-        let f3 = if total_keys > 0 {
-            correction_keys as f64 / total_keys as f64
-        } else {
-            0.0
         };
+        let cutoff = last_ts.saturating_sub(WINDOW_MS);
 
-        // --- F4: バースト長 = 連続FT<200ms のチャンクの平均文字数 ---
-        let mut burst_lengths: Vec<usize> = Vec::new();
-        let mut current_burst: usize = 0;
-        let mut last_rel_for_burst: Option<u64> = None;
-
-        for event in &events {
-            if event.is_press {
-                if let Some(rel) = last_rel_for_burst {
-                    let ft = event.timestamp.saturating_sub(rel);
-                    if ft < 200 {
-                        current_burst += 1;
-                    } else {
-                        if current_burst > 0 {
-                            burst_lengths.push(current_burst);
-                        }
-                        current_burst = 1;
-                    }
-                } else {
-                    current_burst = 1;
-                }
-            } else {
-                last_rel_for_burst = Some(event.timestamp);
-            }
-        }
-        if current_burst > 0 {
-            burst_lengths.push(current_burst);
+        Features {
+            f1_flight_time_median: self.calculate_flight_time_median(),
+            f2_flight_time_variance: self.window.flight_time_variance(),
+            f3_correction_rate: self.window.correction_rate(),
+            f4_burst_length: self.window.burst_length(),
+            f5_pause_count: self.window.pause_count(),
+            f6_pause_after_del_rate: self.window.pause_after_del_rate(),
+            f7_hold_time_median: self.calculate_hold_time_median(),
+            f8_hold_time_variance: self.window.hold_time_variance(),
+            f9_digraph_slowdown: self.calculate_digraph_slowdown(cutoff),
         }
+    }
+}
 
-        let f4 = if !burst_lengths.is_empty() {
-            burst_lengths.iter().sum::<usize>() as f64 / burst_lengths.len() as f64
-        } else {
-            0.0
-        };
-
-        // --- F5: ポーズ回数 = 連続キー押下間で2秒以上の間隔の数 ---
-        let press_ts: Vec<u64> = events
-            .iter()
-            .filter(|e| e.is_press)
-            .map(|e| e.timestamp)
-            .collect();
-
-        let f5 = press_ts
-            .windows(2)
-            .filter(|w| w[1].saturating_sub(w[0]) >= 2000)
-            .count() as f64;
-
-        // --- F6: 削除後停止率 = BS/Del直後に2秒以上停止する割合 ---
-        let del_count = press_events
-            .iter()
-            .filter(|e| e.vk_code == VK_BACK || e.vk_code == VK_DELETE)
-            .count();
-
-        // 連続するキー押下ペアで、先頭がBS/Del かつ間隔>=2sのものを数える
-        let del_followed_by_pause = press_ts
-            .windows(2)
-            .zip(press_events.windows(2))
-            .filter(|(ts_win, ev_win)| {
-                let is_del = ev_win[0].vk_code == VK_BACK || ev_win[0].vk_code == VK_DELETE;
-                let long_pause = ts_win[1].saturating_sub(ts_win[0]) >= 2000;
-                is_del && long_pause
-            })
-            .count();
-
-        let f6 = if del_count > 0 {
-            del_followed_by_pause as f64 / del_count as f64
-        } else {
-            0.0
-        };
+const _: () = assert!(HOLD_MEDIAN_WINDOW <= DIGRAPH_HISTORY);
 
-        Features {
-            f1_flight_time_median: f1,
-            f2_flight_time_variance: f2,
-            f3_correction_rate: f3,
-            f4_burst_length: f4,
-            f5_pause_count: f5,
-            f6_pause_after_del_rate: f6,
-        }
+/// 最大 `DIGRAPH_HISTORY` 個 (F7 は `HOLD_MEDIAN_WINDOW` 個) の値の中央値 (データなしは 0)
+fn median(values: impl Iterator<Item = u64>) -> f64 {
+    let mut buf = [0u64; DIGRAPH_HISTORY];
+    let mut n = 0;
+    for (slot, v) in buf.iter_mut().zip(values) {
+        *slot = v;
+        n += 1;
+    }
+    if n == 0 {
+        return 0.0;
+    }
+    let buf = &mut buf[..n];
+    buf.sort_unstable();
+    if n % 2 == 1 {
+        buf[n / 2] as f64
+    } else {
+        (buf[n / 2 - 1] + buf[n / 2]) as f64 / 2.0
     }
 }
@@ -0,0 +1,396 @@
+use crate::analysis::engine::Observation;
+use crate::analysis::params::{
+    EmissionModel, GaussianEmission, HmmParams, TransitionMatrix, TransitionModel,
+};
+
+/// 5×5 グリッド 1 セルの面積。ガウス密度にこれを掛けて「1 ビン相当の確率質量」に換算し、
+/// 離散モデルと同じスケールで emission_floor を加える。
+const GRID_CELL_AREA: f64 = 1.0 / 25.0;
+
+/// 離散モデルの床付き放射確率 `B[state, obs] + ε`
+pub fn emission(params: &HmmParams, state: usize, obs: usize) -> f64 {
+    params.emissions[state][obs] + params.emission_floor
+}
+
+/// 2 次元ガウス密度 N(x; μ, Σ)。Σ が正定値でない場合は 0。
+pub fn gaussian_density(g: &GaussianEmission, x: (f64, f64)) -> f64 {
+    let c = &g.covariance;
+    let det = c[0][0] * c[1][1] - c[0][1] * c[1][0];
+    if det <= 0.0 {
+        return 0.0;
+    }
+    let dx = x.0 - g.mean[0];
+    let dy = x.1 - g.mean[1];
+    // マハラノビス距離² = d^T Σ^-1 d
+    let m = (c[1][1] * dx * dx - (c[0][1] + c[1][0]) * dx * dy + c[0][0] * dy * dy) / det;
+    (-0.5 * m).exp() / (2.0 * std::f64::consts::PI * det.sqrt())
+}
+
+/// 1 観測に対する各状態の床付き放射尤度。`update()` とオフライン解析で共通。
+///
+/// - `Discrete`: `B[j, obs.index] + ε`
+/// - `Gaussian`: `N(obs.axes; μ_j, Σ_j) · GRID_CELL_AREA + ε`
+///
+/// オーバーライド規則の一致時はモデルに関わらず `B[j, obs.index] + ε` を用いる。
+pub fn emission_likelihoods(params: &HmmParams, obs: &Observation) -> Vec<f64> {
+    (0..params.n_states())
+        .map(|j| match params.emission_model {
+            EmissionModel::Gaussian if obs.override_rule.is_none() => {
+                gaussian_density(&params.gaussians[j], obs.axes) * GRID_CELL_AREA
+                    + params.emission_floor
+            }
+            _ => emission(params, j, obs.index),
+        })
+        .collect()
+}
+
+/// 観測系列を放射尤度の系列に変換する
+pub fn emission_sequence(params: &HmmParams, obs: &[Observation]) -> Vec<Vec<f64>> {
+    obs.iter()
+        .map(|o| emission_likelihoods(params, o))
+        .collect()
+}
+
+/// 離散ビン列を放射尤度の系列に変換する (`EmissionModel` は参照しない)
+fn discrete_sequence(params: &HmmParams, obs: &[usize]) -> Vec<Vec<f64>> {
+    obs.iter()
+        .map(|&o| {
+            (0..params.n_states())
+                .map(|j| emission(params, j, o))
+                .collect()
+        })
+        .collect()
+}
+
+/// 1 更新分の遷移行列。
+///
+/// - `Discrete`: 経過時間に関わらず `transitions`
+/// - `ContinuousTime`: A(Δt) = exp(Q·Δt)。`dt_secs` が None (最初の更新) なら `initial_step_secs`
+pub fn transition_matrix(params: &HmmParams, dt_secs: Option<f64>) -> TransitionMatrix {
+    match params.transition_model {
+        TransitionModel::Discrete => params.transitions.clone(),
+        TransitionModel::ContinuousTime => {
+            let dt = dt_secs.unwrap_or(params.initial_step_secs).max(0.0);
+            let mut m = params.generator.clone();
+            for v in m.iter_mut().flatten() {
+                *v *= dt;
+            }
+            let mut a = expm(&m);
+            // 丸め誤差で生じる負値・行和のずれを補正して確率行列に戻す
+            for row in a.iter_mut() {
+                for v in row.iter_mut() {
+                    *v = v.max(0.0);
+                }
+                let sum: f64 = row.iter().sum();
+                if sum > 0.0 {
+                    for v in row.iter_mut() {
+                        *v /= sum;
+                    }
+                }
+            }
+            a
+        }
+    }
+}
+
+/// 観測系列を遷移行列の系列に変換する (`Observation::dt_secs` を用いる)
+pub fn transition_sequence(params: &HmmParams, obs: &[Observation]) -> Vec<TransitionMatrix> {
+    obs.iter()
+        .map(|o| transition_matrix(params, o.dt_secs))
+        .collect()
+}
+
+fn mat_mul(a: &TransitionMatrix, b: &TransitionMatrix) -> TransitionMatrix {
+    let n = a.len();
+    let mut out = vec![vec![0.0; n]; n];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = (0..n).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+/// N×N の単位行列
+fn identity(n: usize) -> TransitionMatrix {
+    let mut out = vec![vec![0.0; n]; n];
+    for (i, row) in out.iter_mut().enumerate() {
+        row[i] = 1.0;
+    }
+    out
+}
+
+/// 行列指数関数 exp(M) (スケーリング・二乗法 + Taylor 展開)
+fn expm(m: &TransitionMatrix) -> TransitionMatrix {
+    // ‖M / 2^s‖∞ ≤ 0.5 となるよう縮小してから Taylor 展開し、s 回二乗して戻す
+    let norm = m
+        .iter()
+        .map(|row| row.iter().map(|v| v.abs()).sum::<f64>())
+        .fold(0.0, f64::max);
+    let squarings = if norm > 0.5 {
+        (norm / 0.5).log2().ceil() as u32
+    } else {
+        0
+    };
+    let scale = 0.5f64.powi(squarings as i32);
+
+    let mut scaled = m.clone();
+    for v in scaled.iter_mut().flatten() {
+        *v *= scale;
+    }
+
+    let mut result = identity(m.len());
+    let mut term = identity(m.len());
+    for k in 1..=12 {
+        term = mat_mul(&term, &scaled);
+        for v in term.iter_mut().flatten() {
+            *v /= k as f64;
+        }
+        for (r, t) in result.iter_mut().flatten().zip(term.iter().flatten()) {
+            *r += t;
+        }
+    }
+
+    for _ in 0..squarings {
+        result = mat_mul(&result, &result);
+    }
+    result
+}
+
+/// スケーリング付き Forward-Backward の結果。
+///
+/// ライブの `update()` と同じ規約を用いる:
+/// - 初期分布 π は「最初の観測の直前」の信念であり、最初の観測の前にも遷移 A を 1 回適用する。
+/// - 放射尤度は `emission_likelihoods()` (離散なら `B[j, obs] + emission_floor`) を用いる。
+/// - 遷移行列は `transition_matrix()` (連続時間モデルなら観測ごとの Δt に応じた A(Δt)) を用いる。
+///
+/// したがって index 0 は観測を持たない仮想ステップ (= π) で、観測 o_1..o_T は index 1..T に対応する。
+/// `alpha` / `beta` / `scale` はいずれも長さ T+1。
+#[derive(Debug, Clone)]
+pub struct ForwardBackward {
+    /// alpha[t] = P(s_t | o_1..o_t)  — 正規化済み (ライブの current_state_probs と同じ)
+    pub alpha: Vec<Vec<f64>>,
+    /// スケーリング済み backward 変数
+    pub beta: Vec<Vec<f64>>,
+    /// scale[t] = P(o_t | o_1..o_{t-1})  (scale[0] = 1)
+    pub scale: Vec<f64>,
+    /// likelihoods[t] = 観測 o_{t+1} の各状態の放射尤度 (長さ T)
+    pub likelihoods: Vec<Vec<f64>>,
+    /// transitions[t] = 観測 o_{t+1} の直前に適用した遷移行列 (長さ T)
+    pub transitions: Vec<TransitionMatrix>,
+}
+
+impl ForwardBackward {
+    /// 離散ビン列に対して実行する (遷移は常に `params.transitions`)
+    pub fn run(params: &HmmParams, obs: &[usize]) -> Self {
+        let transitions = vec![params.transitions.clone(); obs.len()];
+        Self::run_steps(params, discrete_sequence(params, obs), transitions)
+    }
+
+    /// `params.emission_model` / `params.transition_model` に従い観測系列に対して実行する
+    pub fn run_observations(params: &HmmParams, obs: &[Observation]) -> Self {
+        Self::run_steps(
+            params,
+            emission_sequence(params, obs),
+            transition_sequence(params, obs),
+        )
+    }
+
+    /// 放射尤度と遷移行列の系列 (同じ長さ) に対して実行する。`params` からは π のみ参照する。
+    pub fn run_steps(
+        params: &HmmParams,
+        likelihoods: Vec<Vec<f64>>,
+        transitions: Vec<TransitionMatrix>,
+    ) -> Self {
+        let n = likelihoods.len().min(transitions.len());
+        let k = params.n_states();
+        let mut alpha = vec![vec![0.0; k]; n + 1];
+        let mut beta = vec![vec![1.0; k]; n + 1];
+        let mut scale = vec![1.0; n + 1];
+
+        let init_sum: f64 = params.initial.iter().sum();
+        for (a, &p) in alpha[0].iter_mut().zip(params.initial.iter()) {
+            *a = if init_sum > 0.0 {
+                p / init_sum
+            } else {
+                1.0 / k as f64
+            };
+        }
+
+        // --- Forward ---
+        for t in 1..=n {
+            let e = &likelihoods[t - 1];
+            let a_t = &transitions[t - 1];
+            let (done, rest) = alpha.split_at_mut(t);
+            let prev = &done[t - 1];
+            let mut sum = 0.0;
+            for (j, a) in rest[0].iter_mut().enumerate() {
+                let pred: f64 = (0..k).map(|i| prev[i] * a_t[i][j]).sum();
+                *a = pred * e[j];
+                sum += *a;
+            }
+            if sum > 0.0 {
+                for a in alpha[t].iter_mut() {
+                    *a /= sum;
+                }
+                scale[t] = sum;
+            } else {
+                // 全状態で尤度 0 → 直前の信念を維持 (update() と同じフォールバック)
+                alpha[t] = alpha[t - 1].clone();
+                scale[t] = f64::MIN_POSITIVE;
+            }
+        }
+
+        // --- Backward ---
+        for t in (0..n).rev() {
+            let e = &likelihoods[t];
+            let a_t = &transitions[t];
+            let (head, tail) = beta.split_at_mut(t + 1);
+            let next = &tail[0];
+            for (i, b) in head[t].iter_mut().enumerate() {
+                let s: f64 = (0..k).map(|j| a_t[i][j] * e[j] * next[j]).sum();
+                *b = s / scale[t + 1];
+            }
+        }
+
+        Self {
+            alpha,
+            beta,
+            scale,
+            likelihoods,
+            transitions,
+        }
+    }
+
+    /// 観測系列の対数尤度 log P(o_1..o_T)
+    pub fn log_likelihood(&self) -> f64 {
+        self.scale.iter().skip(1).map(|c| c.ln()).sum()
+    }
+
+    /// gamma[t] = P(s_t | o_1..o_T)  (t = 0 は初期状態の事後分布)
+    pub fn gamma(&self, t: usize) -> Vec<f64> {
+        let mut g: Vec<f64> = self.alpha[t]
+            .iter()
+            .zip(self.beta[t].iter())
+            .map(|(a, b)| a * b)
+            .collect();
+        let sum: f64 = g.iter().sum();
+        if sum > 0.0 {
+            for v in g.iter_mut() {
+                *v /= sum;
+            }
+        }
+        g
+    }
+
+    /// xi[i][j] = P(s_t = i, s_{t+1} = j | o_1..o_T)   (t = 0..T-1)
+    pub fn xi(&self, t: usize) -> TransitionMatrix {
+        let e = &self.likelihoods[t];
+        let a_t = &self.transitions[t];
+        let k = e.len();
+        let mut xi = vec![vec![0.0; k]; k];
+        let mut sum = 0.0;
+        for (i, row) in xi.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = self.alpha[t][i] * a_t[i][j] * e[j] * self.beta[t + 1][j];
+                sum += *v;
+            }
+        }
+        if sum > 0.0 {
+            for v in xi.iter_mut().flat_map(|r| r.iter_mut()) {
+                *v /= sum;
+            }
+        }
+        xi
+    }
+}
+
+/// Viterbi による最尤状態系列。
+///
+/// `ForwardBackward` と同じ規約 (仮想ステップ 0 = π、最初の観測の前にも遷移を適用、
+/// 床付き放射尤度) で計算する。`states[t]` は観測 o_{t+1} に対応する状態 (長さ T)。
+#[derive(Debug, Clone)]
+pub struct ViterbiPath {
+    pub states: Vec<usize>,
+    /// log P(s*_1..s*_T, o_1..o_T)
+    pub log_probability: f64,
+}
+
+/// 離散ビン列に対する Viterbi (遷移は常に `params.transitions`)
+pub fn viterbi(params: &HmmParams, obs: &[usize]) -> ViterbiPath {
+    let transitions = vec![params.transitions.clone(); obs.len()];
+    viterbi_steps(params, &discrete_sequence(params, obs), &transitions)
+}
+
+/// `params.emission_model` / `params.transition_model` に従う観測系列の Viterbi
+pub fn viterbi_observations(params: &HmmParams, obs: &[Observation]) -> ViterbiPath {
+    viterbi_steps(
+        params,
+        &emission_sequence(params, obs),
+        &transition_sequence(params, obs),
+    )
+}
+
+/// 放射尤度と遷移行列の系列 (同じ長さ) に対する Viterbi
+pub fn viterbi_steps(
+    params: &HmmParams,
+    likelihoods: &[Vec<f64>],
+    transitions: &[TransitionMatrix],
+) -> ViterbiPath {
+    let n = likelihoods.len().min(transitions.len());
+    if n == 0 {
+        return ViterbiPath {
+            states: Vec::new(),
+            log_probability: 0.0,
+        };
+    }
+
+    let k = params.n_states();
+    let init_sum: f64 = params.initial.iter().sum();
+    let mut delta: Vec<f64> = params
+        .initial
+        .iter()
+        .map(|&p| {
+            if init_sum > 0.0 {
+                (p / init_sum).ln()
+            } else {
+                -(k as f64).ln()
+            }
+        })
+        .collect();
+
+    // backpointer[t][j] = 観測 t で状態 j に至る最尤の直前状態
+    let mut backpointer = vec![vec![0usize; k]; n];
+    for (t, (e, a_t)) in likelihoods.iter().zip(transitions.iter()).enumerate() {
+        let prev = delta.clone();
+        for (j, d) in delta.iter_mut().enumerate() {
+            let (best_i, best) = (0..k).map(|i| (i, prev[i] + a_t[i][j].ln())).fold(
+                (0, f64::NEG_INFINITY),
+                |acc, x| if x.1 > acc.1 { x } else { acc },
+            );
+            backpointer[t][j] = best_i;
+            *d = best + e[j].ln();
+        }
+    }
+
+    let (mut state, log_probability) =
+        delta
+            .iter()
+            .copied()
+            .enumerate()
+            .fold(
+                (0, f64::NEG_INFINITY),
+                |acc, x| if x.1 > acc.1 { x } else { acc },
+            );
+
+    let mut states = vec![0; n];
+    for t in (0..n).rev() {
+        states[t] = state;
+        state = backpointer[t][state];
+    }
+
+    ViterbiPath {
+        states,
+        log_probability,
+    }
+}
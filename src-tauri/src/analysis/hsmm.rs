@@ -0,0 +1,420 @@
+use serde::Serialize;
+
+use crate::analysis::params::{
+    DurationDistribution, HmmParams, HsmmParams, TransitionMatrix, TransitionModel,
+};
+
+// ---------------------------------------------------------------------------
+// 滞在時間分布
+// ---------------------------------------------------------------------------
+
+impl DurationDistribution {
+    pub fn mean(&self) -> f64 {
+        match *self {
+            DurationDistribution::Gamma { shape, scale } => shape * scale,
+            DurationDistribution::LogNormal { mu, sigma } => (mu + 0.5 * sigma * sigma).exp(),
+        }
+    }
+
+    /// 生存関数 S(a) = P(D > a)
+    pub fn survival(&self, age_secs: f64) -> f64 {
+        if age_secs <= 0.0 {
+            return 1.0;
+        }
+        match *self {
+            DurationDistribution::Gamma { shape, scale } => gamma_q(shape, age_secs / scale),
+            DurationDistribution::LogNormal { mu, sigma } => {
+                0.5 * erfc((age_secs.ln() - mu) / (sigma * std::f64::consts::SQRT_2))
+            }
+        }
+    }
+
+    /// 平均残余時間 E[D − a | D > a]。尾部で S(a) が数値的に 0 の場合は 0 を返す。
+    pub fn mean_residual(&self, age_secs: f64) -> f64 {
+        let a = age_secs.max(0.0);
+        let s = self.survival(a);
+        if s < 1e-300 {
+            return 0.0;
+        }
+        // E[(D − a)⁺] = E[D; D > a] − a·S(a)
+        let partial_mean = match *self {
+            DurationDistribution::Gamma { shape, scale } => {
+                shape * scale * gamma_q(shape + 1.0, a / scale)
+            }
+            DurationDistribution::LogNormal { mu, sigma } => {
+                if a <= 0.0 {
+                    self.mean()
+                } else {
+                    self.mean()
+                        * 0.5
+                        * erfc((a.ln() - mu - sigma * sigma) / (sigma * std::f64::consts::SQRT_2))
+                }
+            }
+        };
+        ((partial_mean - a * s) / s).max(0.0)
+    }
+
+    /// 滞在時間のサンプル [秒] から分布を推定する (2 件以上の正値が必要)。
+    ///
+    /// - Gamma: 最尤推定の閉形式近似 (Minka 2002) で shape を求め、scale = 平均 / shape
+    /// - LogNormal: ln D の標本平均・標準偏差
+    pub fn fit(family: DurationFamily, samples: &[f64]) -> Option<Self> {
+        let xs: Vec<f64> = samples
+            .iter()
+            .copied()
+            .filter(|v| v.is_finite() && *v > 0.0)
+            .collect();
+        if xs.len() < 2 {
+            return None;
+        }
+        let n = xs.len() as f64;
+        let mean_log = xs.iter().map(|v| v.ln()).sum::<f64>() / n;
+
+        match family {
+            DurationFamily::Gamma => {
+                let mean = xs.iter().sum::<f64>() / n;
+                let s = mean.ln() - mean_log;
+                if s <= 1e-12 {
+                    return None;
+                }
+                let shape = (3.0 - s + ((s - 3.0).powi(2) + 24.0 * s).sqrt()) / (12.0 * s);
+                Some(DurationDistribution::Gamma {
+                    shape,
+                    scale: mean / shape,
+                })
+            }
+            DurationFamily::LogNormal => {
+                let var = xs.iter().map(|v| (v.ln() - mean_log).powi(2)).sum::<f64>() / (n - 1.0);
+                Some(DurationDistribution::LogNormal {
+                    mu: mean_log,
+                    sigma: var.sqrt().max(1e-3),
+                })
+            }
+        }
+    }
+}
+
+/// `DurationDistribution::fit()` で推定する分布族
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DurationFamily {
+    Gamma,
+    LogNormal,
+}
+
+// ---------------------------------------------------------------------------
+// オンライン HSMM フィルタ
+// ---------------------------------------------------------------------------
+
+/// 同じ状態に同じ時間だけ滞在しているとみなす確率質量の塊
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cohort {
+    age_secs: f64,
+    weight: f64,
+}
+
+/// 現在状態の滞在時間の推定値
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DwellEstimate {
+    /// 最も確率の高い状態の名前 (`states[].name`)
+    pub state: String,
+    pub probability: f64,
+    /// その状態に入ってからの期待経過時間 [秒]
+    pub elapsed_secs: f64,
+    /// その状態に留まる期待残り時間 [秒]
+    pub expected_remaining_secs: f64,
+}
+
+/// 明示的滞在時間 HSMM の前向きフィルタ。
+///
+/// 信念を (状態, 滞在時間) の同時分布として保持する。各更新では経過時間 Δt の間に
+/// 離脱する確率をハザード 1 − S(a+Δt)/S(a) で求め、離脱した質量を A / Q の非対角成分の比で
+/// 他状態の滞在時間 0 のコホートへ移し、放射尤度を掛けて正規化する。
+/// コホート数は状態ごとに `HsmmParams::max_cohorts` で上限を設け、1 更新あたり O(K)。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HsmmFilter {
+    // One cohort list per state; empty until seeded by from_probs()
+    cohorts: Vec<Vec<Cohort>>,
+}
+
+impl HsmmFilter {
+    /// 状態確率から初期化する (全状態とも滞在時間 0)
+    pub fn from_probs(probs: &[f64]) -> Self {
+        let cohorts = probs
+            .iter()
+            .map(|&p| {
+                if p > 0.0 {
+                    vec![Cohort {
+                        age_secs: 0.0,
+                        weight: p,
+                    }]
+                } else {
+                    Vec::new()
+                }
+            })
+            .collect();
+        Self { cohorts }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cohorts.iter().all(|c| c.is_empty())
+    }
+
+    /// 状態の周辺確率
+    pub fn marginal(&self) -> Vec<f64> {
+        let mut out: Vec<f64> = self
+            .cohorts
+            .iter()
+            .map(|c| c.iter().map(|x| x.weight).sum())
+            .collect();
+        let sum: f64 = out.iter().sum();
+        if sum > 0.0 {
+            for v in out.iter_mut() {
+                *v /= sum;
+            }
+        }
+        out
+    }
+
+    /// 1 更新分進め、正規化済みの状態確率を返す。
+    /// 全状態で尤度 0 になった場合は信念を変更しない (`forward_step()` と同じフォールバック)。
+    pub fn step(
+        &mut self,
+        hmm: &HmmParams,
+        hsmm: &HsmmParams,
+        dt_secs: f64,
+        likelihoods: &[f64],
+    ) -> Vec<f64> {
+        let dt = dt_secs.max(0.0);
+        let jumps = jump_matrix(hmm);
+        let mut next = self.cohorts.clone();
+        let mut entering = vec![0.0; next.len()];
+
+        // --- 滞在時間の経過と離脱 ---
+        for (i, cohorts) in next.iter_mut().enumerate() {
+            let dist = &hsmm.durations[i];
+            for c in cohorts.iter_mut() {
+                let s0 = dist.survival(c.age_secs);
+                let s1 = dist.survival(c.age_secs + dt);
+                let hazard = if s0 > 0.0 {
+                    (1.0 - s1 / s0).clamp(0.0, 1.0)
+                } else {
+                    1.0
+                };
+                let leaving = c.weight * hazard;
+                for (e, &p) in entering.iter_mut().zip(jumps[i].iter()) {
+                    *e += leaving * p;
+                }
+                c.weight -= leaving;
+                c.age_secs += dt;
+            }
+        }
+        for (cohorts, &w) in next.iter_mut().zip(entering.iter()) {
+            if w > 0.0 {
+                cohorts.push(Cohort {
+                    age_secs: 0.0,
+                    weight: w,
+                });
+            }
+        }
+
+        // --- 観測更新 ---
+        let mut total = 0.0;
+        for (cohorts, &e) in next.iter_mut().zip(likelihoods.iter()) {
+            for c in cohorts.iter_mut() {
+                c.weight *= e;
+                total += c.weight;
+            }
+        }
+        if total <= 0.0 {
+            return self.marginal();
+        }
+        for c in next.iter_mut().flatten() {
+            c.weight /= total;
+        }
+
+        for cohorts in next.iter_mut() {
+            compact(cohorts, hsmm.max_cohorts);
+        }
+        self.cohorts = next;
+        self.marginal()
+    }
+
+    /// 最も確率の高い状態について、期待経過時間と期待残り時間を求める
+    pub fn dwell_estimate(&self, hmm: &HmmParams, hsmm: &HsmmParams) -> Option<DwellEstimate> {
+        let marginal = self.marginal();
+        let (index, probability) =
+            marginal
+                .iter()
+                .copied()
+                .enumerate()
+                .fold(
+                    (0, f64::NEG_INFINITY),
+                    |acc, x| if x.1 > acc.1 { x } else { acc },
+                );
+        let cohorts = self.cohorts.get(index)?;
+        let weight: f64 = cohorts.iter().map(|c| c.weight).sum();
+        if weight <= 0.0 {
+            return None;
+        }
+
+        let dist = hsmm.durations.get(index)?;
+        let elapsed_secs = cohorts.iter().map(|c| c.weight * c.age_secs).sum::<f64>() / weight;
+        let expected_remaining_secs = cohorts
+            .iter()
+            .map(|c| c.weight * dist.mean_residual(c.age_secs))
+            .sum::<f64>()
+            / weight;
+
+        Some(DwellEstimate {
+            state: hmm.states.get(index)?.name.clone(),
+            probability,
+            elapsed_secs,
+            expected_remaining_secs,
+        })
+    }
+}
+
+/// 離脱先の分布 (埋め込みジャンプ連鎖)。A / Q の非対角成分を行ごとに正規化する。
+/// 非対角成分がすべて 0 の行は他状態へ均等に按分する。
+fn jump_matrix(hmm: &HmmParams) -> TransitionMatrix {
+    let source = match hmm.transition_model {
+        TransitionModel::Discrete => &hmm.transitions,
+        TransitionModel::ContinuousTime => &hmm.generator,
+    };
+    let n = hmm.n_states();
+    let mut out = vec![vec![0.0; n]; n];
+    for (i, row) in out.iter_mut().enumerate() {
+        let off: f64 = (0..n)
+            .filter(|&j| j != i)
+            .map(|j| source[i][j].max(0.0))
+            .sum();
+        for (j, v) in row.iter_mut().enumerate() {
+            if j == i {
+                continue;
+            }
+            *v = if off > 0.0 {
+                source[i][j].max(0.0) / off
+            } else {
+                1.0 / (n - 1) as f64
+            };
+        }
+    }
+    out
+}
+
+/// 無視できる重みのコホートを捨て、上限を超えた分は滞在時間の比が最も近い隣同士を統合する
+fn compact(cohorts: &mut Vec<Cohort>, max_cohorts: usize) {
+    cohorts.retain(|c| c.weight > 1e-12);
+    cohorts.sort_by(|a, b| a.age_secs.total_cmp(&b.age_secs));
+    while cohorts.len() > max_cohorts.max(1) {
+        let mut best = 0;
+        let mut best_gap = f64::INFINITY;
+        for k in 0..cohorts.len() - 1 {
+            // 対数スケールの間隔: 若いコホートほど細かく保持する
+            let gap = (cohorts[k + 1].age_secs + 1.0).ln() - (cohorts[k].age_secs + 1.0).ln();
+            if gap < best_gap {
+                best_gap = gap;
+                best = k;
+            }
+        }
+        let b = cohorts.remove(best + 1);
+        let a = &mut cohorts[best];
+        let w = a.weight + b.weight;
+        a.age_secs = (a.weight * a.age_secs + b.weight * b.age_secs) / w;
+        a.weight = w;
+    }
+}
+
+// ---------------------------------------------------------------------------
+// 特殊関数
+// ---------------------------------------------------------------------------
+
+/// ln Γ(x) (Lanczos 近似, x > 0)
+fn ln_gamma(x: f64) -> f64 {
+    const COEF: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.120_865_097_386_617_9e-2,
+        -0.539_523_938_495_3e-5,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut ser = 1.000_000_000_190_015;
+    let mut y = x;
+    for c in COEF {
+        y += 1.0;
+        ser += c / y;
+    }
+    -tmp + (2.506_628_274_631_000_5 * ser / x).ln()
+}
+
+/// 正則化上側不完全ガンマ関数 Q(a, x) = Γ(a, x) / Γ(a)
+fn gamma_q(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let gln = ln_gamma(a);
+    if x < a + 1.0 {
+        // 級数展開で P(a, x) を求める
+        let mut ap = a;
+        let mut del = 1.0 / a;
+        let mut sum = del;
+        for _ in 0..500 {
+            ap += 1.0;
+            del *= x / ap;
+            sum += del;
+            if del.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        1.0 - sum * (-x + a * x.ln() - gln).exp()
+    } else {
+        // 連分数展開 (修正 Lentz 法) で Q(a, x) を求める
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..500 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let del = d * c;
+            h *= del;
+            if (del - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        (-x + a * x.ln() - gln).exp() * h
+    }
+}
+
+/// 相補誤差関数 erfc(x) (Chebyshev 近似, 相対誤差 < 1.2e-7)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.265_512_23
+        + t * (1.000_023_68
+            + t * (0.374_091_96
+                + t * (0.096_784_18
+                    + t * (-0.186_288_06
+                        + t * (0.278_868_07
+                            + t * (-1.135_203_98
+                                + t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    let ans = t * poly.exp();
+    if x >= 0.0 {
+        ans
+    } else {
+        2.0 - ans
+    }
+}
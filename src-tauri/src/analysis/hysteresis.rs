@@ -0,0 +1,330 @@
+use crate::analysis::decoding::argmax;
+use crate::analysis::params::{HysteresisKind, HysteresisParams};
+
+/// `HysteresisPolicy::apply()` への 1 更新分の入力
+pub struct HysteresisInput<'a> {
+    pub params: &'a HysteresisParams,
+    /// 推定器の出力 (ヒステリシス前の current_state_probs)
+    pub raw: &'a [f64],
+    /// 直前の display_probs
+    pub display: &'a [f64],
+    /// オーバーライド規則の一致時の α (規則の `display_alpha`、省略時は `penalty_alpha`)。
+    /// 規則に一致しなかった更新では None。
+    pub override_alpha: Option<f64>,
+    /// 更新時刻 (Unix ms)
+    pub timestamp: u64,
+}
+
+/// raw の状態確率から表示用の display_probs を作るヒステリシス層。
+///
+/// README の Fix ① (ウィンドウリセット時の Stuck→Flow 瞬間遷移) を防ぐのが目的。
+/// 係数はスナップショットの `HysteresisParams` から毎回読むため、方式 (`policy`) が
+/// 変わらない限りホットリロードで作り直す必要はない。
+pub trait HysteresisPolicy: Send {
+    fn name(&self) -> &'static str;
+
+    /// 新しい display_probs を返す
+    fn apply(&mut self, input: &HysteresisInput) -> Vec<f64>;
+
+    /// display_probs が外部から上書きされたとき (IME 切り替え等) に内部状態を捨てる
+    fn reset(&mut self) {}
+}
+
+pub fn build_policy(kind: HysteresisKind) -> Box<dyn HysteresisPolicy> {
+    match kind {
+        HysteresisKind::Ema => Box::new(EmaPolicy),
+        HysteresisKind::Schmitt => Box::new(SchmittPolicy),
+        HysteresisKind::MinDwell => Box::new(MinDwellPolicy::default()),
+        HysteresisKind::Confirmations => Box::new(ConfirmationPolicy::default()),
+    }
+}
+
+/// 遅い EMA (既定)。
+///
+/// α=0.25 (通常・既定): 時定数 ≈ 4 更新 ≈ 4 秒
+/// α=0.50 (ペナルティ・既定): オーバーライド規則の一致時 (連続 Backspace 等) は素早く収束
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EmaPolicy;
+
+impl HysteresisPolicy for EmaPolicy {
+    fn name(&self) -> &'static str {
+        "ema"
+    }
+
+    fn apply(&mut self, input: &HysteresisInput) -> Vec<f64> {
+        let alpha = input.override_alpha.unwrap_or(input.params.alpha);
+
+        let mut display: Vec<f64> = input
+            .raw
+            .iter()
+            .zip(input.display.iter())
+            .map(|(r, d)| alpha * r + (1.0 - alpha) * d)
+            .collect();
+        let sum: f64 = display.iter().sum();
+        if sum > 0.0 {
+            for d in display.iter_mut() {
+                *d /= sum;
+            }
+        }
+        display
+    }
+}
+
+// 以下のラッチ型の方式は「表示中の状態 = display の argmax」を保持し、切り替えを
+// 認めない更新では直前の display をそのまま返す。raw の argmax が表示中の状態と同じなら
+// raw をそのまま表示する。オーバーライド規則の一致時は EMA の penalty_alpha と同じく
+// 規則の示す状態への素早い収束を優先し、ロックを無視して raw を表示する。
+
+/// シュミットトリガ: 表示中の状態 s は raw[s] < exit[s] になるまで保持し、
+/// 切り替え先 j は raw[j] >= enter[j] を満たす必要がある。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SchmittPolicy;
+
+impl HysteresisPolicy for SchmittPolicy {
+    fn name(&self) -> &'static str {
+        "schmitt"
+    }
+
+    fn apply(&mut self, input: &HysteresisInput) -> Vec<f64> {
+        let shown = argmax(input.display);
+        let top = argmax(input.raw);
+        if top == shown || input.override_alpha.is_some() {
+            return input.raw.to_vec();
+        }
+
+        let t = &input.params.schmitt;
+        if input.raw[shown] < t.exit[shown] && input.raw[top] >= t.enter[top] {
+            input.raw.to_vec()
+        } else {
+            input.display.to_vec()
+        }
+    }
+}
+
+/// 最小滞在ロック: 別の状態が raw の argmax であり続けた時間が `min_dwell_ms` に
+/// 達するまで表示状態を切り替えない (更新頻度に依存しない時間基準)。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MinDwellPolicy {
+    // 挑戦中の状態と、argmax になった時刻
+    candidate: Option<(usize, u64)>,
+}
+
+impl HysteresisPolicy for MinDwellPolicy {
+    fn name(&self) -> &'static str {
+        "min_dwell"
+    }
+
+    fn apply(&mut self, input: &HysteresisInput) -> Vec<f64> {
+        let shown = argmax(input.display);
+        let top = argmax(input.raw);
+        if top == shown || input.override_alpha.is_some() {
+            self.candidate = None;
+            return input.raw.to_vec();
+        }
+
+        let since = match self.candidate {
+            Some((c, t)) if c == top => t,
+            _ => input.timestamp,
+        };
+        if input.timestamp.saturating_sub(since) >= input.params.min_dwell_ms {
+            self.candidate = None;
+            input.raw.to_vec()
+        } else {
+            self.candidate = Some((top, since));
+            input.display.to_vec()
+        }
+    }
+
+    fn reset(&mut self) {
+        self.candidate = None;
+    }
+}
+
+/// k 回連続確認: 挑戦者の事後確率 raw[j] が `threshold` 以上の更新が k 回続いたら
+/// 切り替える。各更新を独立した証拠とみなし、単発の観測では表示を動かさない。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConfirmationPolicy {
+    // 挑戦中の状態と連続確認回数
+    candidate: Option<(usize, u32)>,
+}
+
+impl HysteresisPolicy for ConfirmationPolicy {
+    fn name(&self) -> &'static str {
+        "confirmations"
+    }
+
+    fn apply(&mut self, input: &HysteresisInput) -> Vec<f64> {
+        let shown = argmax(input.display);
+        let top = argmax(input.raw);
+        if top == shown || input.override_alpha.is_some() {
+            self.candidate = None;
+            return input.raw.to_vec();
+        }
+
+        let c = &input.params.confirmations;
+        if input.raw[top] < c.threshold {
+            self.candidate = None;
+            return input.display.to_vec();
+        }
+        let count = match self.candidate {
+            Some((s, n)) if s == top => n + 1,
+            _ => 1,
+        };
+        if count >= c.k {
+            self.candidate = None;
+            input.raw.to_vec()
+        } else {
+            self.candidate = Some((top, count));
+            input.display.to_vec()
+        }
+    }
+
+    fn reset(&mut self) {
+        self.candidate = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STUCK: usize = 2;
+
+    // README Fix ① の再現: 30 秒ウィンドウが Backspace 多発区間を抜けた直後、
+    // raw は 1 ステップで Stuck (0.994) から Flow 寄りへ跳ぶ。
+    // raw p_stuck ≈ 0.01 は README の表 (display p_stuck 0.994 → 0.748 → …) から逆算した値。
+    const BEFORE_RESET: [f64; 3] = [0.003, 0.003, 0.994];
+    const AFTER_RESET: [[f64; 3]; 4] = [
+        [0.48, 0.51, 0.01],
+        [0.52, 0.47, 0.01],
+        [0.54, 0.45, 0.01],
+        [0.56, 0.43, 0.01],
+    ];
+    const TICK_MS: u64 = 1000;
+
+    /// 1 秒間隔で raw を与え、各ティックの display を返す
+    fn run(
+        policy: &mut dyn HysteresisPolicy,
+        params: &HysteresisParams,
+        raws: &[[f64; 3]],
+        penalty: bool,
+    ) -> Vec<Vec<f64>> {
+        let mut display = BEFORE_RESET.to_vec();
+        raws.iter()
+            .enumerate()
+            .map(|(i, raw)| {
+                display = policy.apply(&HysteresisInput {
+                    params,
+                    raw,
+                    display: &display,
+                    override_alpha: penalty.then_some(params.penalty_alpha),
+                    timestamp: (i as u64 + 1) * TICK_MS,
+                });
+                display.clone()
+            })
+            .collect()
+    }
+
+    /// ウィンドウリセット後 4 ティックの間、表示が Stuck から瞬間的に崩れないこと
+    fn assert_holds_stuck_through_reset(kind: HysteresisKind) {
+        let params = HysteresisParams {
+            policy: kind,
+            ..HysteresisParams::default()
+        };
+        let mut policy = build_policy(kind);
+        let displays = run(policy.as_mut(), &params, &AFTER_RESET, false);
+        for (tick, d) in displays.iter().enumerate() {
+            assert_eq!(
+                argmax(d),
+                STUCK,
+                "{}: display left Stuck at tick {} ({:?})",
+                policy.name(),
+                tick + 1,
+                d
+            );
+        }
+    }
+
+    fn sustained_flow(ticks: usize) -> Vec<[f64; 3]> {
+        vec![[0.80, 0.15, 0.05]; ticks]
+    }
+
+    #[test]
+    fn ema_reproduces_readme_decay_table() {
+        let params = HysteresisParams::default();
+        let displays = run(&mut EmaPolicy, &params, &AFTER_RESET, false);
+        let expected_stuck = [0.748, 0.563, 0.424, 0.319];
+        for (d, e) in displays.iter().zip(expected_stuck.iter()) {
+            assert!((d[STUCK] - e).abs() < 0.005, "{} vs {}", d[STUCK], e);
+            assert!((d.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn ema_penalty_alpha_converges_faster() {
+        let params = HysteresisParams::default();
+        let normal = run(&mut EmaPolicy, &params, &AFTER_RESET[..1], false);
+        let penalty = run(&mut EmaPolicy, &params, &AFTER_RESET[..1], true);
+        assert!(penalty[0][STUCK] < normal[0][STUCK]);
+    }
+
+    #[test]
+    fn schmitt_holds_stuck_through_window_reset() {
+        assert_holds_stuck_through_reset(HysteresisKind::Schmitt);
+    }
+
+    #[test]
+    fn schmitt_switches_once_enter_threshold_is_reached() {
+        let params = HysteresisParams::default();
+        let displays = run(&mut SchmittPolicy, &params, &sustained_flow(1), false);
+        assert_eq!(argmax(&displays[0]), 0);
+    }
+
+    #[test]
+    fn min_dwell_holds_stuck_through_window_reset() {
+        assert_holds_stuck_through_reset(HysteresisKind::MinDwell);
+    }
+
+    #[test]
+    fn min_dwell_switches_after_dwell_elapses() {
+        let params = HysteresisParams::default();
+        let mut policy = MinDwellPolicy::default();
+        let displays = run(&mut policy, &params, &sustained_flow(6), false);
+        // 挑戦者は tick 1 (t = 1000) から argmax。min_dwell_ms = 4000 なので t = 5000 で切り替わる
+        for d in &displays[..4] {
+            assert_eq!(argmax(d), STUCK);
+        }
+        assert_eq!(argmax(&displays[4]), 0);
+        assert_eq!(argmax(&displays[5]), 0);
+    }
+
+    #[test]
+    fn confirmations_hold_stuck_through_window_reset() {
+        assert_holds_stuck_through_reset(HysteresisKind::Confirmations);
+    }
+
+    #[test]
+    fn confirmations_switch_on_kth_consecutive_update() {
+        let params = HysteresisParams::default();
+        let mut policy = ConfirmationPolicy::default();
+        let displays = run(&mut policy, &params, &sustained_flow(4), false);
+        for d in &displays[..3] {
+            assert_eq!(argmax(d), STUCK);
+        }
+        assert_eq!(argmax(&displays[3]), 0);
+    }
+
+    #[test]
+    fn latching_policies_follow_raw_on_backspace_penalty() {
+        let params = HysteresisParams::default();
+        for kind in [
+            HysteresisKind::Schmitt,
+            HysteresisKind::MinDwell,
+            HysteresisKind::Confirmations,
+        ] {
+            let mut policy = build_policy(kind);
+            let displays = run(policy.as_mut(), &params, &AFTER_RESET[..1], true);
+            assert_eq!(displays[0], AFTER_RESET[0], "{}", policy.name());
+        }
+    }
+}
@@ -1,2 +1,18 @@
+pub mod calibration;
+pub mod decoding;
 pub mod engine;
+pub mod estimator;
+pub mod explain;
 pub mod features;
+pub mod hmm;
+pub mod hsmm;
+pub mod hysteresis;
+pub mod normalize;
+pub mod overrides;
+pub mod params;
+pub mod persistence;
+pub mod presence;
+pub mod projection;
+pub mod replay;
+pub mod rolling;
+pub mod training;
@@ -0,0 +1,223 @@
+use crate::analysis::engine::PhiFeatures;
+use crate::analysis::features::Features;
+use crate::analysis::params::{BaselineParams, NormalizationParams, NormalizerKind, ZScoreParams};
+
+/// 特徴量 1 つを [0, 1] に写す正規化関数。
+///
+/// β (母集団または個人ベースライン) は呼び出しごとに渡す。係数は生成時に
+/// `NormalizationParams` から取り込むため、設定が変わったら作り直す
+/// (`ObservationEncoder::set_normalization`)。
+pub trait Normalizer: Send {
+    fn name(&self) -> &'static str;
+
+    /// x を [0, 1] に写す
+    fn normalize(&self, x: f64, beta: f64) -> f64;
+
+    /// 打鍵ごとの特徴量で内部統計を更新する (既定は何もしない)
+    fn observe(&mut self, _x: f64, _beta: f64) {}
+}
+
+pub fn build_normalizer(
+    kind: NormalizerKind,
+    params: &NormalizationParams,
+    ecdf_samples: &[f64],
+) -> Box<dyn Normalizer> {
+    match kind {
+        NormalizerKind::Phi => Box::new(PhiNormalizer {
+            kappa: params.kappa,
+        }),
+        NormalizerKind::ZScore => Box::new(ZScoreNormalizer::new(&params.z_score)),
+        NormalizerKind::Ecdf => Box::new(EcdfNormalizer::new(ecdf_samples)),
+        NormalizerKind::Logistic => Box::new(LogisticNormalizer {
+            slope: params.logistic_slope,
+        }),
+    }
+}
+
+/// B-2: φ(x, β) = clamp((x − β) / (κ · β), 0.0, 1.0)。
+/// β 未満の値はすべて 0 になる片側の正規化 (既定)。
+#[derive(Debug, Clone, Copy)]
+pub struct PhiNormalizer {
+    pub kappa: f64,
+}
+
+impl Default for PhiNormalizer {
+    fn default() -> Self {
+        Self { kappa: 2.0 }
+    }
+}
+
+impl Normalizer for PhiNormalizer {
+    fn name(&self) -> &'static str {
+        "phi"
+    }
+
+    fn normalize(&self, x: f64, beta: f64) -> f64 {
+        if beta <= 0.0 {
+            return 0.0;
+        }
+        ((x - beta) / (self.kappa * beta)).clamp(0.0, 1.0)
+    }
+}
+
+/// 両側 z-score。
+///
+/// ```text
+/// z = (x − μ) / σ,   out = clamp(0.5 + z / (2 · clip), 0, 1)
+/// ```
+///
+/// μ・σ² は打鍵ごとの指数移動平均・分散。最初の観測までは μ = β, σ = β とみなす
+/// (x = β で 0.5)。
+#[derive(Debug, Clone, Copy)]
+pub struct ZScoreNormalizer {
+    alpha: f64,
+    clip: f64,
+    // (mean, variance); None until the first observation
+    moments: Option<(f64, f64)>,
+}
+
+impl ZScoreNormalizer {
+    pub fn new(params: &ZScoreParams) -> Self {
+        Self {
+            alpha: params.alpha,
+            clip: params.clip,
+            moments: None,
+        }
+    }
+}
+
+impl Normalizer for ZScoreNormalizer {
+    fn name(&self) -> &'static str {
+        "z_score"
+    }
+
+    fn normalize(&self, x: f64, beta: f64) -> f64 {
+        let (mean, var) = self.moments.unwrap_or((beta, beta * beta));
+        let sd = var.sqrt();
+        if sd <= f64::EPSILON {
+            return 0.5;
+        }
+        let z = (x - mean) / sd;
+        (0.5 + z / (2.0 * self.clip)).clamp(0.0, 1.0)
+    }
+
+    fn observe(&mut self, x: f64, beta: f64) {
+        let (mean, var) = self.moments.unwrap_or((beta, beta * beta));
+        let a = self.alpha;
+        let d = x - mean;
+        self.moments = Some((mean + a * d, (1.0 - a) * (var + a * d * d)));
+    }
+}
+
+/// 経験累積分布 (ECDF)。校正用標本のうち x 以下の割合を返す。
+#[derive(Debug, Clone)]
+pub struct EcdfNormalizer {
+    // Calibration samples, ascending
+    sorted: Vec<f64>,
+}
+
+impl EcdfNormalizer {
+    pub fn new(samples: &[f64]) -> Self {
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        Self { sorted }
+    }
+}
+
+impl Normalizer for EcdfNormalizer {
+    fn name(&self) -> &'static str {
+        "ecdf"
+    }
+
+    fn normalize(&self, x: f64, _beta: f64) -> f64 {
+        if self.sorted.is_empty() {
+            return 0.0;
+        }
+        let below = self.sorted.partition_point(|&s| s <= x);
+        below as f64 / self.sorted.len() as f64
+    }
+}
+
+/// ロジスティック関数 σ(slope · (x − β) / β)。x = β で 0.5。
+#[derive(Debug, Clone, Copy)]
+pub struct LogisticNormalizer {
+    pub slope: f64,
+}
+
+impl Normalizer for LogisticNormalizer {
+    fn name(&self) -> &'static str {
+        "logistic"
+    }
+
+    fn normalize(&self, x: f64, beta: f64) -> f64 {
+        if beta <= 0.0 {
+            return 0.0;
+        }
+        1.0 / (1.0 + (-self.slope * (x - beta) / beta).exp())
+    }
+}
+
+/// 潜在軸に用いる F1–F6 それぞれの正規化関数
+pub struct FeatureNormalizers {
+    pub f1: Box<dyn Normalizer>,
+    pub f2: Box<dyn Normalizer>,
+    pub f3: Box<dyn Normalizer>,
+    pub f4: Box<dyn Normalizer>,
+    pub f5: Box<dyn Normalizer>,
+    pub f6: Box<dyn Normalizer>,
+}
+
+impl Default for FeatureNormalizers {
+    fn default() -> Self {
+        Self::from_params(&NormalizationParams::default())
+    }
+}
+
+impl std::fmt::Debug for FeatureNormalizers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FeatureNormalizers")
+            .field("f1", &self.f1.name())
+            .field("f2", &self.f2.name())
+            .field("f3", &self.f3.name())
+            .field("f4", &self.f4.name())
+            .field("f5", &self.f5.name())
+            .field("f6", &self.f6.name())
+            .finish()
+    }
+}
+
+impl FeatureNormalizers {
+    pub fn from_params(params: &NormalizationParams) -> Self {
+        let e = &params.ecdf;
+        Self {
+            f1: build_normalizer(params.f1, params, &e.f1),
+            f2: build_normalizer(params.f2, params, &e.f2),
+            f3: build_normalizer(params.f3, params, &e.f3),
+            f4: build_normalizer(params.f4, params, &e.f4),
+            f5: build_normalizer(params.f5, params, &e.f5),
+            f6: build_normalizer(params.f6, params, &e.f6),
+        }
+    }
+
+    /// F1–F6 を正規化する (内部統計は更新しない)
+    pub fn normalize(&self, beta: &BaselineParams, features: &Features) -> PhiFeatures {
+        PhiFeatures {
+            f1: self.f1.normalize(features.f1_flight_time_median, beta.f1),
+            f2: self.f2.normalize(features.f2_flight_time_variance, beta.f2),
+            f3: self.f3.normalize(features.f3_correction_rate, beta.f3),
+            f4: self.f4.normalize(features.f4_burst_length, beta.f4),
+            f5: self.f5.normalize(features.f5_pause_count, beta.f5),
+            f6: self.f6.normalize(features.f6_pause_after_del_rate, beta.f6),
+        }
+    }
+
+    /// 打鍵による特徴量で内部統計を更新する。沈黙ティックの合成値では呼ばない
+    pub fn observe(&mut self, beta: &BaselineParams, features: &Features) {
+        self.f1.observe(features.f1_flight_time_median, beta.f1);
+        self.f2.observe(features.f2_flight_time_variance, beta.f2);
+        self.f3.observe(features.f3_correction_rate, beta.f3);
+        self.f4.observe(features.f4_burst_length, beta.f4);
+        self.f5.observe(features.f5_pause_count, beta.f5);
+        self.f6.observe(features.f6_pause_after_del_rate, beta.f6);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::analysis::params::{KeyPattern, OverrideRule, KEY_HISTORY_LEN};
+
+// Windows 仮想キーコード (修飾キー)
+const VK_SHIFT: u32 = 0x10;
+const VK_CONTROL: u32 = 0x11;
+const VK_MENU: u32 = 0x12;
+const VK_LWIN: u32 = 0x5B;
+const VK_RWIN: u32 = 0x5C;
+const VK_LSHIFT: u32 = 0xA0;
+const VK_RSHIFT: u32 = 0xA1;
+const VK_LCONTROL: u32 = 0xA2;
+const VK_RCONTROL: u32 = 0xA3;
+const VK_LMENU: u32 = 0xA4;
+const VK_RMENU: u32 = 0xA5;
+
+fn is_control(vk: u32) -> bool {
+    matches!(vk, VK_CONTROL | VK_LCONTROL | VK_RCONTROL)
+}
+
+pub(crate) fn is_modifier(vk: u32) -> bool {
+    matches!(
+        vk,
+        VK_SHIFT
+            | VK_CONTROL
+            | VK_MENU
+            | VK_LWIN
+            | VK_RWIN
+            | VK_LSHIFT
+            | VK_RSHIFT
+            | VK_LCONTROL
+            | VK_RCONTROL
+            | VK_LMENU
+            | VK_RMENU
+    )
+}
+
+/// 1 回のキー押下 (押下時に Ctrl が押されていたかを含む)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyPress {
+    pub vk: u32,
+    pub timestamp: u64,
+    pub ctrl: bool,
+}
+
+/// 直近 `KEY_HISTORY_LEN` 回のキー押下のリングバッファ。
+///
+/// 固定長配列のためキー入力ごとのアロケーションは発生しない。Ctrl の押下状態は
+/// 押下・解放イベントの両方から追跡し、各押下に記録する。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyHistory {
+    presses: [KeyPress; KEY_HISTORY_LEN],
+    // 次に書き込む位置
+    head: usize,
+    len: usize,
+    ctrl_down: bool,
+}
+
+impl Default for KeyHistory {
+    fn default() -> Self {
+        Self {
+            presses: [KeyPress::default(); KEY_HISTORY_LEN],
+            head: 0,
+            len: 0,
+            ctrl_down: false,
+        }
+    }
+}
+
+impl KeyHistory {
+    /// キーイベント (押下・解放) を 1 件取り込む
+    pub fn observe(&mut self, vk: u32, is_press: bool, timestamp: u64) {
+        if is_control(vk) {
+            self.ctrl_down = is_press;
+        }
+        if !is_press {
+            return;
+        }
+        self.presses[self.head] = KeyPress {
+            vk,
+            timestamp,
+            ctrl: self.ctrl_down,
+        };
+        self.head = (self.head + 1) % KEY_HISTORY_LEN;
+        self.len = (self.len + 1).min(KEY_HISTORY_LEN);
+    }
+
+    /// 新しい順に押下を返す
+    pub fn recent(&self) -> impl Iterator<Item = &KeyPress> + '_ {
+        (0..self.len)
+            .map(move |i| &self.presses[(self.head + KEY_HISTORY_LEN - 1 - i) % KEY_HISTORY_LEN])
+    }
+}
+
+fn key_matches(keys: &[u32], ctrl: Option<bool>, press: &KeyPress) -> bool {
+    keys.contains(&press.vk) && (ctrl.is_none() || ctrl == Some(press.ctrl))
+}
+
+/// `now` (Unix ms) 時点で履歴がパターンに一致するか
+pub fn pattern_matches(pattern: &KeyPattern, history: &KeyHistory, now: u64) -> bool {
+    match pattern {
+        KeyPattern::Streak {
+            keys,
+            ctrl,
+            min_count,
+        } => {
+            let run = history
+                .recent()
+                .take_while(|p| key_matches(keys, *ctrl, p))
+                .count();
+            run >= *min_count as usize
+        }
+        KeyPattern::Burst {
+            keys,
+            ctrl,
+            min_count,
+            within_ms,
+        } => {
+            let hits = history
+                .recent()
+                .take_while(|p| now.saturating_sub(p.timestamp) <= *within_ms)
+                .filter(|p| key_matches(keys, *ctrl, p))
+                .count();
+            hits >= *min_count as usize
+        }
+        KeyPattern::Sequence { steps, within_ms } => {
+            let mut presses = history.recent().filter(|p| !is_modifier(p.vk));
+            let mut last = None;
+            let mut first = 0;
+            for step in steps.iter().rev() {
+                match presses.next() {
+                    Some(p) if key_matches(&step.keys, step.ctrl, p) => {
+                        last.get_or_insert(p.timestamp);
+                        first = p.timestamp;
+                    }
+                    _ => return false,
+                }
+            }
+            // 最後のステップから within_ms を過ぎた沈黙ティックでは一致させない
+            last.is_some_and(|l: u64| {
+                l.saturating_sub(first) <= *within_ms && now.saturating_sub(l) <= *within_ms
+            })
+        }
+    }
+}
+
+/// 先頭から規則を評価し、最初に一致した規則のインデックスを返す
+pub fn evaluate(rules: &[OverrideRule], history: &KeyHistory, now: u64) -> Option<usize> {
+    rules
+        .iter()
+        .position(|r| pattern_matches(&r.pattern, history, now))
+}
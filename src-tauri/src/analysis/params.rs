@@ -0,0 +1,1607 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// 観測ビン数 (5×5 グリッド + ペナルティビン)
+pub const N_OBS: usize = 26;
+
+/// オーバーライド規則の評価に保持するキー押下履歴の長さ
+pub const KEY_HISTORY_LEN: usize = 64;
+
+/// ダイグラフ (キー対) ごとに保持する押下→押下間隔の数 (本人の通常値 = この中央値)
+pub const DIGRAPH_HISTORY: usize = 20;
+
+/// 状態遷移行列 N×N (行 = 遷移元、N = `states` の数)
+pub type TransitionMatrix = Vec<Vec<f64>>;
+
+/// エンジン全体の調整可能パラメータ。
+///
+/// 起動時に JSON ファイルから読み込まれ (`load_or_default`)、ファイルが無い・不正な場合は
+/// コンパイル済みの既定値 (`Default`) にフォールバックする。各セクションは省略可能で、
+/// 省略したフィールドは既定値で補われる。HMM の A・B・π はトップレベルに展開されるため、
+/// `train_hmm` の出力をそのまま読み込める。
+///
+/// 状態の数と並びは `states` で決まり、状態ごとの配列 (A・B・π・ガウス放射・Q・
+/// シュミット閾値・滞在時間分布) はすべてこの並びに従う。
+///
+/// ```jsonc
+/// {
+///   "states":      [{ "name": "flow", "label": "FLOW" }, { "name": "incubation", "label": "INCUBATION" }, ...],
+///   "transitions": [[0.80, 0.13, 0.07], ...],   // N×N, 行 = 遷移元
+///   "emissions":   [[0.01, 0.02, ...], ...],     // N×26, 行 = 状態
+///   "initial":     [0.5, 0.3, 0.2],
+///   "emission_floor": 0.04,
+///   "emission_model": "discrete",               // or "gaussian"
+///   "transition_model": "discrete",             // or "continuous_time"
+///   "generator": [[-0.20, 0.13, 0.07], ...],    // Q [1/s], 行和 = 0
+///   "initial_step_secs": 1.0,
+///   "gaussians": [{ "mean": [0.25, 0.73], "covariance": [[0.022, 0.0], [0.0, 0.022]] }, ...],
+///   "estimator":  { "backend": "hmm", "onnx": { "model_path": "", "runtime_path": "", "output": "probabilities" },
+///                   "ensemble": { "fusion": "weighted_average", "members": [{ "backend": "hmm", "weight": 1.0 }, ...] } },
+///   "hysteresis": { "policy": "ema", "alpha": 0.25, "penalty_alpha": 0.50, ... },
+///   "uncertainty": { "cold_start_updates": 5 },
+///   "state_events": { "min_dwell_ms": 3000 },
+///   "debug":      { "explain": false },
+///   "persistence": { "enabled": true, "fresh_secs": 600, "half_life_secs": 3600, "max_gap_secs": 259200 },
+///   "presence":   { "enabled": true, "away_after_secs": 90, "motion_grace_secs": 30, "lock_is_away": true, "resume_half_life_secs": 1800 },
+///   "calibration": { "baseline": "population", "profile": "default", "state": "flow", "flow_threshold": 0.7, ... },
+///   "overrides":  [{ "name": "backspace_streak", "pattern": { "kind": "streak", "keys": [8], "min_count": 5 }, "obs": 25 }, ...],
+///   "hsmm":       { "enabled": false, "durations": [{ "family": "gamma", "shape": 2.0, "scale": 15.0 }, ...] },
+///   "axes":       { "ewma_alpha": 0.3, "beta": { "f1": 250.0, ... },
+///                   "normalization": { "f1": "phi", "f3": "z_score", ..., "kappa": 2.0 },
+///                   "projection": null /* or fit_axes output */, ... },
+///   "features":   { "pause_threshold_ms": 2000, "burst_threshold_ms": 200, "max_hold_ms": 1000, "digraph_min_samples": 5, "silence": { ... } }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngineParams {
+    #[serde(flatten)]
+    pub hmm: HmmParams,
+    pub estimator: EstimatorParams,
+    pub hysteresis: HysteresisParams,
+    pub uncertainty: UncertaintyParams,
+    pub state_events: StateEventParams,
+    pub debug: DebugParams,
+    pub persistence: PersistenceParams,
+    pub presence: PresenceParams,
+    pub calibration: CalibrationParams,
+    /// 観測ビンのオーバーライド規則 (先頭から評価し、最初に一致した規則を適用)
+    pub overrides: Vec<OverrideRule>,
+    pub hsmm: HsmmParams,
+    pub axes: AxisParams,
+    pub features: FeatureParams,
+}
+
+impl Default for EngineParams {
+    fn default() -> Self {
+        Self {
+            hmm: HmmParams::default(),
+            estimator: EstimatorParams::default(),
+            hysteresis: HysteresisParams::default(),
+            uncertainty: UncertaintyParams::default(),
+            state_events: StateEventParams::default(),
+            debug: DebugParams::default(),
+            persistence: PersistenceParams::default(),
+            presence: PresenceParams::default(),
+            calibration: CalibrationParams::default(),
+            overrides: builtin_overrides(),
+            hsmm: HsmmParams::default(),
+            axes: AxisParams::default(),
+            features: FeatureParams::default(),
+        }
+    }
+}
+
+/// HMM パラメータ (状態の定義・遷移行列 A・放射行列 B・初期分布 π・放射確率の床 ε)。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HmmParams {
+    /// 状態の定義。並び順が確率ベクトル・各行列のインデックスになる
+    pub states: Vec<StateDef>,
+    pub transitions: TransitionMatrix,
+    /// N×26 (行 = 状態)
+    pub emissions: Vec<Vec<f64>>,
+    pub initial: Vec<f64>,
+    /// ε-floor: 放射確率の最小値を保証し、単一観測で状態確率が完全に0になることを防ぐ。
+    /// 0.04 (旧: 0.01) に引き上げることで「段階的天井」クラスタリングを緩和する。
+    /// 最大 p ≈ 0.88–0.90 程度に収まり、状態間の確率変化が滑らかになる。
+    pub emission_floor: f64,
+    /// 放射モデルの選択 (既定: 26 ビンの離散テーブル)
+    pub emission_model: EmissionModel,
+    /// `EmissionModel::Gaussian` 時の状態ごとの 2 次元ガウス密度 (X = Friction, Y = Engagement)
+    pub gaussians: Vec<GaussianEmission>,
+    /// 遷移の時間モデル (既定: 更新ごとに A を 1 回適用)
+    pub transition_model: TransitionModel,
+    /// `TransitionModel::ContinuousTime` 時の生成行列 Q [1/秒]。
+    /// 非対角 Q[i,j] ≥ 0 は i→j の遷移率、対角は行和が 0 になる負値 (平均滞在時間 = −1/Q[i,i] 秒)。
+    pub generator: TransitionMatrix,
+    /// 直前の更新時刻が無い最初の更新で用いる Δt [秒]
+    pub initial_step_secs: f64,
+}
+
+/// 1 状態の定義
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateDef {
+    /// IPC・ログ・設定で用いるキー (例: "flow")
+    pub name: String,
+    /// `analysis/behavioral_gt.py` の GT ラベル表記 (例: "FLOW")
+    pub label: String,
+}
+
+impl StateDef {
+    pub fn new(name: &str, label: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            label: label.to_string(),
+        }
+    }
+}
+
+/// 既定の 3 状態 (Flow / Incubation / Stuck)
+pub fn default_states() -> Vec<StateDef> {
+    vec![
+        StateDef::new("flow", "FLOW"),
+        StateDef::new("incubation", "INCUBATION"),
+        StateDef::new("stuck", "STUCK"),
+    ]
+}
+
+impl HmmParams {
+    /// 状態数 N
+    pub fn n_states(&self) -> usize {
+        self.states.len()
+    }
+
+    /// 名前から状態インデックスを引く
+    pub fn state_index(&self, name: &str) -> Option<usize> {
+        self.states.iter().position(|s| s.name == name)
+    }
+
+    /// GT ラベル (FLOW 等) から状態インデックスを引く (UNKNOWN などは None)
+    pub fn state_by_label(&self, label: &str) -> Option<usize> {
+        self.states.iter().position(|s| s.label == label)
+    }
+
+    /// 状態インデックスの名前 (範囲外は "unknown")
+    pub fn state_name(&self, index: usize) -> &str {
+        self.states
+            .get(index)
+            .map_or("unknown", |s| s.name.as_str())
+    }
+
+    /// 状態インデックスの GT ラベル (範囲外は "UNKNOWN")
+    pub fn state_label(&self, index: usize) -> &str {
+        self.states
+            .get(index)
+            .map_or("UNKNOWN", |s| s.label.as_str())
+    }
+}
+
+/// 遷移行列の時間モデル
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransitionModel {
+    /// 更新間隔に関わらず `transitions` を 1 回適用する (離散時間)
+    #[default]
+    Discrete,
+    /// 直前の更新からの経過時間 Δt に応じて A(Δt) = exp(Q·Δt) を適用する (連続時間)
+    ContinuousTime,
+}
+
+/// 放射確率の計算方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmissionModel {
+    /// EWMA 後の (X, Y) を 5×5 グリッドに量子化し、B[state, bin] を引く
+    #[default]
+    Discrete,
+    /// EWMA 後の連続値 (X, Y) に状態ごとの 2 次元ガウス密度を当てる。
+    /// オーバーライド規則の一致時は離散モデルと同じく B[state, obs] を用いる。
+    Gaussian,
+}
+
+/// 2 次元ガウス分布 N(mean, covariance)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GaussianEmission {
+    /// [X, Y]
+    pub mean: [f64; 2],
+    /// 2×2 共分散行列 (対称・正定値)
+    pub covariance: [[f64; 2]; 2],
+}
+
+/// 表示用ヒステリシス層 (display_probs) の設定。
+/// `policy` で方式を選び、各方式はそれぞれのフィールドだけを参照する。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HysteresisParams {
+    pub policy: HysteresisKind,
+    /// ema: 通常更新時の α (時定数 ≈ 1/α 更新)
+    pub alpha: f64,
+    /// ema: オーバーライド規則の一致時の既定 α (素早く収束させる)
+    pub penalty_alpha: f64,
+    /// schmitt: 状態ごとの進入・離脱閾値
+    pub schmitt: SchmittThresholds,
+    /// min_dwell: 新しい状態が raw の argmax であり続けるべき時間 [ms]
+    pub min_dwell_ms: u64,
+    /// confirmations: 連続確認の回数と閾値
+    pub confirmations: ConfirmationParams,
+}
+
+/// ヒステリシス方式 (実装は `hysteresis.rs`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HysteresisKind {
+    /// display ← α·raw + (1−α)·display (既定)
+    #[default]
+    Ema,
+    /// 進入・離脱閾値を分けたシュミットトリガ
+    Schmitt,
+    /// 挑戦者が一定時間 argmax であり続けるまで表示状態をロック
+    MinDwell,
+    /// 挑戦者の事後確率が閾値以上の更新が k 回連続したら切り替える
+    Confirmations,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchmittThresholds {
+    /// raw[j] がこの値以上になったら状態 j へ切り替えられる
+    pub enter: Vec<f64>,
+    /// 表示中の状態 j は raw[j] がこの値未満になるまで保持する (exit < enter)
+    pub exit: Vec<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfirmationParams {
+    pub k: u32,
+    pub threshold: f64,
+}
+
+/// 不確かさ指標 (`StateUncertainty`) の設定
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UncertaintyParams {
+    /// 起動・リセット後、この回数の更新を終えるまでは証拠不足 (cold start) とみなす
+    pub cold_start_updates: u32,
+}
+
+impl Default for UncertaintyParams {
+    fn default() -> Self {
+        Self {
+            cold_start_updates: 5,
+        }
+    }
+}
+
+/// 終了時の状態保存と起動時のウォームスタートの設定 (`persistence.rs`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersistenceParams {
+    pub enabled: bool,
+    /// 保存からの経過がこの時間以内なら保存値をそのまま復元する [秒]
+    pub fresh_secs: f64,
+    /// fresh_secs を超えた分について、保存値の重みが半分になる時間 [秒]。
+    /// 残りの重みは事前分布 (initial / EWMA 初期値) に割り当てる
+    pub half_life_secs: f64,
+    /// これより古い保存状態は破棄してコールドスタートする [秒]
+    pub max_gap_secs: f64,
+}
+
+impl Default for PersistenceParams {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            fresh_secs: 600.0,
+            half_life_secs: 3600.0,
+            max_gap_secs: 259_200.0,
+        }
+    }
+}
+
+/// 離席の判定と復帰の設定 (`presence.rs`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresenceParams {
+    pub enabled: bool,
+    /// キーボード・マウスとも入力が無い状態がこの時間続いたら離席とみなす [秒]
+    pub away_after_secs: f64,
+    /// 加速度センサーがこの時間以内に動きを検出していれば、無入力でも在席とみなす [秒]
+    pub motion_grace_secs: f64,
+    /// 画面ロック中は無入力の時間に関わらず離席とみなす
+    pub lock_is_away: bool,
+    /// 復帰時、離席前の信念の重みが半分になる離席時間 [秒]。
+    /// 残りの重みは事前分布 (initial / EWMA 初期値) に割り当てる
+    pub resume_half_life_secs: f64,
+}
+
+impl Default for PresenceParams {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            away_after_secs: 90.0,
+            motion_grace_secs: 30.0,
+            lock_is_away: true,
+            resume_half_life_secs: 1800.0,
+        }
+    }
+}
+
+/// φ に用いる β の出所
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BaselineSource {
+    /// 固定の母集団中央値 (`axes.beta`)
+    #[default]
+    Population,
+    /// 本人のデータから学習した β (キャリブレーション完了後のみ。それまでは母集団値)
+    Personal,
+}
+
+/// 個人ベースライン β のオンライン学習の設定 (`calibration.rs`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CalibrationParams {
+    pub baseline: BaselineSource,
+    /// 学習結果を保存するプロファイル名 (GSE-sessions/profiles/<profile>.json)
+    pub profile: String,
+    /// 学習に用いる状態 (`states` の名前、既定: "flow")
+    pub state: String,
+    /// display_probs[state] がこの値以上の打鍵だけを学習に用いる
+    pub flow_threshold: f64,
+    /// 個人 β を使い始めるまでに必要な学習サンプル数 (キャリブレーション期間)
+    pub min_samples: u32,
+    /// 移動中央値の 1 ステップの相対幅 (β ← β ± η·β)
+    pub learning_rate: f64,
+    /// 個人 β を母集団 β の [1/max_ratio, max_ratio] 倍に制限する
+    pub max_ratio: f64,
+}
+
+impl Default for CalibrationParams {
+    fn default() -> Self {
+        Self {
+            baseline: BaselineSource::Population,
+            profile: "default".to_string(),
+            state: "flow".to_string(),
+            flow_threshold: 0.7,
+            min_samples: 300,
+            learning_rate: 0.02,
+            max_ratio: 4.0,
+        }
+    }
+}
+
+/// 状態遷移イベント (`StateTransition`) の設定
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StateEventParams {
+    /// display_probs の argmax が変わってから遷移として確定するまでの最小継続時間 [ms]
+    pub min_dwell_ms: u64,
+}
+
+impl Default for StateEventParams {
+    fn default() -> Self {
+        Self { min_dwell_ms: 3000 }
+    }
+}
+
+/// 直近のキー入力パターンに一致したとき、観測ビンを強制的に置き換える規則。
+/// 例: 5 回以上の連続 Backspace → ペナルティビン 25 (ほぼ確実に Stuck)。
+/// ボーナス規則は Flow が優勢なビン (例: 4 = 低 Friction × 高 Engagement) を指定する。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OverrideRule {
+    pub name: String,
+    pub pattern: KeyPattern,
+    /// 一致時に用いる観測ビン [0..25]
+    pub obs: usize,
+    /// 一致時のヒステリシス α (省略時は `hysteresis.penalty_alpha`)
+    #[serde(default)]
+    pub display_alpha: Option<f64>,
+}
+
+/// キー入力パターン (`overrides.rs` で評価)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum KeyPattern {
+    /// 直近の押下が連続して `min_count` 回以上 `keys` のいずれか。
+    /// 修飾キーを含むすべての押下が連続を途切れさせる (従来の Backspace Streak と同じ)。
+    Streak {
+        keys: Vec<u32>,
+        /// Some(true) = Ctrl 押下中のみ、Some(false) = Ctrl 非押下時のみ、None = 問わない
+        #[serde(default)]
+        ctrl: Option<bool>,
+        min_count: u32,
+    },
+    /// 直近 `within_ms` の間に `keys` のいずれかが `min_count` 回以上押された
+    Burst {
+        keys: Vec<u32>,
+        #[serde(default)]
+        ctrl: Option<bool>,
+        min_count: u32,
+        within_ms: u64,
+    },
+    /// 修飾キー以外の直近の押下が `steps` と順に一致し、最初から最後まで・最後から現在までが
+    /// それぞれ `within_ms` 以内
+    Sequence { steps: Vec<KeyMatcher>, within_ms: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyMatcher {
+    pub keys: Vec<u32>,
+    #[serde(default)]
+    pub ctrl: Option<bool>,
+}
+
+/// 組み込みのオーバーライド規則
+pub fn builtin_overrides() -> Vec<OverrideRule> {
+    const VK_BACK: u32 = 0x08;
+    const VK_DELETE: u32 = 0x2E;
+    const VK_A: u32 = 0x41;
+    const VK_Z: u32 = 0x5A;
+    let penalty = |name: &str, pattern: KeyPattern| OverrideRule {
+        name: name.to_string(),
+        pattern,
+        obs: 25,
+        display_alpha: None,
+    };
+
+    vec![
+        // 5回以上の連続Backspaceを「大きな修正＝Stuck」とする安全装置
+        penalty(
+            "backspace_streak",
+            KeyPattern::Streak {
+                keys: vec![VK_BACK],
+                ctrl: None,
+                min_count: 5,
+            },
+        ),
+        penalty(
+            "delete_streak",
+            KeyPattern::Streak {
+                keys: vec![VK_DELETE],
+                ctrl: None,
+                min_count: 5,
+            },
+        ),
+        // Ctrl+Backspace / Ctrl+Delete は 1 回で単語単位を消すため少ない回数で発火させる
+        penalty(
+            "ctrl_word_delete",
+            KeyPattern::Burst {
+                keys: vec![VK_BACK, VK_DELETE],
+                ctrl: Some(true),
+                min_count: 3,
+                within_ms: 3000,
+            },
+        ),
+        penalty(
+            "undo_storm",
+            KeyPattern::Burst {
+                keys: vec![VK_Z],
+                ctrl: Some(true),
+                min_count: 4,
+                within_ms: 3000,
+            },
+        ),
+        penalty(
+            "select_all_delete",
+            KeyPattern::Sequence {
+                steps: vec![
+                    KeyMatcher {
+                        keys: vec![VK_A],
+                        ctrl: Some(true),
+                    },
+                    KeyMatcher {
+                        keys: vec![VK_BACK, VK_DELETE],
+                        ctrl: None,
+                    },
+                ],
+                within_ms: 2000,
+            },
+        ),
+    ]
+}
+
+/// 診断用の設定
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DebugParams {
+    /// 更新ごとに `UpdateExplanation` を生成し、セッションログに記録する
+    pub explain: bool,
+}
+
+/// 状態推定器 (`StateEstimator`) の選択
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EstimatorParams {
+    pub backend: EstimatorBackend,
+    /// `backend = "onnx"` 時の設定
+    pub onnx: OnnxParams,
+    /// `backend = "ensemble"` 時の設定
+    pub ensemble: EnsembleParams,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EstimatorBackend {
+    /// HMM / HSMM 前向きフィルタ (既定)
+    #[default]
+    Hmm,
+    /// ONNX Runtime で学習済み分類器を実行する
+    Onnx,
+    /// 複数の推定器を同じ観測で並走させ、出力を融合する
+    Ensemble,
+}
+
+/// ONNX 推定器の設定。
+///
+/// モデルは float32 の入力 1 つ (shape [1, 10]) を受け取り、
+/// [F1, F2, F3, F4, F5, F6, raw X, raw Y, EWMA X, EWMA Y] を与えられて
+/// shape [1, N] (N = `hmm.states` の数) の状態確率を `states` の並びで出力すること。
+/// N 個より多く出力する場合は先頭 N 個を用いる。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OnnxParams {
+    /// .onnx モデルファイルのパス
+    pub model_path: String,
+    /// ONNX Runtime 共有ライブラリ (onnxruntime.dll) のパス。
+    /// 空の場合は `ORT_DYLIB_PATH` 環境変数または既定の検索パスを用いる (load-dynamic)。
+    pub runtime_path: String,
+    pub output: OnnxOutput,
+}
+
+/// ONNX モデルの出力の解釈
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnnxOutput {
+    /// 確率 (合計 1 に再正規化する)
+    #[default]
+    Probabilities,
+    /// ロジット (softmax をかける)
+    Logits,
+}
+
+/// アンサンブル推定器の設定。
+///
+/// 各メンバーは自身の信念を独立に保持し、毎更新で同じ特徴量・観測を受け取る。
+/// ヒステリシス層は融合後の確率にのみかかる。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnsembleParams {
+    pub fusion: FusionRule,
+    pub members: Vec<EnsembleMember>,
+}
+
+/// メンバー出力の融合方法 (w_k = メンバーの信頼度 `weight`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FusionRule {
+    /// p_j = Σ_k w_k · p_k,j / Σ_k w_k
+    #[default]
+    WeightedAverage,
+    /// p_j ∝ Π_k p_k,j ^ w_k (Product of Experts)
+    ProductOfExperts,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnsembleMember {
+    /// "hmm" または "onnx" (入れ子の "ensemble" は不可)
+    pub backend: EstimatorBackend,
+    /// 信頼度 (融合時の重み、>= 0)
+    pub weight: f64,
+    /// hmm メンバーが使うパラメータファイル (例: `train_hmm` の出力)。
+    /// 読み込むのは HMM / HSMM のセクションのみで、空の場合はエンジン本体のパラメータを使う。
+    pub params_path: String,
+    /// onnx メンバーの設定
+    pub onnx: OnnxParams,
+}
+
+impl Default for EnsembleMember {
+    fn default() -> Self {
+        Self {
+            backend: EstimatorBackend::Hmm,
+            weight: 1.0,
+            params_path: String::new(),
+            onnx: OnnxParams::default(),
+        }
+    }
+}
+
+/// 隠れセミマルコフモデル (HSMM) の設定。
+///
+/// 有効時は A / Q の自己遷移 (幾何分布の滞在時間) の代わりに、状態ごとの滞在時間分布から
+/// 求めたハザードで状態を離脱させる。離脱先は A / Q の非対角成分の比で按分する。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HsmmParams {
+    pub enabled: bool,
+    /// 状態ごとの滞在時間分布 [秒] (`states` の並び)
+    pub durations: Vec<DurationDistribution>,
+    /// 状態ごとに保持する滞在時間コホートの上限 (超えたら近いもの同士を統合)
+    pub max_cohorts: usize,
+}
+
+/// 滞在時間分布 [秒]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "family", rename_all = "snake_case")]
+pub enum DurationDistribution {
+    /// ガンマ分布 (平均 = shape × scale)
+    Gamma { shape: f64, scale: f64 },
+    /// 対数正規分布 (ln D ~ N(mu, sigma²))
+    LogNormal { mu: f64, sigma: f64 },
+}
+
+impl Default for HsmmParams {
+    fn default() -> Self {
+        // 実際の Incubation / Stuck エピソードは数十秒〜数分続く
+        Self {
+            enabled: false,
+            durations: vec![
+                // Flow: 平均 30s
+                DurationDistribution::Gamma {
+                    shape: 2.0,
+                    scale: 15.0,
+                },
+                // Incubation: 平均 60s
+                DurationDistribution::Gamma {
+                    shape: 2.0,
+                    scale: 30.0,
+                },
+                // Stuck: 平均 45s
+                DurationDistribution::Gamma {
+                    shape: 1.5,
+                    scale: 30.0,
+                },
+            ],
+            max_cohorts: 32,
+        }
+    }
+}
+
+impl Default for HysteresisParams {
+    fn default() -> Self {
+        Self {
+            policy: HysteresisKind::Ema,
+            alpha: 0.25,
+            penalty_alpha: 0.50,
+            schmitt: SchmittThresholds::default(),
+            // EMA の時定数 (≈ 4 更新 ≈ 4 秒) に合わせる
+            min_dwell_ms: 4000,
+            confirmations: ConfirmationParams::default(),
+        }
+    }
+}
+
+impl Default for SchmittThresholds {
+    fn default() -> Self {
+        Self {
+            enter: vec![0.60, 0.55, 0.60],
+            exit: vec![0.40, 0.35, 0.40],
+        }
+    }
+}
+
+impl Default for ConfirmationParams {
+    fn default() -> Self {
+        Self {
+            k: 4,
+            threshold: 0.50,
+        }
+    }
+}
+
+/// 潜在軸 (Friction × Engagement) の算出パラメータ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AxisParams {
+    /// 2軸 EWMA の α (新値の重み)
+    pub ewma_alpha: f64,
+    /// φ 正規化の基準値 β
+    pub beta: BaselineParams,
+    /// X (Friction) の重み
+    pub friction: FrictionWeights,
+    /// Y (Engagement) の重み
+    pub engagement: EngagementWeights,
+    /// 特徴量ごとの正規化方式 (既定: すべて φ)
+    pub normalization: NormalizationParams,
+    /// `fit_axes` が学習した射影。None なら friction / engagement の手設定の重みを用いる
+    pub projection: Option<LearnedProjection>,
+}
+
+impl Default for AxisParams {
+    fn default() -> Self {
+        Self {
+            ewma_alpha: 0.3,
+            beta: BaselineParams::default(),
+            friction: FrictionWeights::default(),
+            engagement: EngagementWeights::default(),
+            normalization: NormalizationParams::default(),
+            projection: None,
+        }
+    }
+}
+
+/// φ(x, β) の基準値 (母集団中央値の想定)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BaselineParams {
+    /// 標準FT中央値 (ms)
+    pub f1: f64,
+    /// 標準FT分散 (ms²)。学習した射影 (`axes.projection`) でのみ用いる
+    pub f2: f64,
+    /// 標準修正率 (10%)
+    pub f3: f64,
+    /// 標準バースト長 (文字数)
+    pub f4: f64,
+    /// 標準ポーズ回数 (3回/30s)
+    pub f5: f64,
+    /// 標準削除後停止率 (15%)
+    pub f6: f64,
+}
+
+impl Default for BaselineParams {
+    fn default() -> Self {
+        Self {
+            f1: 250.0,
+            f2: 10000.0,
+            f3: 0.10,
+            f4: 2.0,
+            f5: 3.0,
+            f6: 0.15,
+        }
+    }
+}
+
+/// X (Friction) = f3·φ(F3) + f6·φ(F6) + f1·φ(F1) + f5·φ(F5)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrictionWeights {
+    pub f3: f64,
+    pub f6: f64,
+    pub f1: f64,
+    pub f5: f64,
+}
+
+impl Default for FrictionWeights {
+    fn default() -> Self {
+        Self {
+            f3: 0.30,
+            f6: 0.25,
+            f1: 0.25,
+            f5: 0.20,
+        }
+    }
+}
+
+/// Y (Engagement) = f4·φ(F4) + f1·(1 − φ(F1)) + f5·(1 − φ(F5))
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngagementWeights {
+    pub f4: f64,
+    pub f1: f64,
+    pub f5: f64,
+}
+
+impl Default for EngagementWeights {
+    fn default() -> Self {
+        Self {
+            f4: 0.40,
+            f1: 0.35,
+            f5: 0.25,
+        }
+    }
+}
+
+/// 正規化した F1–F6 から潜在軸 (X, Y) への線形射影 (`fit_axes` の出力)。
+///
+/// ```text
+/// X = clamp(friction.bias + Σ friction.weights.fᵢ · φ(Fᵢ), 0, 1)
+/// Y = clamp(engagement.bias + Σ engagement.weights.fᵢ · φ(Fᵢ), 0, 1)
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LearnedProjection {
+    /// 学習方法 (記録用)
+    pub method: ProjectionMethod,
+    pub friction: LinearAxis,
+    pub engagement: LinearAxis,
+}
+
+impl Default for LearnedProjection {
+    /// 手設定の重みと同じ射影 (Y の (1 − φ) 項は bias と負の重みに展開)
+    fn default() -> Self {
+        let f = FrictionWeights::default();
+        let e = EngagementWeights::default();
+        Self {
+            method: ProjectionMethod::HandSet,
+            friction: LinearAxis {
+                bias: 0.0,
+                weights: FeatureWeights {
+                    f1: f.f1,
+                    f3: f.f3,
+                    f5: f.f5,
+                    f6: f.f6,
+                    ..FeatureWeights::default()
+                },
+            },
+            engagement: LinearAxis {
+                bias: e.f1 + e.f5,
+                weights: FeatureWeights {
+                    f1: -e.f1,
+                    f4: e.f4,
+                    f5: -e.f5,
+                    ..FeatureWeights::default()
+                },
+            },
+        }
+    }
+}
+
+/// 射影の学習方法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectionMethod {
+    /// 手設定の重み
+    #[default]
+    HandSet,
+    /// 主成分分析 (第 1・第 2 主成分)
+    Pca,
+    /// 2 因子の因子分析 (EM)
+    Factor,
+    /// GT ラベルに対するリッジ回帰
+    Regression,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinearAxis {
+    pub bias: f64,
+    pub weights: FeatureWeights,
+}
+
+/// φ(F1)〜φ(F6) の重み (負値可)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeatureWeights {
+    pub f1: f64,
+    pub f2: f64,
+    pub f3: f64,
+    pub f4: f64,
+    pub f5: f64,
+    pub f6: f64,
+}
+
+/// 特徴量を [0, 1] に写す正規化の設定 (実装は `normalize.rs`)。
+/// `f1`〜`f6` で特徴量ごとに方式を選び、各方式はそれぞれの係数だけを参照する。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalizationParams {
+    pub f1: NormalizerKind,
+    pub f2: NormalizerKind,
+    pub f3: NormalizerKind,
+    pub f4: NormalizerKind,
+    pub f5: NormalizerKind,
+    pub f6: NormalizerKind,
+    /// phi: 傾き κ (3β で 1.0 となる 2.0 が既定)
+    pub kappa: f64,
+    /// z_score: 移動平均・分散
+    pub z_score: ZScoreParams,
+    /// logistic: σ(slope · (x − β) / β) の傾き
+    pub logistic_slope: f64,
+    /// ecdf: 特徴量ごとの校正用標本
+    pub ecdf: EcdfSamples,
+}
+
+impl Default for NormalizationParams {
+    fn default() -> Self {
+        Self {
+            f1: NormalizerKind::Phi,
+            f2: NormalizerKind::Phi,
+            f3: NormalizerKind::Phi,
+            f4: NormalizerKind::Phi,
+            f5: NormalizerKind::Phi,
+            f6: NormalizerKind::Phi,
+            kappa: 2.0,
+            z_score: ZScoreParams::default(),
+            logistic_slope: 4.0,
+            ecdf: EcdfSamples::default(),
+        }
+    }
+}
+
+/// 正規化方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizerKind {
+    /// clamp((x − β) / (κ·β), 0, 1) (既定)。β 未満はすべて 0
+    #[default]
+    Phi,
+    /// 移動平均・標準偏差による両側 z-score。±clip を 0 と 1 に写す
+    ZScore,
+    /// 校正用標本の経験累積分布 (分位点)
+    Ecdf,
+    /// β を中心 (0.5) とするロジスティック関数
+    Logistic,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ZScoreParams {
+    /// 移動平均・分散の指数重み α (打鍵ごと)
+    pub alpha: f64,
+    /// この |z| で 0 / 1 に飽和する
+    pub clip: f64,
+}
+
+impl Default for ZScoreParams {
+    fn default() -> Self {
+        Self {
+            alpha: 0.01,
+            clip: 3.0,
+        }
+    }
+}
+
+/// ecdf の校正用標本 (順不同)。ecdf を選んだ特徴量は空にできない
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EcdfSamples {
+    pub f1: Vec<f64>,
+    pub f2: Vec<f64>,
+    pub f3: Vec<f64>,
+    pub f4: Vec<f64>,
+    pub f5: Vec<f64>,
+    pub f6: Vec<f64>,
+}
+
+/// FeatureExtractor の閾値
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeatureParams {
+    /// ポーズとみなす無入力間隔 (ms)。F5/F6 の判定と F1/F2 の外れ値除外に使う
+    pub pause_threshold_ms: u64,
+    /// バーストとみなす Flight Time の上限 (ms)。F4 の判定に使う
+    pub burst_threshold_ms: u64,
+    /// ホールド時間 (押下→解放) として採用する上限 (ms)。F7/F8 の外れ値除外に使う。
+    /// これより長い押下 (押しっぱなし・オートリピート・解放の取りこぼし) は除外する。
+    /// これより古い未解放の押下は、同じキーの次の押下で新しい押下に置き換える
+    pub max_hold_ms: u64,
+    /// F9 でキー対の通常値 (押下→押下間隔の中央値) を使い始めるのに必要な履歴の数。
+    /// これより少ないキー対は F9 に数えない
+    pub digraph_min_samples: usize,
+    /// サイレンス期間中の合成特徴量
+    pub silence: SilenceParams,
+}
+
+impl Default for FeatureParams {
+    fn default() -> Self {
+        Self {
+            pause_threshold_ms: 2000,
+            burst_threshold_ms: 200,
+            max_hold_ms: 1000,
+            digraph_min_samples: 5,
+            silence: SilenceParams::default(),
+        }
+    }
+}
+
+/// `make_silence_observation()` の合成ランプ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SilenceParams {
+    /// これ未満の無音はスキップ (s)
+    pub min_silence_secs: f64,
+    /// F5: 何秒ごとに 1 ポーズと換算するか
+    pub secs_per_pause: f64,
+    /// F5 の上限
+    pub max_pause_count: f64,
+    /// F6 合成の開始時刻 (s)
+    pub f6_onset_secs: f64,
+    /// F6 合成が 1.0 増加するのに要する秒数 (傾きの逆数)
+    pub f6_ramp_secs: f64,
+    /// F6 合成の上限
+    pub f6_max: f64,
+    /// F3 合成の開始時刻 (s)
+    pub f3_onset_secs: f64,
+    /// F3 合成が 1.0 増加するのに要する秒数 (傾きの逆数)
+    pub f3_ramp_secs: f64,
+    /// F3 合成の上限
+    pub f3_max: f64,
+}
+
+impl Default for SilenceParams {
+    fn default() -> Self {
+        Self {
+            min_silence_secs: 2.0,
+            secs_per_pause: 2.0,
+            max_pause_count: 20.0,
+            f6_onset_secs: 20.0,
+            f6_ramp_secs: 60.0,
+            f6_max: 0.50,
+            f3_onset_secs: 30.0,
+            f3_ramp_secs: 100.0,
+            f3_max: 0.40,
+        }
+    }
+}
+
+impl Default for HmmParams {
+    /// 手動チューニング済みの既定パラメータ
+    fn default() -> Self {
+        // Transition probabilities
+        // FLOW -> FLOW: 0.80  (escape time 1/(1-0.80)=5s; reduced from 0.92 to prevent saturation)
+        // FLOW -> INCUBATION: 0.13
+        // FLOW -> STUCK: 0.07
+        // INCUBATION -> FLOW: 0.12
+        // INCUBATION -> INCUBATION: 0.80  (Sio & Ormerod 2009)
+        // INCUBATION -> STUCK: 0.08
+        // STUCK -> FLOW: 0.06
+        // STUCK -> INCUBATION: 0.18
+        // STUCK -> STUCK: 0.76  (Hall et al. 2024)
+        let transitions = vec![
+            vec![0.80, 0.13, 0.07],
+            vec![0.12, 0.80, 0.08],
+            vec![0.06, 0.18, 0.76],
+        ];
+
+        // Generator Q [1/s]: 上の A を「1 秒に 1 回の更新」とみなした遷移率
+        // (沈黙ティックは 1 秒間隔)。平均滞在時間 Flow 5.0s / Incubation 5.0s / Stuck 4.2s
+        let generator = vec![
+            vec![-0.20, 0.13, 0.07],
+            vec![0.12, -0.20, 0.08],
+            vec![0.06, 0.18, -0.24],
+        ];
+
+        // Emissions B: 3 states × 26 bins
+        //
+        // Grid layout: obs = x_bin * 5 + y_bin
+        //
+        //        x→  0(lo F)  1      2      3      4(hi F)
+        //  y↓
+        //  0(lo E)   [0]     [5]   [10]   [15]   [20]
+        //  1         [1]     [6]   [11]   [16]   [21]
+        //  2         [2]     [7]   [12]   [17]   [22]
+        //  3         [3]     [8]   [13]   [18]   [23]
+        //  4(hi E)   [4]     [9]   [14]   [19]   [24]
+        //  penalty   [25]
+        //
+        // Flow:       peaks at low Friction (x=0,1) × high Engagement (y=3,4)
+        // Incubation: peaks at low-mid Friction (x=0,1,2) × low Engagement (y=0,1)
+        // Stuck:      peaks at high Friction (x=3,4) × low Engagement (y=0,1)
+        //
+        // Penalty bin (obs=25): target of the built-in override rules
+        // (backspace_streak ≥ 5, undo storm, …) → near-certain Stuck.
+        // Each state's non-penalty bins sum to ≈1.0; HMM normalizes anyway.
+        #[rustfmt::skip]
+        let emissions = vec![
+            // ── Flow (State 0) ─────────────────────────── non-penalty sum = 1.00
+            vec![
+            //  x=0 (low F)    y: 0     1     2     3     4
+                               0.01, 0.02, 0.05, 0.16, 0.20,
+            //  x=1            y: 0     1     2     3     4
+                               0.01, 0.02, 0.05, 0.14, 0.16,
+            //  x=2            y: 0     1     2     3     4
+                               0.00, 0.01, 0.03, 0.06, 0.08,
+            //  x=3            y: 0     1     2     3     4
+                               0.00, 0.00, 0.00, 0.00, 0.00,
+            //  x=4 (high F)   y: 0     1     2     3     4
+                               0.00, 0.00, 0.00, 0.00, 0.00,
+            //  penalty bin
+                               0.00,
+            ],
+
+            // ── Incubation (State 1) ──────────────────── non-penalty sum = 1.00
+            vec![
+            //  x=0 (low F)    y: 0     1     2     3     4
+                               0.15, 0.10, 0.04, 0.01, 0.00,
+            //  x=1            y: 0     1     2     3     4
+                               0.14, 0.10, 0.04, 0.01, 0.00,
+            //  x=2            y: 0     1     2     3     4
+                               0.10, 0.08, 0.03, 0.01, 0.00,
+            //  x=3            y: 0     1     2     3     4
+                               0.05, 0.04, 0.01, 0.00, 0.00,
+            //  x=4 (high F)   y: 0     1     2     3     4
+                               0.04, 0.03, 0.01, 0.00, 0.00,
+            //  penalty bin
+                               0.01,
+            ],
+
+            // ── Stuck (State 2) ───────────────── non-penalty sum = 1.00 (+0.99)
+            vec![
+            //  x=0 (low F)    y: 0     1     2     3     4
+                               0.00, 0.00, 0.00, 0.00, 0.00,
+            //  x=1            y: 0     1     2     3     4
+                               0.00, 0.00, 0.00, 0.00, 0.00,
+            //  x=2            y: 0     1     2     3     4
+                               0.02, 0.04, 0.02, 0.00, 0.00,
+            //  x=3            y: 0     1     2     3     4
+                               0.10, 0.16, 0.07, 0.02, 0.00,
+            //  x=4 (high F)   y: 0     1     2     3     4
+                               0.16, 0.22, 0.12, 0.05, 0.02,
+            //  penalty bin  (override rules, e.g. backspace streak ≥5 → near-certain Stuck)
+                               0.99,
+            ],
+        ];
+
+        // 初期事前確率: バランス型で開始 (Flow偏重を排除)
+        let initial = vec![0.5, 0.3, 0.2];
+
+        // ガウス放射の既定値: 上の離散テーブルの重心・広がりに合わせた対角共分散
+        let gaussians = vec![
+            // Flow: 低 Friction × 高 Engagement
+            GaussianEmission {
+                mean: [0.25, 0.73],
+                covariance: [[0.022, 0.0], [0.0, 0.022]],
+            },
+            // Incubation: 低〜中 Friction × 低 Engagement
+            GaussianEmission {
+                mean: [0.37, 0.25],
+                covariance: [[0.050, 0.0], [0.0, 0.025]],
+            },
+            // Stuck: 高 Friction × 低 Engagement
+            GaussianEmission {
+                mean: [0.80, 0.33],
+                covariance: [[0.020, 0.0], [0.0, 0.030]],
+            },
+        ];
+
+        Self {
+            states: default_states(),
+            transitions,
+            emissions,
+            initial,
+            emission_floor: 0.04,
+            emission_model: EmissionModel::Discrete,
+            gaussians,
+            transition_model: TransitionModel::Discrete,
+            generator,
+            initial_step_secs: 1.0,
+        }
+    }
+}
+
+impl EngineParams {
+    /// JSON ファイルからパラメータを読み込み、検証する。
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+        let params: Self =
+            serde_json::from_str(&text).map_err(|e| format!("failed to parse {:?}: {}", path, e))?;
+        params
+            .validate()
+            .map_err(|e| format!("invalid parameters in {:?}: {}", path, e))?;
+        Ok(params)
+    }
+
+    /// ファイルがあれば読み込み、無い・不正な場合は既定値を返す。
+    pub fn load_or_default(path: &Path) -> Self {
+        if !path.exists() {
+            tracing::info!("Engine params: {:?} not found, using defaults", path);
+            return Self::default();
+        }
+        match Self::load(path) {
+            Ok(p) => {
+                tracing::info!("Engine params loaded from {:?}", path);
+                p
+            }
+            Err(e) => {
+                tracing::warn!("Engine params: {}. Falling back to defaults", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// JSON ファイルへパラメータを書き出す (整形済み)。
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("failed to write {:?}: {}", path, e))
+    }
+
+    /// 値域を検証する。最初に見つかった問題をエラーとして返す。
+    pub fn validate(&self) -> Result<(), String> {
+        let hmm = &self.hmm;
+        let n = hmm.n_states();
+        if n < 2 {
+            return Err("states must define at least 2 states".to_string());
+        }
+        for (i, state) in hmm.states.iter().enumerate() {
+            if state.name.is_empty()
+                || !state
+                    .name
+                    .chars()
+                    .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '_')
+            {
+                return Err(format!(
+                    "states[{}].name must be non-empty and use only [a-z0-9_]",
+                    i
+                ));
+            }
+            if state.label.is_empty() {
+                return Err(format!("states[{}].label must not be empty", i));
+            }
+            if hmm.states[..i]
+                .iter()
+                .any(|s| s.name == state.name || s.label == state.label)
+            {
+                return Err(format!(
+                    "states[{}] ({}) duplicates the name or label of an earlier state",
+                    i, state.name
+                ));
+            }
+        }
+        check_len(hmm.transitions.len(), n, "transitions")?;
+        for (i, row) in hmm.transitions.iter().enumerate() {
+            let name = format!("transitions[{}]", i);
+            check_len(row.len(), n, &name)?;
+            check_distribution(row, &name)?;
+        }
+        check_len(hmm.emissions.len(), n, "emissions")?;
+        for (i, row) in hmm.emissions.iter().enumerate() {
+            if row.len() != N_OBS {
+                return Err(format!("emissions[{}] must have {} bins", i, N_OBS));
+            }
+            if row.iter().any(|&v| !v.is_finite() || v < 0.0) {
+                return Err(format!("emissions[{}] must be finite and >= 0", i));
+            }
+        }
+        check_len(hmm.initial.len(), n, "initial")?;
+        check_distribution(&hmm.initial, "initial")?;
+        check_range(hmm.emission_floor, 0.0, 1.0, "emission_floor")?;
+        // 状態ごとの配列のうち選択中のモデル・方式が参照しないものは長さを問わない
+        if hmm.transition_model == TransitionModel::ContinuousTime {
+            check_len(hmm.generator.len(), n, "generator")?;
+        }
+        for (i, row) in hmm.generator.iter().enumerate() {
+            let name = format!("generator[{}]", i);
+            check_len(row.len(), hmm.generator.len(), &name)?;
+            check_generator_row(row, i, &name)?;
+        }
+        check_positive(hmm.initial_step_secs, "initial_step_secs")?;
+        if hmm.emission_model == EmissionModel::Gaussian {
+            check_len(hmm.gaussians.len(), n, "gaussians")?;
+        }
+        for (i, g) in hmm.gaussians.iter().enumerate() {
+            check_gaussian(g, &format!("gaussians[{}]", i))?;
+        }
+
+        if self.estimator.backend == EstimatorBackend::Onnx
+            && self.estimator.onnx.model_path.is_empty()
+        {
+            return Err("estimator.onnx.model_path is required for the onnx backend".to_string());
+        }
+        if self.estimator.backend == EstimatorBackend::Ensemble {
+            let members = &self.estimator.ensemble.members;
+            if members.is_empty() {
+                return Err("estimator.ensemble.members must not be empty".to_string());
+            }
+            for (i, m) in members.iter().enumerate() {
+                let name = format!("estimator.ensemble.members[{}]", i);
+                if !m.weight.is_finite() || m.weight < 0.0 {
+                    return Err(format!("{}.weight must be finite and >= 0", name));
+                }
+                match m.backend {
+                    EstimatorBackend::Hmm => {}
+                    EstimatorBackend::Onnx if m.onnx.model_path.is_empty() => {
+                        return Err(format!("{}.onnx.model_path is required", name));
+                    }
+                    EstimatorBackend::Onnx => {}
+                    EstimatorBackend::Ensemble => {
+                        return Err(format!("{}.backend must be hmm or onnx", name));
+                    }
+                }
+            }
+            if members.iter().map(|m| m.weight).sum::<f64>() <= 0.0 {
+                return Err("estimator.ensemble member weights must not all be 0".to_string());
+            }
+        }
+
+        for (i, rule) in self.overrides.iter().enumerate() {
+            let name = format!("overrides[{}] ({})", i, rule.name);
+            if rule.obs >= N_OBS {
+                return Err(format!("{}.obs must be < {}", name, N_OBS));
+            }
+            if let Some(a) = rule.display_alpha {
+                check_unit_alpha(a, &format!("{}.display_alpha", name))?;
+            }
+            let (count, steps) = match &rule.pattern {
+                KeyPattern::Streak { min_count, .. } | KeyPattern::Burst { min_count, .. } => {
+                    (*min_count as usize, 1)
+                }
+                KeyPattern::Sequence { steps, .. } => (steps.len(), steps.len()),
+            };
+            if count == 0 || steps == 0 {
+                return Err(format!("{} must match at least one key press", name));
+            }
+            if count > KEY_HISTORY_LEN {
+                return Err(format!(
+                    "{} must not span more than {} key presses",
+                    name, KEY_HISTORY_LEN
+                ));
+            }
+        }
+
+        let p = &self.persistence;
+        check_range(p.fresh_secs, 0.0, f64::MAX, "persistence.fresh_secs")?;
+        check_positive(p.half_life_secs, "persistence.half_life_secs")?;
+        check_range(
+            p.max_gap_secs,
+            p.fresh_secs,
+            f64::MAX,
+            "persistence.max_gap_secs",
+        )?;
+
+        let p = &self.presence;
+        check_positive(p.away_after_secs, "presence.away_after_secs")?;
+        check_range(
+            p.motion_grace_secs,
+            0.0,
+            f64::MAX,
+            "presence.motion_grace_secs",
+        )?;
+        check_positive(p.resume_half_life_secs, "presence.resume_half_life_secs")?;
+
+        let c = &self.calibration;
+        if c.profile.is_empty()
+            || !c
+                .profile
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+        {
+            return Err(
+                "calibration.profile must be non-empty and use only [A-Za-z0-9_-]".to_string(),
+            );
+        }
+        if hmm.state_index(&c.state).is_none() {
+            return Err(format!(
+                "calibration.state '{}' is not one of the defined states",
+                c.state
+            ));
+        }
+        check_range(c.flow_threshold, 0.0, 1.0, "calibration.flow_threshold")?;
+        check_unit_alpha(c.learning_rate, "calibration.learning_rate")?;
+        check_range(c.max_ratio, 1.0, f64::MAX, "calibration.max_ratio")?;
+
+        check_unit_alpha(self.hysteresis.alpha, "hysteresis.alpha")?;
+        check_unit_alpha(self.hysteresis.penalty_alpha, "hysteresis.penalty_alpha")?;
+        let schmitt = &self.hysteresis.schmitt;
+        if self.hysteresis.policy == HysteresisKind::Schmitt {
+            check_len(schmitt.enter.len(), n, "hysteresis.schmitt.enter")?;
+            check_len(schmitt.exit.len(), n, "hysteresis.schmitt.exit")?;
+        }
+        for j in 0..schmitt.enter.len().min(schmitt.exit.len()) {
+            check_range(
+                schmitt.enter[j],
+                0.0,
+                1.0,
+                &format!("hysteresis.schmitt.enter[{}]", j),
+            )?;
+            check_range(
+                schmitt.exit[j],
+                0.0,
+                1.0,
+                &format!("hysteresis.schmitt.exit[{}]", j),
+            )?;
+            if schmitt.exit[j] >= schmitt.enter[j] {
+                return Err(format!(
+                    "hysteresis.schmitt.exit[{}] must be < enter[{}]",
+                    j, j
+                ));
+            }
+        }
+        if self.hysteresis.confirmations.k == 0 {
+            return Err("hysteresis.confirmations.k must be > 0".to_string());
+        }
+        check_range(
+            self.hysteresis.confirmations.threshold,
+            0.0,
+            1.0,
+            "hysteresis.confirmations.threshold",
+        )?;
+
+        if self.hsmm.enabled {
+            check_len(self.hsmm.durations.len(), n, "hsmm.durations")?;
+        }
+        for (i, d) in self.hsmm.durations.iter().enumerate() {
+            let name = format!("hsmm.durations[{}]", i);
+            match *d {
+                DurationDistribution::Gamma { shape, scale } => {
+                    check_positive(shape, &format!("{}.shape", name))?;
+                    check_positive(scale, &format!("{}.scale", name))?;
+                }
+                DurationDistribution::LogNormal { mu, sigma } => {
+                    if !mu.is_finite() {
+                        return Err(format!("{}.mu must be finite", name));
+                    }
+                    check_positive(sigma, &format!("{}.sigma", name))?;
+                }
+            }
+        }
+        if self.hsmm.max_cohorts == 0 {
+            return Err("hsmm.max_cohorts must be > 0".to_string());
+        }
+
+        let axes = &self.axes;
+        check_unit_alpha(axes.ewma_alpha, "axes.ewma_alpha")?;
+        let b = &axes.beta;
+        for (v, name) in [
+            (b.f1, "axes.beta.f1"),
+            (b.f2, "axes.beta.f2"),
+            (b.f3, "axes.beta.f3"),
+            (b.f4, "axes.beta.f4"),
+            (b.f5, "axes.beta.f5"),
+            (b.f6, "axes.beta.f6"),
+        ] {
+            check_positive(v, name)?;
+        }
+        let fw = &axes.friction;
+        let ew = &axes.engagement;
+        for (v, name) in [
+            (fw.f3, "axes.friction.f3"),
+            (fw.f6, "axes.friction.f6"),
+            (fw.f1, "axes.friction.f1"),
+            (fw.f5, "axes.friction.f5"),
+            (ew.f4, "axes.engagement.f4"),
+            (ew.f1, "axes.engagement.f1"),
+            (ew.f5, "axes.engagement.f5"),
+        ] {
+            check_range(v, 0.0, 1.0, name)?;
+        }
+        if let Some(p) = &axes.projection {
+            for (axis, name) in [(&p.friction, "friction"), (&p.engagement, "engagement")] {
+                let w = &axis.weights;
+                if ![axis.bias, w.f1, w.f2, w.f3, w.f4, w.f5, w.f6]
+                    .iter()
+                    .all(|v| v.is_finite())
+                {
+                    return Err(format!("axes.projection.{} must be finite", name));
+                }
+            }
+        }
+        let n = &axes.normalization;
+        check_positive(n.kappa, "axes.normalization.kappa")?;
+        check_unit_alpha(n.z_score.alpha, "axes.normalization.z_score.alpha")?;
+        check_positive(n.z_score.clip, "axes.normalization.z_score.clip")?;
+        check_positive(n.logistic_slope, "axes.normalization.logistic_slope")?;
+        for (kind, samples, name) in [
+            (n.f1, &n.ecdf.f1, "f1"),
+            (n.f2, &n.ecdf.f2, "f2"),
+            (n.f3, &n.ecdf.f3, "f3"),
+            (n.f4, &n.ecdf.f4, "f4"),
+            (n.f5, &n.ecdf.f5, "f5"),
+            (n.f6, &n.ecdf.f6, "f6"),
+        ] {
+            if samples.iter().any(|v| !v.is_finite()) {
+                return Err(format!("axes.normalization.ecdf.{} must be finite", name));
+            }
+            if kind == NormalizerKind::Ecdf && samples.is_empty() {
+                return Err(format!(
+                    "axes.normalization.ecdf.{} must not be empty when {} uses ecdf",
+                    name, name
+                ));
+            }
+        }
+
+        let f = &self.features;
+        if f.pause_threshold_ms == 0 {
+            return Err("features.pause_threshold_ms must be > 0".to_string());
+        }
+        if f.burst_threshold_ms == 0 {
+            return Err("features.burst_threshold_ms must be > 0".to_string());
+        }
+        if f.max_hold_ms == 0 {
+            return Err("features.max_hold_ms must be > 0".to_string());
+        }
+        if f.digraph_min_samples == 0 || f.digraph_min_samples > DIGRAPH_HISTORY {
+            return Err(format!(
+                "features.digraph_min_samples must be in [1, {}] (got {})",
+                DIGRAPH_HISTORY, f.digraph_min_samples
+            ));
+        }
+        let s = &f.silence;
+        check_positive(s.secs_per_pause, "features.silence.secs_per_pause")?;
+        check_positive(s.f6_ramp_secs, "features.silence.f6_ramp_secs")?;
+        check_positive(s.f3_ramp_secs, "features.silence.f3_ramp_secs")?;
+        for (v, name) in [
+            (s.min_silence_secs, "features.silence.min_silence_secs"),
+            (s.max_pause_count, "features.silence.max_pause_count"),
+            (s.f6_onset_secs, "features.silence.f6_onset_secs"),
+            (s.f6_max, "features.silence.f6_max"),
+            (s.f3_onset_secs, "features.silence.f3_onset_secs"),
+            (s.f3_max, "features.silence.f3_max"),
+        ] {
+            check_range(v, 0.0, f64::MAX, name)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// パラメータファイルの既定パス。
+/// 環境変数 `GSE_PARAMS` があればそれを、無ければ Documents/GSE-sessions/engine_params.json を使う。
+pub fn default_params_path() -> PathBuf {
+    match std::env::var("GSE_PARAMS") {
+        Ok(p) if !p.is_empty() => PathBuf::from(p),
+        _ => crate::logger::default_session_dir().join("engine_params.json"),
+    }
+}
+
+fn check_distribution(row: &[f64], name: &str) -> Result<(), String> {
+    if row.iter().any(|&v| !v.is_finite() || v < 0.0) {
+        return Err(format!("{} must be finite and >= 0", name));
+    }
+    let sum: f64 = row.iter().sum();
+    if (sum - 1.0).abs() > 1e-3 {
+        return Err(format!("{} must sum to 1.0 (got {:.4})", name, sum));
+    }
+    Ok(())
+}
+
+fn check_len(len: usize, expected: usize, name: &str) -> Result<(), String> {
+    if len != expected {
+        return Err(format!(
+            "{} must have {} entries (one per state), got {}",
+            name, expected, len
+        ));
+    }
+    Ok(())
+}
+
+fn check_generator_row(row: &[f64], diag: usize, name: &str) -> Result<(), String> {
+    for (j, &v) in row.iter().enumerate() {
+        if !v.is_finite() || (j != diag && v < 0.0) {
+            return Err(format!(
+                "{} off-diagonal rates must be finite and >= 0",
+                name
+            ));
+        }
+    }
+    let sum: f64 = row.iter().sum();
+    if sum.abs() > 1e-6 {
+        return Err(format!("{} must sum to 0 (got {:.6})", name, sum));
+    }
+    Ok(())
+}
+
+fn check_gaussian(g: &GaussianEmission, name: &str) -> Result<(), String> {
+    if g.mean.iter().any(|v| !v.is_finite()) {
+        return Err(format!("{}.mean must be finite", name));
+    }
+    let c = &g.covariance;
+    if c.iter().flatten().any(|v| !v.is_finite()) {
+        return Err(format!("{}.covariance must be finite", name));
+    }
+    if (c[0][1] - c[1][0]).abs() > 1e-9 {
+        return Err(format!("{}.covariance must be symmetric", name));
+    }
+    let det = c[0][0] * c[1][1] - c[0][1] * c[1][0];
+    if c[0][0] <= 0.0 || c[1][1] <= 0.0 || det <= 0.0 {
+        return Err(format!("{}.covariance must be positive definite", name));
+    }
+    Ok(())
+}
+
+fn check_range(v: f64, lo: f64, hi: f64, name: &str) -> Result<(), String> {
+    if !v.is_finite() || v < lo || v > hi {
+        return Err(format!("{} must be in [{}, {}] (got {})", name, lo, hi, v));
+    }
+    Ok(())
+}
+
+fn check_positive(v: f64, name: &str) -> Result<(), String> {
+    if !v.is_finite() || v <= 0.0 {
+        return Err(format!("{} must be > 0 (got {})", name, v));
+    }
+    Ok(())
+}
+
+fn check_unit_alpha(v: f64, name: &str) -> Result<(), String> {
+    if !v.is_finite() || v <= 0.0 || v > 1.0 {
+        return Err(format!("{} must be in (0, 1] (got {})", name, v));
+    }
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::params::PersistenceParams;
+
+/// `CognitiveStateEngine` の保存対象 (信念・表示確率・EWMA 軸)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EngineCheckpoint {
+    /// 保存時の状態名の並び (確率ベクトルのインデックス順)
+    #[serde(default = "legacy_states")]
+    pub states: Vec<String>,
+    pub current_state_probs: Vec<f64>,
+    pub display_probs: Vec<f64>,
+    /// 2軸 EWMA (X = Friction, Y = Engagement)
+    pub axes_ewma: (f64, f64),
+}
+
+impl EngineCheckpoint {
+    /// 保存値を重み `weight`、事前分布 `prior` を重み 1 − weight で混合する
+    pub fn decay_toward(&self, prior: &EngineCheckpoint, weight: f64) -> EngineCheckpoint {
+        let w = weight.clamp(0.0, 1.0);
+        let mix = |a: f64, b: f64| w * a + (1.0 - w) * b;
+        let mut out = prior.clone();
+        for (o, (&a, &b)) in out.current_state_probs.iter_mut().zip(
+            self.current_state_probs
+                .iter()
+                .zip(prior.current_state_probs.iter()),
+        ) {
+            *o = mix(a, b);
+        }
+        for (o, (&a, &b)) in out
+            .display_probs
+            .iter_mut()
+            .zip(self.display_probs.iter().zip(prior.display_probs.iter()))
+        {
+            *o = mix(a, b);
+        }
+        out.axes_ewma = (
+            mix(self.axes_ewma.0, prior.axes_ewma.0),
+            mix(self.axes_ewma.1, prior.axes_ewma.1),
+        );
+        out
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let n = self.states.len();
+        if self.current_state_probs.len() != n || self.display_probs.len() != n {
+            return Err(format!("state probabilities must have {} entries", n));
+        }
+        let unit = |v: f64| v.is_finite() && (0.0..=1.0).contains(&v);
+        let probs = self
+            .current_state_probs
+            .iter()
+            .chain(self.display_probs.iter());
+        if !probs.copied().all(unit) || !unit(self.axes_ewma.0) || !unit(self.axes_ewma.1) {
+            return Err("engine state values must be in [0, 1]".to_string());
+        }
+        for p in [&self.current_state_probs, &self.display_probs] {
+            if (p.iter().sum::<f64>() - 1.0).abs() > 1e-3 {
+                return Err("state probabilities must sum to 1.0".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+// 状態名を記録する前の状態ファイルは既定の 3 状態
+fn legacy_states() -> Vec<String> {
+    ["flow", "incubation", "stuck"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// `FeatureExtractor` の保存対象 (学習済みのフライトタイム・ホールド時間・ダイグラフの履歴 = 打鍵速度のベースライン)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExtractorCheckpoint {
+    /// 古い順
+    pub flight_times: Vec<u64>,
+    /// 古い順 (ホールド時間を保存する前の状態ファイルでは空)
+    #[serde(default)]
+    pub hold_times: Vec<u64>,
+    /// キー対ごとの押下→押下間隔 (ダイグラフを保存する前の状態ファイルでは空)
+    #[serde(default)]
+    pub digraphs: Vec<DigraphCheckpoint>,
+}
+
+/// 1 つのダイグラフ (vk_a → vk_b) の押下→押下間隔の履歴
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DigraphCheckpoint {
+    pub from: u32,
+    pub to: u32,
+    /// 古い順 [ms]
+    pub latencies: Vec<u64>,
+}
+
+/// 終了時に書き出し、次回起動時に読み込む状態ファイル (engine_state.json)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PersistedState {
+    /// 保存時刻 (Unix ms)
+    pub saved_at: u64,
+    pub engine: EngineCheckpoint,
+    pub extractor: ExtractorCheckpoint,
+}
+
+impl PersistedState {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+        let state: Self = serde_json::from_str(&text)
+            .map_err(|e| format!("failed to parse {:?}: {}", path, e))?;
+        state
+            .engine
+            .validate()
+            .map_err(|e| format!("invalid state in {:?}: {}", path, e))?;
+        Ok(state)
+    }
+
+    /// 一時ファイルに書いてから置き換える (終了処理中に中断されても壊れたファイルを残さない)
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let text = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, text).map_err(|e| format!("failed to write {:?}: {}", tmp, e))?;
+        fs::rename(&tmp, path).map_err(|e| format!("failed to replace {:?}: {}", path, e))
+    }
+
+    /// 起動時刻 `now` (Unix ms) における保存値の重み ∈ (0, 1]。
+    ///
+    /// ```text
+    /// gap ≤ fresh_secs            → 1 (そのまま復元)
+    /// fresh_secs < gap ≤ max_gap  → 0.5 ^ ((gap − fresh_secs) / half_life_secs)
+    /// gap > max_gap_secs          → None (破棄してコールドスタート)
+    /// ```
+    pub fn warm_start_weight(&self, params: &PersistenceParams, now: u64) -> Option<f64> {
+        let gap_secs = now.saturating_sub(self.saved_at) as f64 / 1000.0;
+        if gap_secs > params.max_gap_secs {
+            return None;
+        }
+        let decayed = (gap_secs - params.fresh_secs).max(0.0);
+        Some(0.5f64.powf(decayed / params.half_life_secs))
+    }
+}
+
+/// 状態ファイルの既定パス。
+/// 環境変数 `GSE_STATE` があればそれを、無ければ Documents/GSE-sessions/engine_state.json を使う。
+pub fn default_state_path() -> PathBuf {
+    match std::env::var("GSE_STATE") {
+        Ok(p) if !p.is_empty() => PathBuf::from(p),
+        _ => crate::logger::default_session_dir().join("engine_state.json"),
+    }
+}
@@ -0,0 +1,240 @@
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::engine::CognitiveStateEngine;
+use crate::analysis::params::PresenceParams;
+use crate::analysis::persistence::EngineCheckpoint;
+
+/// この時間以内に入力・動きがあれば、その時点の信念を「在席を確認した信念」として記録する [秒]
+const ACTIVE_WINDOW_SECS: f64 = 2.0;
+
+/// 在席状態
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Presence {
+    #[default]
+    Present,
+    /// 離席中。エンジンは更新を止め、信念は離席前の値のまま保つ
+    Away,
+}
+
+/// 離席と判定した理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AwayReason {
+    /// キーボード・マウスとも `away_after_secs` 以上入力が無く、端末も動いていない
+    Idle,
+    /// 画面ロック中
+    Locked,
+}
+
+/// 現在の在席状態 (`get_presence` 用)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct PresenceStatus {
+    pub presence: Presence,
+    /// 離席の理由 (在席中は None)
+    pub reason: Option<AwayReason>,
+    /// 現在の在席状態になった時刻 (Unix ms、起動から変化が無ければ 0)
+    pub since: u64,
+}
+
+/// 在席状態の切り替わり (フロントエンドへの "presence-changed" イベント・ログ用)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PresenceChange {
+    pub presence: Presence,
+    /// 離席時の理由 (復帰時は None)
+    pub reason: Option<AwayReason>,
+    /// 切り替わった時刻 (Unix ms)
+    pub at: u64,
+    /// 復帰時: 離席していた時間 [ms] (離席時は 0)
+    pub away_ms: u64,
+    /// 復帰時: 離席前の信念に残した重み ∈ (0, 1] (離席時は 1)
+    pub weight: f64,
+    /// 巻き戻し・減衰を反映した後のエンジンの EWMA。再生はこの値から再開する
+    pub axes_ewma: (f64, f64),
+}
+
+/// 在席判定の入力。分析スレッドが沈黙ティック (約 1 秒) ごとに集める
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PresenceSignals {
+    /// 最後のキーイベントからの経過 [秒]
+    pub keyboard_idle_secs: f64,
+    /// OS の最終入力 (マウスを含む) からの経過 [秒]。取得できない場合は None
+    pub system_idle_secs: Option<f64>,
+    /// 加速度センサーが最後に動きを検出してからの経過 [秒]。センサーが無い・未検出なら None
+    pub motion_idle_secs: Option<f64>,
+    /// 画面ロック中か
+    pub locked: bool,
+}
+
+impl PresenceSignals {
+    /// 直近 (`ACTIVE_WINDOW_SECS` 以内) にキーボード以外の入力か端末の動きがあったか
+    fn recently_active(&self) -> bool {
+        [self.system_idle_secs, self.motion_idle_secs]
+            .into_iter()
+            .flatten()
+            .any(|secs| secs < ACTIVE_WINDOW_SECS)
+    }
+}
+
+/// 信号から離席かどうかを判定する。在席なら None。
+///
+/// ```text
+/// locked (lock_is_away)                                  → Locked
+/// keyboard ≥ away_after かつ system ≥ away_after
+///   かつ motion ≥ motion_grace (センサー・信号が無ければ条件を満たすとみなす) → Idle
+/// ```
+pub fn away_reason(params: &PresenceParams, signals: &PresenceSignals) -> Option<AwayReason> {
+    if params.lock_is_away && signals.locked {
+        return Some(AwayReason::Locked);
+    }
+    let idle = params.away_after_secs;
+    if signals.keyboard_idle_secs < idle
+        || signals.system_idle_secs.is_some_and(|s| s < idle)
+        || signals
+            .motion_idle_secs
+            .is_some_and(|s| s < params.motion_grace_secs)
+    {
+        return None;
+    }
+    Some(AwayReason::Idle)
+}
+
+/// 離席の検出と復帰の処理。分析スレッドだけが持つ。
+///
+/// 無入力の沈黙ティックは F3・F6 の摩擦を合成するため、席を外している間も信念は
+/// Stuck へ流れる。離席と判定した時点で、最後に在席を確認した更新 (打鍵、または
+/// マウス操作・端末の動きがあった沈黙ティック) の信念まで巻き戻し、エンジンを止める。
+///
+/// 復帰 (打鍵・マウス操作・ロック解除) 時は、巻き戻した信念を離席時間に応じて
+/// 事前分布へ減衰させてから再開する。
+///
+/// ```text
+/// weight = 0.5 ^ (away_secs / resume_half_life_secs)
+/// ```
+///
+/// 沈黙時間は復帰時刻から数え直す (呼び出し側が最後の入力時刻を更新する)。
+#[derive(Debug, Default)]
+pub struct PresenceTracker {
+    // (reason, since) while away
+    away: Option<(AwayReason, u64)>,
+    // Belief at the last update that saw the user (restored when going away)
+    anchor: Option<EngineCheckpoint>,
+}
+
+impl PresenceTracker {
+    pub fn is_away(&self) -> bool {
+        self.away.is_some()
+    }
+
+    /// 打鍵による更新の後に呼ぶ。その時点の信念を在席中の信念として記録する
+    pub fn record(&mut self, engine: &CognitiveStateEngine) {
+        self.anchor = Some(engine.checkpoint());
+    }
+
+    /// 沈黙ティックごとに呼ぶ。在席状態が変わった場合はエンジンに反映して返す。
+    ///
+    /// 在席中の沈黙ティックでは、直近にマウス操作・端末の動きがあれば `record()` と
+    /// 同じく信念を記録する (その沈黙は在席中の沈黙として扱う)。
+    pub fn poll(
+        &mut self,
+        engine: &CognitiveStateEngine,
+        params: &PresenceParams,
+        signals: &PresenceSignals,
+        now: u64,
+    ) -> Option<PresenceChange> {
+        let reason = if params.enabled {
+            away_reason(params, signals)
+        } else {
+            None
+        };
+        match (self.away, reason) {
+            (None, None) => {
+                if signals.recently_active() {
+                    self.record(engine);
+                }
+                None
+            }
+            (None, Some(reason)) => Some(self.go_away(engine, reason, now)),
+            (Some((current, since)), Some(reason)) if current != reason => {
+                // Idle ↔ Locked: 離席のまま理由だけ更新する (信念は巻き戻し済み)
+                self.away = Some((reason, since));
+                engine.set_presence(PresenceStatus {
+                    presence: Presence::Away,
+                    reason: Some(reason),
+                    since,
+                });
+                None
+            }
+            (Some(_), Some(_)) => None,
+            (Some(_), None) => Some(self.resume(engine, params, now)),
+        }
+    }
+
+    /// 打鍵時に呼ぶ。離席中なら復帰させる
+    pub fn on_key(
+        &mut self,
+        engine: &CognitiveStateEngine,
+        params: &PresenceParams,
+        now: u64,
+    ) -> Option<PresenceChange> {
+        self.away?;
+        Some(self.resume(engine, params, now))
+    }
+
+    fn go_away(
+        &mut self,
+        engine: &CognitiveStateEngine,
+        reason: AwayReason,
+        now: u64,
+    ) -> PresenceChange {
+        if let Some(anchor) = &self.anchor {
+            if let Err(e) = engine.restore(anchor) {
+                tracing::warn!("Presence: belief not rolled back: {}", e);
+            }
+        }
+        self.away = Some((reason, now));
+        engine.set_presence(PresenceStatus {
+            presence: Presence::Away,
+            reason: Some(reason),
+            since: now,
+        });
+        PresenceChange {
+            presence: Presence::Away,
+            reason: Some(reason),
+            at: now,
+            away_ms: 0,
+            weight: 1.0,
+            axes_ewma: engine.snapshot().axes_ewma,
+        }
+    }
+
+    fn resume(
+        &mut self,
+        engine: &CognitiveStateEngine,
+        params: &PresenceParams,
+        now: u64,
+    ) -> PresenceChange {
+        let since = self.away.take().map_or(now, |(_, since)| since);
+        let away_ms = now.saturating_sub(since);
+        let weight = 0.5f64.powf(away_ms as f64 / 1000.0 / params.resume_half_life_secs);
+        let checkpoint = engine.checkpoint();
+        let resumed = checkpoint.decay_toward(&engine.prior_checkpoint(), weight);
+        if let Err(e) = engine.restore(&resumed) {
+            tracing::warn!("Presence: belief not decayed on resume: {}", e);
+        }
+        self.anchor = Some(resumed);
+        engine.set_presence(PresenceStatus {
+            presence: Presence::Present,
+            reason: None,
+            since: now,
+        });
+        PresenceChange {
+            presence: Presence::Present,
+            reason: None,
+            at: now,
+            away_ms,
+            weight,
+            axes_ewma: engine.snapshot().axes_ewma,
+        }
+    }
+}
@@ -0,0 +1,552 @@
+use std::fs;
+use std::path::Path;
+
+use crate::analysis::engine::{CognitiveState, PhiFeatures};
+use crate::analysis::normalize::FeatureNormalizers;
+use crate::analysis::params::{
+    AxisParams, FeatureWeights, HmmParams, LearnedProjection, LinearAxis, ProjectionMethod,
+};
+use crate::analysis::replay::ReplayedSession;
+
+/// 射影の入力次元 (正規化した F1–F6)
+const N_FEATURES: usize = 6;
+
+type Vector = [f64; N_FEATURES];
+type Matrix = [[f64; N_FEATURES]; N_FEATURES];
+
+// 因子分析の独自分散 Ψ の下限 (Heywood ケースの回避)
+const MIN_UNIQUENESS: f64 = 0.005;
+const FACTOR_ITERS: usize = 200;
+
+/// 学習用の 1 標本: ある更新時点の正規化した F1–F6
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProjectionSample {
+    pub timestamp: u64,
+    pub phi: Vector,
+}
+
+fn phi_vector(phi: &PhiFeatures) -> Vector {
+    [phi.f1, phi.f2, phi.f3, phi.f4, phi.f5, phi.f6]
+}
+
+fn to_weights(w: &Vector) -> FeatureWeights {
+    FeatureWeights {
+        f1: w[0],
+        f2: w[1],
+        f3: w[2],
+        f4: w[3],
+        f5: w[4],
+        f6: w[5],
+    }
+}
+
+fn from_weights(w: &FeatureWeights) -> Vector {
+    [w.f1, w.f2, w.f3, w.f4, w.f5, w.f6]
+}
+
+/// 再生したセッションから学習用標本を集める。
+///
+/// `update()` と同じく HMM を進めた更新 (F1 が得られた更新) だけを用い、正規化は
+/// `axes.normalization` で行う (z-score の移動統計も打鍵ごとに進める)。
+pub fn collect_samples(session: &ReplayedSession, axes: &AxisParams) -> Vec<ProjectionSample> {
+    let mut normalizers = FeatureNormalizers::from_params(&axes.normalization);
+    let mut samples = Vec::new();
+    for tick in &session.ticks {
+        if tick.observation.is_none() {
+            continue;
+        }
+        let phi = normalizers.normalize(&axes.beta, &tick.features);
+        samples.push(ProjectionSample {
+            timestamp: tick.timestamp,
+            phi: phi_vector(&phi),
+        });
+        if tick.vk_code.is_some() {
+            normalizers.observe(&axes.beta, &tick.features);
+        }
+    }
+    samples
+}
+
+/// `behavioral_gt.py` が出力した GT ラベルの 1 区間 (UNKNOWN は読み飛ばす)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GtSegment {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub state: CognitiveState,
+}
+
+/// `<session>_labeled.csv` (start_ms, end_ms, label, evidence) を読み込む。
+/// ラベルは `hmm.states[].label` と照合し、どの状態にも当たらない区間は読み飛ばす。
+pub fn load_gt_segments(path: &Path, hmm: &HmmParams) -> Result<Vec<GtSegment>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+    let mut segments = Vec::new();
+    for (i, line) in text.lines().enumerate().skip(1) {
+        let mut cols = line.splitn(4, ',');
+        let (Some(start), Some(end), Some(label)) = (cols.next(), cols.next(), cols.next()) else {
+            continue;
+        };
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<u64>()
+                .map_err(|e| format!("{:?} line {}: {}", path, i + 1, e))
+        };
+        if let Some(index) = hmm.state_by_label(label.trim()) {
+            segments.push(GtSegment {
+                start_ms: parse(start)?,
+                end_ms: parse(end)?,
+                state: CognitiveState(index),
+            });
+        }
+    }
+    Ok(segments)
+}
+
+/// `timestamp` を含む GT 区間の状態
+pub fn gt_state_at(segments: &[GtSegment], timestamp: u64) -> Option<CognitiveState> {
+    segments
+        .iter()
+        .find(|s| s.start_ms <= timestamp && timestamp < s.end_ms)
+        .map(|s| s.state)
+}
+
+/// 学習結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectionFit {
+    pub projection: LearnedProjection,
+    /// 各軸 (X, Y) の適合度: pca は寄与率、factor は共通性の割合、regression は R²
+    pub score: (f64, f64),
+    pub samples: usize,
+}
+
+// 各特徴量の平均・標準偏差
+struct Moments {
+    mean: Vector,
+    sd: Vector,
+}
+
+impl Moments {
+    fn of(samples: &[Vector]) -> Self {
+        let n = samples.len() as f64;
+        let mut mean = [0.0; N_FEATURES];
+        let mut sd = [0.0; N_FEATURES];
+        for x in samples {
+            for i in 0..N_FEATURES {
+                mean[i] += x[i] / n;
+            }
+        }
+        for x in samples {
+            for i in 0..N_FEATURES {
+                sd[i] += (x[i] - mean[i]).powi(2) / n;
+            }
+        }
+        for v in &mut sd {
+            *v = v.sqrt();
+        }
+        Self { mean, sd }
+    }
+
+    fn is_active(&self, i: usize) -> bool {
+        self.sd[i] > 1e-9
+    }
+
+    /// 相関行列。分散 0 の特徴量は対角 1・非対角 0 とする (寄与しない独立成分)
+    fn correlation(&self, samples: &[Vector]) -> Matrix {
+        let n = samples.len() as f64;
+        let mut r = [[0.0; N_FEATURES]; N_FEATURES];
+        for x in samples {
+            for i in 0..N_FEATURES {
+                for j in 0..N_FEATURES {
+                    if self.is_active(i) && self.is_active(j) {
+                        let zi = (x[i] - self.mean[i]) / self.sd[i];
+                        let zj = (x[j] - self.mean[j]) / self.sd[j];
+                        r[i][j] += zi * zj / n;
+                    }
+                }
+            }
+        }
+        for (i, row) in r.iter_mut().enumerate() {
+            if !self.is_active(i) {
+                row[i] = 1.0;
+            }
+        }
+        r
+    }
+
+    /// 標準化空間の方向 d を φ 空間の (bias, w) に戻す: s = d · (φ − μ) / σ
+    fn unstandardize(&self, d: &Vector) -> (f64, Vector) {
+        let mut w = [0.0; N_FEATURES];
+        let mut bias = 0.0;
+        for i in 0..N_FEATURES {
+            if self.is_active(i) {
+                w[i] = d[i] / self.sd[i];
+                bias -= w[i] * self.mean[i];
+            }
+        }
+        (bias, w)
+    }
+}
+
+fn dot(a: &Vector, b: &Vector) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn mean_sd(xs: &[f64]) -> (f64, f64) {
+    let n = xs.len() as f64;
+    let mean = xs.iter().sum::<f64>() / n;
+    let var = xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+    (mean, var.sqrt())
+}
+
+fn correlation(a: &[f64], b: &[f64]) -> f64 {
+    let (ma, sa) = mean_sd(a);
+    let (mb, sb) = mean_sd(b);
+    if sa <= 1e-12 || sb <= 1e-12 {
+        return 0.0;
+    }
+    let n = a.len() as f64;
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - ma) * (y - mb))
+        .sum::<f64>()
+        / (n * sa * sb)
+}
+
+/// 対称行列の固有値分解 (Jacobi 法)。固有値の降順に (λ, 固有ベクトル) を返す
+fn symmetric_eigen(m: &Matrix) -> Vec<(f64, Vector)> {
+    let mut a = *m;
+    let mut v = [[0.0; N_FEATURES]; N_FEATURES];
+    for (i, row) in v.iter_mut().enumerate() {
+        row[i] = 1.0;
+    }
+    for _ in 0..100 {
+        let off: f64 = (0..N_FEATURES)
+            .flat_map(|i| {
+                (0..N_FEATURES)
+                    .filter(move |&j| j != i)
+                    .map(move |j| (i, j))
+            })
+            .map(|(i, j)| a[i][j] * a[i][j])
+            .sum();
+        if off < 1e-20 {
+            break;
+        }
+        for p in 0..N_FEATURES {
+            for q in p + 1..N_FEATURES {
+                if a[p][q].abs() < 1e-300 {
+                    continue;
+                }
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                // A ← Jᵀ A J,  V ← V J
+                for row in a.iter_mut() {
+                    let (akp, akq) = (row[p], row[q]);
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (rp, rq) = (a[p], a[q]);
+                a[p] = std::array::from_fn(|k| c * rp[k] - s * rq[k]);
+                a[q] = std::array::from_fn(|k| s * rp[k] + c * rq[k]);
+                for row in v.iter_mut() {
+                    let (vkp, vkq) = (row[p], row[q]);
+                    row[p] = c * vkp - s * vkq;
+                    row[q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+    let mut pairs: Vec<(f64, Vector)> = (0..N_FEATURES)
+        .map(|j| {
+            let mut col = [0.0; N_FEATURES];
+            for (i, c) in col.iter_mut().enumerate() {
+                *c = v[i][j];
+            }
+            (a[j][j], col)
+        })
+        .collect();
+    pairs.sort_by(|x, y| y.0.total_cmp(&x.0));
+    pairs
+}
+
+/// 部分ピボット付きガウス消去で a · x = b を解く (特異なら None)
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col].clone();
+        for row in col + 1..n {
+            let f = a[row][col] / pivot_row[col];
+            for (v, p) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *v -= f * p;
+            }
+            b[row] -= f * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let s: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - s) / a[row][row];
+    }
+    Some(x)
+}
+
+fn invert(m: &Matrix) -> Option<Matrix> {
+    let rows: Vec<Vec<f64>> = m.iter().map(|r| r.to_vec()).collect();
+    let mut inv = [[0.0; N_FEATURES]; N_FEATURES];
+    for j in 0..N_FEATURES {
+        let mut e = vec![0.0; N_FEATURES];
+        e[j] = 1.0;
+        let col = solve(rows.clone(), e)?;
+        for i in 0..N_FEATURES {
+            inv[i][j] = col[i];
+        }
+    }
+    Some(inv)
+}
+
+/// 2 因子の因子分析 (相関行列 R に対する EM)。
+/// 因子得分の係数 (Thomson 法: B = Λᵀ Σ⁻¹) と各因子の共通性の割合を返す。
+fn factor_analysis(r: &Matrix, init: [(f64, Vector); 2]) -> Option<([Vector; 2], [f64; 2])> {
+    // Λ (6×2) を主成分で初期化
+    let mut lambda = [[0.0; 2]; N_FEATURES];
+    for (k, (ev, vec)) in init.iter().enumerate() {
+        for i in 0..N_FEATURES {
+            lambda[i][k] = vec[i] * ev.max(1e-6).sqrt();
+        }
+    }
+    let mut psi = [0.0; N_FEATURES];
+    for i in 0..N_FEATURES {
+        psi[i] = (r[i][i] - lambda[i][0].powi(2) - lambda[i][1].powi(2)).max(MIN_UNIQUENESS);
+    }
+
+    let scores = |lambda: &[[f64; 2]; N_FEATURES], psi: &Vector| -> Option<[Vector; 2]> {
+        let mut sigma = [[0.0; N_FEATURES]; N_FEATURES];
+        for i in 0..N_FEATURES {
+            for j in 0..N_FEATURES {
+                sigma[i][j] = lambda[i][0] * lambda[j][0] + lambda[i][1] * lambda[j][1];
+            }
+            sigma[i][i] += psi[i];
+        }
+        let inv = invert(&sigma)?;
+        let mut b = [[0.0; N_FEATURES]; 2];
+        for (k, row) in b.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (0..N_FEATURES).map(|i| lambda[i][k] * inv[i][j]).sum();
+            }
+        }
+        Some(b)
+    };
+
+    for _ in 0..FACTOR_ITERS {
+        // E ステップ: B = Λᵀ Σ⁻¹,  E[zzᵀ] = I − BΛ + B R Bᵀ
+        let b = scores(&lambda, &psi)?;
+        let mut rb = [[0.0; 2]; N_FEATURES]; // R Bᵀ
+        for i in 0..N_FEATURES {
+            for k in 0..2 {
+                rb[i][k] = (0..N_FEATURES).map(|j| r[i][j] * b[k][j]).sum();
+            }
+        }
+        let mut ezz = [[0.0; 2]; 2];
+        for k in 0..2 {
+            for l in 0..2 {
+                let bl: f64 = (0..N_FEATURES).map(|i| b[k][i] * lambda[i][l]).sum();
+                let brb: f64 = (0..N_FEATURES).map(|i| b[k][i] * rb[i][l]).sum();
+                ezz[k][l] = if k == l { 1.0 } else { 0.0 } - bl + brb;
+            }
+        }
+        // M ステップ: Λ = R Bᵀ E[zzᵀ]⁻¹,  Ψ = diag(R − Λ B R)
+        let det = ezz[0][0] * ezz[1][1] - ezz[0][1] * ezz[1][0];
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv = [
+            [ezz[1][1] / det, -ezz[0][1] / det],
+            [-ezz[1][0] / det, ezz[0][0] / det],
+        ];
+        for i in 0..N_FEATURES {
+            for k in 0..2 {
+                lambda[i][k] = rb[i][0] * inv[0][k] + rb[i][1] * inv[1][k];
+            }
+        }
+        for i in 0..N_FEATURES {
+            let explained = lambda[i][0] * rb[i][0] + lambda[i][1] * rb[i][1];
+            psi[i] = (r[i][i] - explained).max(MIN_UNIQUENESS);
+        }
+    }
+
+    let b = scores(&lambda, &psi)?;
+    let mut share = [0.0; 2];
+    for (k, s) in share.iter_mut().enumerate() {
+        *s = (0..N_FEATURES).map(|i| lambda[i][k].powi(2)).sum::<f64>() / N_FEATURES as f64;
+    }
+    Some((b, share))
+}
+
+/// 教師なし (PCA・因子分析) で 2 方向を求め、`reference` の射影が作る X・Y に合わせる。
+///
+/// 2 つの成分は reference の X・Y との相関が大きくなる組み合わせで割り当て、符号を揃える。
+/// 尺度は各軸の平均・標準偏差を reference の軸に一致させるため、学習済みの放射モデルの
+/// ビン配置をおおむね保つ。
+pub fn fit_unsupervised(
+    samples: &[ProjectionSample],
+    reference: &LearnedProjection,
+    method: ProjectionMethod,
+) -> Result<ProjectionFit, String> {
+    if samples.len() < 10 {
+        return Err(format!("need at least 10 samples (got {})", samples.len()));
+    }
+    let xs: Vec<Vector> = samples.iter().map(|s| s.phi).collect();
+    let moments = Moments::of(&xs);
+    let r = moments.correlation(&xs);
+    let eigen = symmetric_eigen(&r);
+    let total: f64 = eigen.iter().map(|(ev, _)| ev.max(0.0)).sum();
+
+    let (directions, share) = match method {
+        ProjectionMethod::Pca => (
+            [eigen[0].1, eigen[1].1],
+            [eigen[0].0 / total, eigen[1].0 / total],
+        ),
+        ProjectionMethod::Factor => factor_analysis(&r, [eigen[0], eigen[1]])
+            .ok_or("factor analysis did not converge (singular covariance)")?,
+        other => return Err(format!("{:?} is not an unsupervised method", other)),
+    };
+
+    // 成分得分と reference の X・Y
+    let components: Vec<(f64, Vector)> = directions
+        .iter()
+        .map(|d| moments.unstandardize(d))
+        .collect();
+    let score = |c: &(f64, Vector), x: &Vector| c.0 + dot(&c.1, x);
+    let reference_axis = |axis: &LinearAxis, x: &Vector| {
+        (axis.bias + dot(&from_weights(&axis.weights), x)).clamp(0.0, 1.0)
+    };
+    let s: Vec<Vec<f64>> = components
+        .iter()
+        .map(|c| xs.iter().map(|x| score(c, x)).collect())
+        .collect();
+    let ref_x: Vec<f64> = xs
+        .iter()
+        .map(|x| reference_axis(&reference.friction, x))
+        .collect();
+    let ref_y: Vec<f64> = xs
+        .iter()
+        .map(|x| reference_axis(&reference.engagement, x))
+        .collect();
+
+    let c = [
+        [correlation(&s[0], &ref_x), correlation(&s[0], &ref_y)],
+        [correlation(&s[1], &ref_x), correlation(&s[1], &ref_y)],
+    ];
+    let swap = c[1][0].abs() + c[0][1].abs() > c[0][0].abs() + c[1][1].abs();
+    let (ix, iy) = if swap { (1, 0) } else { (0, 1) };
+
+    let rescale = |k: usize, corr: f64, target: &[f64]| -> LinearAxis {
+        let (ms, ss) = mean_sd(&s[k]);
+        let (mt, st) = mean_sd(target);
+        let sign = if corr < 0.0 { -1.0 } else { 1.0 };
+        let gain = if ss > 1e-12 { sign * st / ss } else { 0.0 };
+        let (bias, w) = &components[k];
+        let mut weights = [0.0; N_FEATURES];
+        for i in 0..N_FEATURES {
+            weights[i] = gain * w[i];
+        }
+        LinearAxis {
+            bias: mt + gain * (bias - ms),
+            weights: to_weights(&weights),
+        }
+    };
+
+    Ok(ProjectionFit {
+        projection: LearnedProjection {
+            method,
+            friction: rescale(ix, c[ix][0], &ref_x),
+            engagement: rescale(iy, c[iy][1], &ref_y),
+        },
+        score: (share[ix], share[iy]),
+        samples: samples.len(),
+    })
+}
+
+/// 教師あり: 各標本の目標 (X, Y) へのリッジ回帰 (bias は正則化しない)
+pub fn fit_regression(
+    samples: &[ProjectionSample],
+    targets: &[(f64, f64)],
+    ridge: f64,
+) -> Result<ProjectionFit, String> {
+    if samples.len() != targets.len() {
+        return Err("samples and targets must have the same length".to_string());
+    }
+    if samples.len() < 10 {
+        return Err(format!(
+            "need at least 10 labeled samples (got {})",
+            samples.len()
+        ));
+    }
+    const N: usize = N_FEATURES + 1;
+    let design = |x: &Vector| {
+        let mut row = [1.0; N];
+        row[1..].copy_from_slice(x);
+        row
+    };
+    let mut xtx = vec![vec![0.0; N]; N];
+    let mut xty = [vec![0.0; N], vec![0.0; N]];
+    for (s, t) in samples.iter().zip(targets) {
+        let row = design(&s.phi);
+        for i in 0..N {
+            for j in 0..N {
+                xtx[i][j] += row[i] * row[j];
+            }
+            xty[0][i] += row[i] * t.0;
+            xty[1][i] += row[i] * t.1;
+        }
+    }
+    for (i, row) in xtx.iter_mut().enumerate().skip(1) {
+        row[i] += ridge;
+    }
+
+    let fit_axis = |k: usize| -> Result<(LinearAxis, f64), String> {
+        let coef = solve(xtx.clone(), xty[k].clone())
+            .ok_or("regression is singular (increase --ridge)")?;
+        let mut w = [0.0; N_FEATURES];
+        w.copy_from_slice(&coef[1..]);
+        let axis = LinearAxis {
+            bias: coef[0],
+            weights: to_weights(&w),
+        };
+        let ys: Vec<f64> = targets
+            .iter()
+            .map(|t| if k == 0 { t.0 } else { t.1 })
+            .collect();
+        let (my, _) = mean_sd(&ys);
+        let (mut ss_res, mut ss_tot) = (0.0, 0.0);
+        for (s, y) in samples.iter().zip(&ys) {
+            let pred = (coef[0] + dot(&w, &s.phi)).clamp(0.0, 1.0);
+            ss_res += (y - pred).powi(2);
+            ss_tot += (y - my).powi(2);
+        }
+        let r2 = if ss_tot > 0.0 {
+            1.0 - ss_res / ss_tot
+        } else {
+            0.0
+        };
+        Ok((axis, r2))
+    };
+    let (friction, r2_x) = fit_axis(0)?;
+    let (engagement, r2_y) = fit_axis(1)?;
+
+    Ok(ProjectionFit {
+        projection: LearnedProjection {
+            method: ProjectionMethod::Regression,
+            friction,
+            engagement,
+        },
+        score: (r2_x, r2_y),
+        samples: samples.len(),
+    })
+}
@@ -0,0 +1,353 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::analysis::engine::{Observation, ObservationEncoder};
+use crate::analysis::features::{FeatureExtractor, Features, InputEvent};
+use crate::analysis::params::{AxisParams, BaselineParams, BaselineSource, EngineParams, StateDef};
+use crate::analysis::persistence::PersistedState;
+use crate::analysis::presence::Presence;
+
+/// セッションログ (NDJSON) の 1 行。logger.rs が書き出す形式に対応する。
+/// 未知の `type` は `Other` として読み飛ばす。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum LogRecord {
+    Key {
+        t: u64,
+        vk: u32,
+        press: bool,
+    },
+    Feat {
+        t: u64,
+        #[serde(default)]
+        f1: f64,
+        #[serde(default)]
+        f2: f64,
+        #[serde(default)]
+        f3: f64,
+        #[serde(default)]
+        f4: f64,
+        #[serde(default)]
+        f5: f64,
+        #[serde(default)]
+        f6: f64,
+        /// ホールド時間を記録する前のログでは 0
+        #[serde(default)]
+        f7: f64,
+        #[serde(default)]
+        f8: f64,
+        #[serde(default = "neutral_slowdown")]
+        f9: f64,
+        /// 状態名 → display_probs
+        #[serde(default)]
+        probs: HashMap<String, f64>,
+        /// 状態名を記録する前のログ (既定の 3 状態) の display_probs
+        #[serde(default)]
+        p_flow: f64,
+        #[serde(default)]
+        p_inc: f64,
+        #[serde(default)]
+        p_stuck: f64,
+        /// 個人ベースラインで観測を作った更新の β (母集団 β の場合・記録前のログでは None)
+        #[serde(default)]
+        beta: Option<BaselineParams>,
+    },
+    Meta {
+        session_start: Option<u64>,
+        session_end: Option<u64>,
+    },
+    /// 離席・復帰。復帰時にライブの分析スレッドは沈黙時間を数え直す
+    Presence {
+        t: u64,
+        state: Presence,
+        /// 巻き戻し・減衰後のエンジンの EWMA (記録前のログでは None)
+        #[serde(default)]
+        axes_ewma: Option<(f64, f64)>,
+    },
+    /// IME 変換による一時停止の開始・終了。ライブでは強制リセットで EWMA が書き換わる
+    Ime {
+        t: u64,
+        active: bool,
+        /// 記録時点のエンジンの EWMA
+        #[serde(default)]
+        axes_ewma: Option<(f64, f64)>,
+    },
+    /// ウォームスタートで復元した FeatureExtractor の履歴とエンジンの状態
+    #[serde(rename = "warm_start")]
+    WarmStart {
+        t: u64,
+        state: Box<PersistedState>,
+    },
+    /// 起動時・ホットリロード時に適用されたエンジンパラメータ
+    Params {
+        t: u64,
+        #[serde(default)]
+        source: String,
+        params: Box<EngineParams>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// 再生された 1 更新 (ライブの分析スレッドが `update()` を呼んだ 1 回に相当)
+#[derive(Debug, Clone)]
+pub struct ReplayTick {
+    pub timestamp: u64,
+    /// キー押下起因なら Some(vk)、サイレンスタイマー起因なら None
+    pub vk_code: Option<u32>,
+    pub features: Features,
+    /// `update()` が実際に HMM を進めた場合の観測 (F1 データ不足時は None)
+    pub observation: Option<Observation>,
+    /// ログに記録されたライブの display_probs (再生に用いる `states` の並び。記録の無い状態は 0)
+    pub live_probs: Vec<f64>,
+}
+
+/// 1 セッション分の再生結果
+#[derive(Debug, Clone)]
+pub struct ReplayedSession {
+    pub path: PathBuf,
+    pub ticks: Vec<ReplayTick>,
+}
+
+impl ReplayedSession {
+    /// HMM に入力された観測ビンの系列 (スキップされた更新は除く)
+    pub fn observation_sequence(&self) -> Vec<usize> {
+        self.ticks
+            .iter()
+            .filter_map(|t| t.observation.map(|o| o.index))
+            .collect()
+    }
+
+    /// HMM に入力された観測の系列 (連続軸を含む。ガウス放射モデル用)
+    pub fn observations(&self) -> Vec<Observation> {
+        self.ticks.iter().filter_map(|t| t.observation).collect()
+    }
+}
+
+// ダイグラフ減速率を記録する前のログは「普段どおり」
+fn neutral_slowdown() -> f64 {
+    1.0
+}
+
+/// NDJSON ファイルを読み込む。解析できない行は警告を出して読み飛ばす。
+pub fn load_records(path: &Path) -> Result<Vec<LogRecord>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+
+    let mut records = Vec::new();
+    for (line_no, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<LogRecord>(line) {
+            Ok(r) => records.push(r),
+            Err(e) => tracing::warn!("{:?} line {}: parse error: {}", path, line_no + 1, e),
+        }
+    }
+    Ok(records)
+}
+
+/// ログを分析スレッドと同じ手順で再生し、各 `feat` 行に対応する更新を再構築する。
+///
+/// - `key` 行は FeatureExtractor とオーバーライド規則用のキー履歴にそのまま投入する。
+/// - 直前のキー押下と同じタイムスタンプを持つ `feat` 行はキー起因の更新として
+///   `calculate_features()` を再計算する。
+/// - それ以外の `feat` 行はサイレンスタイマー起因とみなし、最後のキーイベント
+///   (離席からの復帰後はその復帰時刻) からの経過時間で `make_silence_observation()` を再計算する。
+///   離席中は `feat` 行が書かれないため、そのまま読み飛ばされる。
+/// - `warm_start` 行では、ライブと同じくフライトタイム等の履歴と EWMA を復元値から始める。
+/// - `presence` 行では、離席時の巻き戻し・復帰時の減衰を反映したライブの EWMA を読み込む。
+///
+/// 観測ビンは `ObservationEncoder` で `update()` と同一の変換を行う。
+/// IME 変換による一時停止中は `key`・`feat` 行が書かれない。ライブの `force_flow_state()` は
+/// EWMA を書き換えるため、`ime` 行 (`active: false`) に記録された再開時の EWMA を読み込む。
+/// `calibration.baseline` が `"personal"` の場合、φ には `feat` 行に記録された β を使う。
+/// β が記録されていない行 (キャリブレーション段階・記録前のログ) は母集団 β になる。
+///
+/// `params` が `None` の場合はログに記録された `params` 行に従ってパラメータを切り替え、
+/// ライブセッションを再現する (最初の `params` 行より前は既定値)。
+/// `Some` の場合は記録を無視し、指定したパラメータで全体を再生する。
+pub fn replay_records(records: &[LogRecord], params: Option<&EngineParams>) -> Vec<ReplayTick> {
+    let follow_log = params.is_none();
+    let mut params = params.cloned().unwrap_or_default();
+    let mut extractor = FeatureExtractor::with_params(600, params.features.clone());
+    let mut encoder = ObservationEncoder::default();
+    encoder.set_normalization(&params.axes.normalization);
+    let mut last_event_ts: Option<u64> = None;
+    let mut pending_press: Option<(u64, u32)> = None;
+    let mut warned_missing_beta = false;
+    let mut ticks = Vec::new();
+
+    for record in records {
+        match *record {
+            LogRecord::Key { t, vk, press } => {
+                extractor.process_event(InputEvent {
+                    vk_code: vk,
+                    timestamp: t,
+                    is_press: press,
+                });
+                encoder.observe_key(vk, press, t);
+                last_event_ts = Some(t);
+                if press {
+                    pending_press = Some((t, vk));
+                }
+            }
+            LogRecord::Feat {
+                t,
+                ref probs,
+                p_flow,
+                p_inc,
+                p_stuck,
+                ref beta,
+                ..
+            } => {
+                let (features, vk_code) = match pending_press.take() {
+                    Some((press_t, vk)) if press_t == t => {
+                        (extractor.calculate_features(), Some(vk))
+                    }
+                    _ => {
+                        let Some(last) = last_event_ts else { continue };
+                        let silence_secs = t.saturating_sub(last) as f64 / 1000.0;
+                        match extractor.make_silence_observation(silence_secs) {
+                            Some(sf) => (sf, None),
+                            None => continue,
+                        }
+                    }
+                };
+
+                // update() と同じく F1 がゼロの更新はスキップされる
+                let observation = if features.f1_flight_time_median > 0.0 {
+                    let axes = match (params.calibration.baseline, beta) {
+                        (BaselineSource::Personal, Some(beta)) => Cow::Owned(AxisParams {
+                            beta: beta.clone(),
+                            ..params.axes.clone()
+                        }),
+                        (BaselineSource::Personal, None) => {
+                            if !warned_missing_beta {
+                                tracing::warn!(
+                                    "feat at {} has no personal baseline recorded; replaying with the population beta",
+                                    t
+                                );
+                                warned_missing_beta = true;
+                            }
+                            Cow::Borrowed(&params.axes)
+                        }
+                        (BaselineSource::Population, _) => Cow::Borrowed(&params.axes),
+                    };
+                    let obs = encoder.encode(&axes, &params.overrides, &features, t);
+                    if vk_code.is_some() {
+                        encoder.observe_features(&axes.beta, &features);
+                    }
+                    Some(obs)
+                } else {
+                    None
+                };
+
+                ticks.push(ReplayTick {
+                    timestamp: t,
+                    vk_code,
+                    features,
+                    observation,
+                    live_probs: logged_probs(&params.hmm.states, probs, [p_flow, p_inc, p_stuck]),
+                });
+            }
+            LogRecord::Presence {
+                t,
+                state,
+                axes_ewma,
+            } => {
+                // 離席・復帰ではライブの信念と EWMA が巻き戻し・減衰で書き換わる
+                if let Some(axes) = axes_ewma {
+                    encoder.axes_ewma = axes;
+                }
+                if state == Presence::Present {
+                    last_event_ts = Some(t);
+                }
+            }
+            LogRecord::Ime {
+                active: false,
+                axes_ewma: Some(axes),
+                ..
+            } => {
+                encoder.axes_ewma = axes;
+            }
+            LogRecord::WarmStart { ref state, .. } => {
+                extractor.restore(&state.extractor);
+                encoder.axes_ewma = state.engine.axes_ewma;
+            }
+            LogRecord::Params {
+                params: ref logged, ..
+            } => {
+                if follow_log {
+                    if logged.axes.normalization != params.axes.normalization {
+                        encoder.set_normalization(&logged.axes.normalization);
+                    }
+                    params = (**logged).clone();
+                    extractor.set_params(params.features.clone());
+                }
+            }
+            LogRecord::Ime { .. } | LogRecord::Meta { .. } | LogRecord::Other => {}
+        }
+    }
+
+    ticks
+}
+
+/// `feat` 行の確率を `states` の並びに揃える。`probs` の無い古いログは
+/// p_flow / p_inc / p_stuck を同名の状態に割り当てる。
+fn logged_probs(states: &[StateDef], probs: &HashMap<String, f64>, legacy: [f64; 3]) -> Vec<f64> {
+    states
+        .iter()
+        .map(|s| match probs.get(&s.name) {
+            Some(&p) => p,
+            None if probs.is_empty() => match s.name.as_str() {
+                "flow" => legacy[0],
+                "incubation" => legacy[1],
+                "stuck" => legacy[2],
+                _ => 0.0,
+            },
+            None => 0.0,
+        })
+        .collect()
+}
+
+/// セッションファイルを読み込んで再生する。`params` の意味は `replay_records()` と同じ。
+pub fn replay_file(path: &Path, params: Option<&EngineParams>) -> Result<ReplayedSession, String> {
+    let records = load_records(path)?;
+    Ok(ReplayedSession {
+        path: path.to_path_buf(),
+        ticks: replay_records(&records, params),
+    })
+}
+
+/// 引数のパス群を `gse_*.ndjson` ファイルの一覧に展開する。
+/// ディレクトリは直下の `gse_*.ndjson` を名前順に列挙する。
+pub fn collect_session_files(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = match fs::read_dir(path) {
+                Ok(rd) => rd
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| {
+                        let name = p.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                        name.starts_with("gse_") && name.ends_with(".ndjson")
+                    })
+                    .collect(),
+                Err(e) => {
+                    tracing::warn!("failed to read directory {:?}: {}", path, e);
+                    continue;
+                }
+            };
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    files
+}
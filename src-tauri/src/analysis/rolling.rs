@@ -0,0 +1,644 @@
+use std::collections::VecDeque;
+
+use crate::analysis::features::InputEvent;
+
+/// F2–F6・F8 を集計する直近の時間窓 [ms]
+pub const WINDOW_MS: u64 = 30_000;
+
+// Virtual key codes
+const VK_BACK: u32 = 0x08;
+const VK_DELETE: u32 = 0x2E;
+
+fn is_correction(vk_code: u32) -> bool {
+    vk_code == VK_BACK || vk_code == VK_DELETE
+}
+
+/// Welford 法による平均・母分散。値の追加に加えて、追加済みの値の取り除きにも対応する
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Welford {
+    count: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Welford {
+    pub fn add(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    /// `add()` した値を取り除く。空になったら誤差を持ち越さないよう 0 から数え直す
+    pub fn remove(&mut self, x: f64) {
+        if self.count <= 1 {
+            *self = Self::default();
+            return;
+        }
+        let n = self.count as f64;
+        let mean = (n * self.mean - x) / (n - 1.0);
+        self.m2 = (self.m2 - (x - self.mean) * (x - mean)).max(0.0);
+        self.mean = mean;
+        self.count -= 1;
+    }
+
+    /// 母分散 (2 件未満は 0)
+    pub fn variance(&self) -> f64 {
+        if self.count > 1 {
+            self.m2 / self.count as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    event: InputEvent,
+    // Press only: flight time from the latest release in the window (F2), if < pause threshold
+    flight_time: Option<u64>,
+    // Press only: whether this press breaks a burst (F4); None before the first release in the window
+    burst_break: Option<bool>,
+    // Press only: gap from the previous press in the window ≥ pause threshold (F5)
+    pause_before: bool,
+    // Press only: same, and the previous press was Backspace/Delete (F6)
+    del_pause_before: bool,
+    // Release only: hold time of the press it closed (F8), set by set_hold_time()
+    hold_time: Option<u64>,
+}
+
+/// F2–F6・F8 の集計値を、イベントがウィンドウに入る・出るたびに更新する。
+///
+/// ウィンドウは直近 `capacity` イベントのうち、最新イベントから `WINDOW_MS` 以内のもの。
+/// 打鍵ごとにバッファを走査し直す代わりに、各押下がウィンドウ内で寄与する値
+/// (フライトタイム・バースト区切り・直前の押下との間隔) を押下時に記録し、
+/// 先頭から外れたイベントの寄与だけを取り消す。
+///
+/// ```text
+/// F2: Welford 法の母分散 (直前の解放がウィンドウ外になった押下は取り除く)
+/// F3: 修正キー数 / 押下数
+/// F4: バースト長の合計 / バースト数 (最初の解放より前の押下は 1 バーストにまとまる)
+/// F5: 直前の押下から pause_threshold_ms 以上空いた押下の数
+/// F6: そのうち直前の押下が BS/Del だったもの / 修正キー数
+/// F8: Welford 法の母分散 (ホールド時間は解放に記録し、解放がウィンドウから出たら取り除く)
+/// ```
+///
+/// 押下・解放ごとの更新は償却 O(1) で、割り当ては行わない。
+/// 結果は走査による算出と同じになる (F2・F8 は浮動小数点の丸め誤差の範囲で一致)。
+/// イベントの時刻は単調非減少を前提とする。
+#[derive(Debug)]
+pub struct RollingWindow {
+    entries: VecDeque<Entry>,
+    capacity: usize,
+    pause_ms: u64,
+    burst_ms: u64,
+    // F2
+    flight_times: Welford,
+    // F8
+    hold_times: Welford,
+    // F3 / F6
+    presses: usize,
+    corrections: usize,
+    // F4: presses before the first release in the window, and breaks among the rest
+    prefix_presses: usize,
+    burst_breaks: usize,
+    // F4: burst_break of the first press after the first release in the window
+    first_break: Option<bool>,
+    // F5 / F6
+    pauses: usize,
+    del_pauses: usize,
+    // Latest release / press in the window, for the contributions of the next press
+    releases: usize,
+    last_release: Option<u64>,
+    last_press: Option<(u64, bool)>,
+}
+
+impl RollingWindow {
+    pub fn new(capacity: usize, pause_ms: u64, burst_ms: u64) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            pause_ms,
+            burst_ms,
+            flight_times: Welford::default(),
+            hold_times: Welford::default(),
+            presses: 0,
+            corrections: 0,
+            prefix_presses: 0,
+            burst_breaks: 0,
+            first_break: None,
+            pauses: 0,
+            del_pauses: 0,
+            releases: 0,
+            last_release: None,
+            last_press: None,
+        }
+    }
+
+    /// 閾値を差し替える。変わった場合はウィンドウ内のイベントから集計し直す
+    pub fn set_thresholds(&mut self, pause_ms: u64, burst_ms: u64) {
+        if pause_ms == self.pause_ms && burst_ms == self.burst_ms {
+            return;
+        }
+        let events: Vec<(InputEvent, Option<u64>)> = self
+            .entries
+            .iter()
+            .map(|e| (e.event, e.hold_time))
+            .collect();
+        *self = Self::new(self.capacity, pause_ms, burst_ms);
+        for (event, hold_time) in events {
+            self.push(event);
+            if let Some(hold_time) = hold_time {
+                self.set_hold_time(hold_time);
+            }
+        }
+    }
+
+    pub fn push(&mut self, event: InputEvent) {
+        if self.entries.len() >= self.capacity {
+            self.evict_front();
+        }
+        let cutoff = event.timestamp.saturating_sub(WINDOW_MS);
+        while self
+            .entries
+            .front()
+            .is_some_and(|e| e.event.timestamp < cutoff)
+        {
+            self.evict_front();
+        }
+
+        let entry = if event.is_press {
+            self.admit_press(event)
+        } else {
+            self.releases += 1;
+            self.last_release = Some(event.timestamp);
+            Entry {
+                event,
+                flight_time: None,
+                burst_break: None,
+                pause_before: false,
+                del_pause_before: false,
+                hold_time: None,
+            }
+        };
+        self.entries.push_back(entry);
+    }
+
+    /// 直前に `push()` した解放に、対になる押下からのホールド時間 (F8) を記録する。
+    /// 最後のイベントが解放でない・記録済みの場合は何もしない
+    pub fn set_hold_time(&mut self, hold_time: u64) {
+        let Some(back) = self.entries.back_mut() else {
+            return;
+        };
+        if back.event.is_press || back.hold_time.is_some() {
+            return;
+        }
+        back.hold_time = Some(hold_time);
+        self.hold_times.add(hold_time as f64);
+    }
+
+    fn admit_press(&mut self, event: InputEvent) -> Entry {
+        let ts = event.timestamp;
+        let (flight_time, burst_break) = match self.last_release {
+            Some(rel) => {
+                let ft = ts.checked_sub(rel).filter(|&ft| ft < self.pause_ms);
+                (ft, Some(ts.saturating_sub(rel) >= self.burst_ms))
+            }
+            None => (None, None),
+        };
+        if let Some(ft) = flight_time {
+            self.flight_times.add(ft as f64);
+        }
+        match burst_break {
+            Some(brk) => {
+                self.burst_breaks += usize::from(brk);
+                self.first_break.get_or_insert(brk);
+            }
+            None => self.prefix_presses += 1,
+        }
+
+        let (pause_before, del_pause_before) = match self.last_press {
+            Some((prev_ts, prev_del)) => {
+                let pause = ts.saturating_sub(prev_ts) >= self.pause_ms;
+                (pause, pause && prev_del)
+            }
+            None => (false, false),
+        };
+        self.pauses += usize::from(pause_before);
+        self.del_pauses += usize::from(del_pause_before);
+
+        let correction = is_correction(event.vk_code);
+        self.presses += 1;
+        self.corrections += usize::from(correction);
+        self.last_press = Some((ts, correction));
+
+        Entry {
+            event,
+            flight_time,
+            burst_break,
+            pause_before,
+            del_pause_before,
+            hold_time: None,
+        }
+    }
+
+    fn evict_front(&mut self) {
+        let Some(front) = self.entries.pop_front() else {
+            return;
+        };
+        if front.event.is_press {
+            self.evict_press(&front);
+        } else {
+            self.evict_release(&front);
+        }
+    }
+
+    /// 先頭の押下 (ウィンドウ内に直前の解放・押下は無い) を取り除き、
+    /// 次の押下から「直前の押下との間隔」の寄与を外す
+    fn evict_press(&mut self, front: &Entry) {
+        if let Some(ft) = front.flight_time {
+            self.flight_times.remove(ft as f64);
+        }
+        match front.burst_break {
+            Some(brk) => self.burst_breaks -= usize::from(brk),
+            None => self.prefix_presses -= 1,
+        }
+        self.pauses -= usize::from(front.pause_before);
+        self.del_pauses -= usize::from(front.del_pause_before);
+        self.presses -= 1;
+        self.corrections -= usize::from(is_correction(front.event.vk_code));
+
+        match self.entries.iter_mut().find(|e| e.event.is_press) {
+            Some(next) => {
+                self.pauses -= usize::from(next.pause_before);
+                self.del_pauses -= usize::from(next.del_pause_before);
+                next.pause_before = false;
+                next.del_pause_before = false;
+            }
+            None => self.last_press = None,
+        }
+    }
+
+    /// 先頭の解放とそのホールド時間を取り除く。これを直前の解放としていた押下 (次の解放まで) は
+    /// 最初の解放より前の押下になり、フライトタイムとバースト区切りの寄与を失う
+    fn evict_release(&mut self, front: &Entry) {
+        if let Some(hold) = front.hold_time {
+            self.hold_times.remove(hold as f64);
+        }
+        self.releases -= 1;
+        if self.releases == 0 {
+            self.last_release = None;
+        }
+
+        let mut converted = 0;
+        for entry in self.entries.iter_mut().take_while(|e| e.event.is_press) {
+            if let Some(ft) = entry.flight_time.take() {
+                self.flight_times.remove(ft as f64);
+            }
+            if let Some(brk) = entry.burst_break.take() {
+                self.burst_breaks -= usize::from(brk);
+                self.prefix_presses += 1;
+            }
+            converted += 1;
+        }
+        // 最初の解放の直後の押下が変わった
+        if converted > 0 {
+            self.first_break = self.entries.range(converted..).find_map(|e| e.burst_break);
+        }
+    }
+
+    /// ウィンドウ内の最新イベントの時刻 (空なら None)
+    pub fn last_timestamp(&self) -> Option<u64> {
+        self.entries.back().map(|e| e.event.timestamp)
+    }
+
+    /// F2: フライトタイム分散
+    pub fn flight_time_variance(&self) -> f64 {
+        self.flight_times.variance()
+    }
+
+    /// F8: ホールド時間分散
+    pub fn hold_time_variance(&self) -> f64 {
+        self.hold_times.variance()
+    }
+
+    /// F3: 修正率
+    pub fn correction_rate(&self) -> f64 {
+        if self.presses > 0 {
+            self.corrections as f64 / self.presses as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// F4: バースト長 = バーストの平均打鍵数
+    pub fn burst_length(&self) -> f64 {
+        let has_prefix = usize::from(self.prefix_presses > 0);
+        let total = has_prefix + (self.presses - self.prefix_presses);
+        let bursts = match self.first_break {
+            None => has_prefix,
+            // 最初の解放より前に押下が無いと、その直後の押下は区切りでも新しいバーストを閉じない
+            Some(first) if self.prefix_presses == 0 => self.burst_breaks - usize::from(first) + 1,
+            Some(_) => self.burst_breaks + 1,
+        };
+        if bursts > 0 {
+            total as f64 / bursts as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// F5: ポーズ回数
+    pub fn pause_count(&self) -> f64 {
+        self.pauses as f64
+    }
+
+    /// F6: 削除後停止率
+    pub fn pause_after_del_rate(&self) -> f64 {
+        if self.corrections > 0 {
+            self.del_pauses as f64 / self.corrections as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 旧 `calculate_features()` の F2–F6・F8 (バッファを毎回走査する実装)。
+    /// バッファの解放にはホールド時間を添える
+    fn rescan(
+        buffer: &VecDeque<(InputEvent, Option<u64>)>,
+        pause_ms: u64,
+        burst_ms: u64,
+    ) -> [f64; 6] {
+        let Some((last, _)) = buffer.back() else {
+            return [0.0; 6];
+        };
+        let cutoff = last.timestamp.saturating_sub(WINDOW_MS);
+        let events: Vec<&InputEvent> = buffer
+            .iter()
+            .map(|(e, _)| e)
+            .filter(|e| e.timestamp >= cutoff)
+            .collect();
+
+        let mut window_fts: Vec<f64> = Vec::new();
+        let mut last_release: Option<u64> = None;
+        for event in &events {
+            if event.is_press {
+                if let Some(rel) = last_release {
+                    if event.timestamp >= rel {
+                        let ft = (event.timestamp - rel) as f64;
+                        if ft < pause_ms as f64 {
+                            window_fts.push(ft);
+                        }
+                    }
+                }
+            } else {
+                last_release = Some(event.timestamp);
+            }
+        }
+        let f2 = if window_fts.len() > 1 {
+            let mean = window_fts.iter().sum::<f64>() / window_fts.len() as f64;
+            window_fts.iter().map(|ft| (ft - mean).powi(2)).sum::<f64>() / window_fts.len() as f64
+        } else {
+            0.0
+        };
+
+        let press_events: Vec<&&InputEvent> = events.iter().filter(|e| e.is_press).collect();
+        let total_keys = press_events.len();
+        let correction_keys = press_events
+            .iter()
+            .filter(|e| is_correction(e.vk_code))
+            .count();
+        let f3 = if total_keys > 0 {
+            correction_keys as f64 / total_keys as f64
+        } else {
+            0.0
+        };
+
+        let mut burst_lengths: Vec<usize> = Vec::new();
+        let mut current_burst: usize = 0;
+        let mut last_rel_for_burst: Option<u64> = None;
+        for event in &events {
+            if event.is_press {
+                if let Some(rel) = last_rel_for_burst {
+                    if event.timestamp.saturating_sub(rel) < burst_ms {
+                        current_burst += 1;
+                    } else {
+                        if current_burst > 0 {
+                            burst_lengths.push(current_burst);
+                        }
+                        current_burst = 1;
+                    }
+                } else {
+                    current_burst = 1;
+                }
+            } else {
+                last_rel_for_burst = Some(event.timestamp);
+            }
+        }
+        if current_burst > 0 {
+            burst_lengths.push(current_burst);
+        }
+        let f4 = if !burst_lengths.is_empty() {
+            burst_lengths.iter().sum::<usize>() as f64 / burst_lengths.len() as f64
+        } else {
+            0.0
+        };
+
+        let press_ts: Vec<u64> = press_events.iter().map(|e| e.timestamp).collect();
+        let f5 = press_ts
+            .windows(2)
+            .filter(|w| w[1].saturating_sub(w[0]) >= pause_ms)
+            .count() as f64;
+        let del_followed_by_pause = press_ts
+            .windows(2)
+            .zip(press_events.windows(2))
+            .filter(|(ts_win, ev_win)| {
+                is_correction(ev_win[0].vk_code) && ts_win[1].saturating_sub(ts_win[0]) >= pause_ms
+            })
+            .count();
+        let f6 = if correction_keys > 0 {
+            del_followed_by_pause as f64 / correction_keys as f64
+        } else {
+            0.0
+        };
+
+        let holds: Vec<f64> = buffer
+            .iter()
+            .filter(|(e, _)| e.timestamp >= cutoff)
+            .filter_map(|&(_, hold)| hold.map(|h| h as f64))
+            .collect();
+        let f8 = if holds.len() > 1 {
+            let mean = holds.iter().sum::<f64>() / holds.len() as f64;
+            holds.iter().map(|h| (h - mean).powi(2)).sum::<f64>() / holds.len() as f64
+        } else {
+            0.0
+        };
+
+        [f2, f3, f4, f5, f6, f8]
+    }
+
+    fn incremental(window: &RollingWindow) -> [f64; 6] {
+        [
+            window.flight_time_variance(),
+            window.correction_rate(),
+            window.burst_length(),
+            window.pause_count(),
+            window.pause_after_del_rate(),
+            window.hold_time_variance(),
+        ]
+    }
+
+    /// 打鍵列を模した乱数列 (線形合同法。外部クレートを使わず再現可能にする)
+    struct Session {
+        state: u64,
+        t: u64,
+        // (vk, press timestamp) of keys held down
+        held: Vec<(u32, u64)>,
+    }
+
+    impl Session {
+        fn new(seed: u64) -> Self {
+            Self {
+                state: seed,
+                t: 1_000,
+                held: Vec::new(),
+            }
+        }
+
+        fn next(&mut self, n: u64) -> u64 {
+            self.state = self
+                .state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.state >> 33) % n
+        }
+
+        /// 次のイベント。押下と解放が重なる打鍵、BS/Del、バースト・ポーズ・30 秒超の沈黙を含む。
+        /// 解放には対になる押下からのホールド時間を添える (1 割は取りこぼしとして None)
+        fn event(&mut self) -> (InputEvent, Option<u64>) {
+            self.t += match self.next(100) {
+                0..=49 => self.next(200),
+                50..=84 => 200 + self.next(600),
+                85..=96 => 2_000 + self.next(4_000),
+                _ => 30_000 + self.next(20_000),
+            };
+            let release = !self.held.is_empty() && (self.held.len() >= 3 || self.next(2) == 0);
+            if release {
+                let i = self.next(self.held.len() as u64) as usize;
+                let (vk_code, pressed_at) = self.held.swap_remove(i);
+                let hold = (self.next(10) > 0).then(|| self.t - pressed_at);
+                let event = InputEvent {
+                    vk_code,
+                    timestamp: self.t,
+                    is_press: false,
+                };
+                return (event, hold);
+            }
+            let vk_code = match self.next(10) {
+                0 => VK_BACK,
+                1 => VK_DELETE,
+                _ => 0x41 + self.next(26) as u32,
+            };
+            self.held.push((vk_code, self.t));
+            let event = InputEvent {
+                vk_code,
+                timestamp: self.t,
+                is_press: true,
+            };
+            (event, None)
+        }
+    }
+
+    fn assert_same(expected: [f64; 6], actual: [f64; 6], step: usize) {
+        for (name, i) in [("F2", 0), ("F8", 5)] {
+            let tol = 1e-6 * expected[i].abs().max(1.0);
+            assert!(
+                (expected[i] - actual[i]).abs() <= tol,
+                "{} differs at event {}: {} vs {}",
+                name,
+                step,
+                expected[i],
+                actual[i]
+            );
+        }
+        assert_eq!(
+            expected[1..5],
+            actual[1..5],
+            "F3–F6 differ at event {}",
+            step
+        );
+    }
+
+    fn push(window: &mut RollingWindow, (event, hold): (InputEvent, Option<u64>)) {
+        window.push(event);
+        if let Some(hold) = hold {
+            window.set_hold_time(hold);
+        }
+    }
+
+    fn run(seed: u64, capacity: usize, events: usize) {
+        let (pause_ms, burst_ms) = (2000, 200);
+        let mut session = Session::new(seed);
+        let mut buffer = VecDeque::with_capacity(capacity);
+        let mut window = RollingWindow::new(capacity, pause_ms, burst_ms);
+        for step in 0..events {
+            let event = session.event();
+            if buffer.len() >= capacity {
+                buffer.pop_front();
+            }
+            buffer.push_back(event);
+            push(&mut window, event);
+            assert_same(
+                rescan(&buffer, pause_ms, burst_ms),
+                incremental(&window),
+                step,
+            );
+        }
+    }
+
+    #[test]
+    fn matches_rescan_within_time_window() {
+        for seed in 1..=8 {
+            run(seed, 600, 5_000);
+        }
+    }
+
+    #[test]
+    fn matches_rescan_when_capacity_evicts() {
+        for seed in 11..=18 {
+            run(seed, 40, 5_000);
+        }
+    }
+
+    #[test]
+    fn matches_rescan_after_threshold_change() {
+        let capacity = 600;
+        let mut session = Session::new(42);
+        let mut buffer = VecDeque::with_capacity(capacity);
+        let mut window = RollingWindow::new(capacity, 2000, 200);
+        for step in 0..3_000 {
+            let (pause_ms, burst_ms) = if step < 1_500 {
+                (2000, 200)
+            } else {
+                (1500, 150)
+            };
+            window.set_thresholds(pause_ms, burst_ms);
+            let event = session.event();
+            if buffer.len() >= capacity {
+                buffer.pop_front();
+            }
+            buffer.push_back(event);
+            push(&mut window, event);
+            assert_same(
+                rescan(&buffer, pause_ms, burst_ms),
+                incremental(&window),
+                step,
+            );
+        }
+    }
+}
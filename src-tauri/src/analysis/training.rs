@@ -0,0 +1,249 @@
+use crate::analysis::engine::Observation;
+use crate::analysis::hmm::ForwardBackward;
+use crate::analysis::params::{EmissionModel, HmmParams, TransitionModel, N_OBS};
+
+/// Baum-Welch (EM) の設定
+#[derive(Debug, Clone)]
+pub struct BaumWelchConfig {
+    /// 最大反復回数
+    pub max_iters: usize,
+    /// 対数尤度の改善量がこれを下回ったら収束とみなす
+    pub tolerance: f64,
+    /// 放射行列の M ステップに加える擬似カウント (ゼロ頻度ビン対策)
+    pub emission_pseudocount: f64,
+    /// 遷移行列の M ステップに加える擬似カウント
+    pub transition_pseudocount: f64,
+    /// ガウス放射の共分散の対角に加える正則化項 (分散の潰れ防止)
+    pub covariance_floor: f64,
+}
+
+impl Default for BaumWelchConfig {
+    fn default() -> Self {
+        Self {
+            max_iters: 100,
+            tolerance: 1e-4,
+            emission_pseudocount: 0.1,
+            transition_pseudocount: 0.1,
+            covariance_floor: 1e-3,
+        }
+    }
+}
+
+/// 学習結果
+#[derive(Debug, Clone)]
+pub struct TrainingReport {
+    pub params: HmmParams,
+    pub iterations: usize,
+    /// 各反復の E ステップで得た総対数尤度 (全セッション合計)
+    pub log_likelihoods: Vec<f64>,
+    pub converged: bool,
+}
+
+/// ガウス放射の M ステップ用の重み付き十分統計量 (Σγ, Σγx, Σγxx^T)
+#[derive(Clone, Copy, Default)]
+struct GaussianStats {
+    weight: f64,
+    sum: [f64; 2],
+    sum_sq: [[f64; 2]; 2],
+}
+
+impl GaussianStats {
+    fn add(&mut self, w: f64, x: (f64, f64)) {
+        let v = [x.0, x.1];
+        self.weight += w;
+        for a in 0..2 {
+            self.sum[a] += w * v[a];
+            for b in 0..2 {
+                self.sum_sq[a][b] += w * v[a] * v[b];
+            }
+        }
+    }
+}
+
+/// 全セッションにわたる期待値統計量 (状態ごとの配列は `states` の並び)
+struct Accumulator {
+    initial: Vec<f64>,
+    transitions: Vec<Vec<f64>>,
+    /// 連続時間モデル用: 状態 i に滞在した期待時間 Σ_t γ_t(i)·Δt [秒]
+    dwell_secs: Vec<f64>,
+    emissions: Vec<[f64; N_OBS]>,
+    gaussians: Vec<GaussianStats>,
+    log_likelihood: f64,
+}
+
+impl Accumulator {
+    fn new(n_states: usize) -> Self {
+        Self {
+            initial: vec![0.0; n_states],
+            transitions: vec![vec![0.0; n_states]; n_states],
+            dwell_secs: vec![0.0; n_states],
+            emissions: vec![[0.0; N_OBS]; n_states],
+            gaussians: vec![GaussianStats::default(); n_states],
+            log_likelihood: 0.0,
+        }
+    }
+
+    /// E ステップ: 1 系列分の期待値を加算する
+    fn add_sequence(&mut self, params: &HmmParams, obs: &[Observation]) {
+        let fb = ForwardBackward::run_observations(params, obs);
+        self.log_likelihood += fb.log_likelihood();
+
+        let g0 = fb.gamma(0);
+        for (acc, g) in self.initial.iter_mut().zip(g0.iter()) {
+            *acc += g;
+        }
+
+        for (t, o) in obs.iter().enumerate() {
+            let xi = fb.xi(t);
+            let dt = o.dt_secs.unwrap_or(params.initial_step_secs);
+            for (i, (acc_row, xi_row)) in self.transitions.iter_mut().zip(xi.iter()).enumerate() {
+                for (acc, x) in acc_row.iter_mut().zip(xi_row.iter()) {
+                    *acc += x;
+                }
+                self.dwell_secs[i] += xi_row.iter().sum::<f64>() * dt;
+            }
+
+            let g = fb.gamma(t + 1);
+            for (j, &gj) in g.iter().enumerate() {
+                self.emissions[j][o.index] += gj;
+                // オーバーライド観測は B[j, obs] で評価されるためガウス統計には含めない
+                if o.override_rule.is_none() {
+                    self.gaussians[j].add(gj, o.axes);
+                }
+            }
+        }
+    }
+
+    /// M ステップ: 期待値から新しいパラメータを推定する。
+    /// 期待カウントがゼロの行は前回の値を維持する。
+    fn estimate(&self, prev: &HmmParams, config: &BaumWelchConfig) -> HmmParams {
+        let mut next = prev.clone();
+
+        let init_sum: f64 = self.initial.iter().sum();
+        if init_sum > 0.0 {
+            for (p, c) in next.initial.iter_mut().zip(self.initial.iter()) {
+                *p = c / init_sum;
+            }
+        }
+
+        match prev.transition_model {
+            TransitionModel::Discrete => {
+                for (row, counts) in next.transitions.iter_mut().zip(self.transitions.iter()) {
+                    normalize_row(row, counts, config.transition_pseudocount);
+                }
+            }
+            TransitionModel::ContinuousTime => {
+                // Q[i,j] ≈ (i→j の期待遷移回数) / (i の期待滞在時間)。
+                // 1 更新間隔に高々 1 回の遷移を仮定した一次近似 (Δt ≪ 平均滞在時間で妥当)。
+                for (i, row) in next.generator.iter_mut().enumerate() {
+                    let dwell = self.dwell_secs[i];
+                    if dwell <= 0.0 {
+                        continue;
+                    }
+                    let mut exit_rate = 0.0;
+                    for (j, q) in row.iter_mut().enumerate() {
+                        if j != i {
+                            *q = self.transitions[i][j] / dwell;
+                            exit_rate += *q;
+                        }
+                    }
+                    row[i] = -exit_rate;
+                }
+            }
+        }
+
+        match prev.emission_model {
+            EmissionModel::Discrete => {
+                for (row, counts) in next.emissions.iter_mut().zip(self.emissions.iter()) {
+                    normalize_row(row, counts, config.emission_pseudocount);
+                }
+            }
+            EmissionModel::Gaussian => {
+                for (g, stats) in next.gaussians.iter_mut().zip(self.gaussians.iter()) {
+                    if stats.weight <= 0.0 {
+                        continue;
+                    }
+                    let mean = [stats.sum[0] / stats.weight, stats.sum[1] / stats.weight];
+                    for a in 0..2 {
+                        for b in 0..2 {
+                            g.covariance[a][b] =
+                                stats.sum_sq[a][b] / stats.weight - mean[a] * mean[b];
+                        }
+                        g.covariance[a][a] += config.covariance_floor;
+                    }
+                    g.mean = mean;
+                }
+            }
+        }
+
+        next
+    }
+}
+
+fn normalize_row(row: &mut [f64], counts: &[f64], pseudocount: f64) {
+    let mass: f64 = counts.iter().sum();
+    if mass <= 0.0 {
+        return;
+    }
+    let total = mass + pseudocount * counts.len() as f64;
+    for (p, c) in row.iter_mut().zip(counts.iter()) {
+        *p = (c + pseudocount) / total;
+    }
+}
+
+/// Baum-Welch で遷移・π と放射パラメータを再推定する。
+///
+/// `sequences` は各セッションの観測系列 (`ReplayedSession::observations()`)。
+/// 放射パラメータは `initial.emission_model` に従い、`Discrete` なら B を、
+/// `Gaussian` なら各状態の平均・共分散を推定する (B のペナルティ列は据え置き)。
+/// 遷移は `initial.transition_model` に従い、`Discrete` なら A を、`ContinuousTime` なら
+/// 生成行列 Q を推定する。
+/// E ステップは `update()` と同じ床付き放射尤度 (`hmm::emission_likelihoods()`) を用いるため、
+/// 学習後のパラメータもライブ推論時と同じ規約で解釈される。
+pub fn baum_welch(
+    initial: &HmmParams,
+    sequences: &[Vec<Observation>],
+    config: &BaumWelchConfig,
+) -> TrainingReport {
+    let mut params = initial.clone();
+    let mut log_likelihoods = Vec::new();
+    let mut converged = false;
+
+    let sequences: Vec<&Vec<Observation>> = sequences.iter().filter(|s| !s.is_empty()).collect();
+    if sequences.is_empty() {
+        return TrainingReport {
+            params,
+            iterations: 0,
+            log_likelihoods,
+            converged,
+        };
+    }
+
+    for _ in 0..config.max_iters {
+        let mut acc = Accumulator::new(params.n_states());
+        for obs in &sequences {
+            acc.add_sequence(&params, obs);
+        }
+
+        let improved = log_likelihoods
+            .last()
+            .map(|prev: &f64| acc.log_likelihood - prev);
+        log_likelihoods.push(acc.log_likelihood);
+
+        if let Some(delta) = improved {
+            if delta.abs() < config.tolerance {
+                converged = true;
+                break;
+            }
+        }
+
+        params = acc.estimate(&params, config);
+    }
+
+    TrainingReport {
+        params,
+        iterations: log_likelihoods.len(),
+        log_likelihoods,
+        converged,
+    }
+}
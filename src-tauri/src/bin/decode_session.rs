@@ -0,0 +1,128 @@
+// Viterbi による最尤状態系列の復号。
+//
+// 使用例:
+//   cargo run --bin decode_session -- path/to/gse_20260221_143022.ndjson
+//   cargo run --bin decode_session -- --params engine_params.json Documents/GSE-sessions/
+//
+// 各セッションについて <入力名>_viterbi.csv を入力と同じディレクトリに書き出す。
+// 列は behavioral_gt.py の出力 (start_ms, end_ms, label, evidence) と同じなので、
+// GT ラベルと区間単位で突き合わせられる。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use gse_next_lib::analysis::decoding::{decode_session, StateSegment};
+use gse_next_lib::analysis::engine::CognitiveState;
+use gse_next_lib::analysis::params::{EngineParams, HmmParams};
+use gse_next_lib::analysis::replay::{collect_session_files, replay_file};
+use gse_next_lib::logger::default_session_dir;
+
+const USAGE: &str = "usage: decode_session [--params PARAMS.json] [SESSION.ndjson | DIR]...";
+
+fn main() {
+    let mut params_path: Option<PathBuf> = None;
+    let mut inputs: Vec<PathBuf> = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--params" => params_path = args.next().map(PathBuf::from),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+
+    if inputs.is_empty() {
+        inputs.push(default_session_dir());
+    }
+
+    let params = match params_path {
+        Some(p) => EngineParams::load(&p).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            exit(1);
+        }),
+        None => EngineParams::default(),
+    };
+
+    let files = collect_session_files(&inputs);
+    if files.is_empty() {
+        eprintln!("No session files found. {}", USAGE);
+        exit(1);
+    }
+
+    for file in &files {
+        let session = match replay_file(file, Some(&params)) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("[warn] {}", e);
+                continue;
+            }
+        };
+
+        let decoded = decode_session(&session, &params.hmm);
+        println!(
+            "{:?}: {} observations, {} segments, log P = {:.3}",
+            file,
+            decoded.path.len(),
+            decoded.segments.len(),
+            decoded.log_probability
+        );
+        print_summary(&decoded.segments, &params.hmm);
+
+        let out_path = output_path(file);
+        match write_csv(&decoded.segments, &params.hmm, &out_path) {
+            Ok(()) => println!("  Wrote {:?}", out_path),
+            Err(e) => eprintln!("[warn] {}", e),
+        }
+    }
+}
+
+fn output_path(input: &Path) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("session");
+    input.with_file_name(format!("{}_viterbi.csv", stem))
+}
+
+fn write_csv(segments: &[StateSegment], hmm: &HmmParams, path: &Path) -> Result<(), String> {
+    let mut out = String::from("start_ms,end_ms,label,evidence\n");
+    for seg in segments {
+        out.push_str(&format!(
+            "{},{},{},ticks={}\n",
+            seg.start_ms,
+            seg.end_ms,
+            hmm.state_label(seg.state.index()),
+            seg.ticks
+        ));
+    }
+    fs::write(path, out).map_err(|e| format!("failed to write {:?}: {}", path, e))
+}
+
+fn print_summary(segments: &[StateSegment], hmm: &HmmParams) {
+    let total: u64 = segments.iter().map(|s| s.duration_ms()).sum();
+    for state in (0..hmm.n_states()).map(CognitiveState) {
+        let ms: u64 = segments
+            .iter()
+            .filter(|s| s.state == state)
+            .map(|s| s.duration_ms())
+            .sum();
+        let count = segments.iter().filter(|s| s.state == state).count();
+        let pct = if total > 0 {
+            100.0 * ms as f64 / total as f64
+        } else {
+            0.0
+        };
+        println!(
+            "  {:<10} {:>3} segments  {:>8.1}s  ({:>5.1}%)",
+            hmm.state_label(state.index()),
+            count,
+            ms as f64 / 1000.0,
+            pct
+        );
+    }
+}
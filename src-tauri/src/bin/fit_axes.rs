@@ -0,0 +1,176 @@
+// 潜在軸 (Friction × Engagement) への射影をセッションログから学習する。
+//
+// 使用例:
+//   cargo run --bin fit_axes -- --out engine_params.json
+//   cargo run --bin fit_axes -- --init engine_params.json --method factor path/to/gse_*.ndjson
+//   cargo run --bin fit_axes -- --method regression Documents/GSE-sessions/
+//
+// 各セッションを --init の特徴量・正規化設定で再生し、正規化した F1–F6 (F2 を含む) から
+// X・Y への線形射影を求める。
+//   pca        第 1・第 2 主成分
+//   factor     2 因子の因子分析 (EM、Thomson 法の因子得分)
+//   regression behavioral_gt.py の GT ラベル (<入力名>_labeled.csv) に対するリッジ回帰。
+//              目標値は各状態のガウス放射の平均 (hmm.gaussians[状態].mean)
+// pca / factor は現在の射影 (axes.projection、無ければ手設定の重み) の X・Y と相関が
+// 大きくなるよう成分を割り当て、平均・標準偏差を揃える。
+// 結果を axes.projection に書き込んで出力する。放射モデルは軸に合わせて学習されているため、
+// 射影を変えたら train_hmm で再学習する。
+
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use gse_next_lib::analysis::params::{EngineParams, ProjectionMethod};
+use gse_next_lib::analysis::projection::{
+    collect_samples, fit_regression, fit_unsupervised, gt_state_at, load_gt_segments,
+    ProjectionSample,
+};
+use gse_next_lib::analysis::replay::{collect_session_files, replay_file};
+use gse_next_lib::logger::default_session_dir;
+
+const USAGE: &str = "usage: fit_axes [--init PARAMS.json] [--out PARAMS.json] \
+                     [--method pca|factor|regression] [--ridge X] [SESSION.ndjson | DIR]...";
+
+fn main() {
+    let mut init_path: Option<PathBuf> = None;
+    let mut out_path = PathBuf::from("engine_params.json");
+    let mut method = ProjectionMethod::Pca;
+    let mut ridge = 1e-3;
+    let mut inputs: Vec<PathBuf> = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--init" => init_path = args.next().map(PathBuf::from),
+            "--out" => out_path = args.next().map(PathBuf::from).unwrap_or(out_path),
+            "--method" => {
+                method = match args.next().as_deref() {
+                    Some("pca") => ProjectionMethod::Pca,
+                    Some("factor") => ProjectionMethod::Factor,
+                    Some("regression") => ProjectionMethod::Regression,
+                    _ => {
+                        eprintln!(
+                            "Error: --method expects pca, factor or regression. {}",
+                            USAGE
+                        );
+                        exit(1);
+                    }
+                }
+            }
+            "--ridge" => {
+                ridge = match args.next().and_then(|v| v.parse::<f64>().ok()) {
+                    Some(v) if v >= 0.0 => v,
+                    _ => {
+                        eprintln!("Error: --ridge expects a number >= 0. {}", USAGE);
+                        exit(1);
+                    }
+                }
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+
+    if inputs.is_empty() {
+        inputs.push(default_session_dir());
+    }
+
+    let mut params = match init_path {
+        Some(p) => EngineParams::load(&p).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            exit(1);
+        }),
+        None => EngineParams::default(),
+    };
+
+    let mut samples: Vec<ProjectionSample> = Vec::new();
+    let mut targets: Vec<(f64, f64)> = Vec::new();
+    for file in collect_session_files(&inputs) {
+        let labels = if method == ProjectionMethod::Regression {
+            match load_gt_segments(&labels_path(&file), &params.hmm) {
+                Ok(l) => Some(l),
+                Err(e) => {
+                    eprintln!("[warn] {} (run behavioral_gt.py first)", e);
+                    continue;
+                }
+            }
+        } else {
+            None
+        };
+        let session = match replay_file(&file, Some(&params)) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("[warn] {}", e);
+                continue;
+            }
+        };
+        let collected = collect_samples(&session, &params.axes);
+        let before = samples.len();
+        match &labels {
+            Some(segments) => {
+                for s in collected {
+                    let gaussian = gt_state_at(segments, s.timestamp)
+                        .and_then(|state| params.hmm.gaussians.get(state.index()));
+                    if let Some(g) = gaussian {
+                        let mean = g.mean;
+                        samples.push(s);
+                        targets.push((mean[0], mean[1]));
+                    }
+                }
+            }
+            None => samples.extend(collected),
+        }
+        println!("  {:?}: {} samples", file, samples.len() - before);
+    }
+
+    let reference = params.axes.projection.clone().unwrap_or_default();
+    let fit = match method {
+        ProjectionMethod::Regression => fit_regression(&samples, &targets, ridge),
+        _ => fit_unsupervised(&samples, &reference, method),
+    };
+    let fit = fit.unwrap_or_else(|e| {
+        eprintln!("Error: {}. {}", e, USAGE);
+        exit(1);
+    });
+
+    let score_label = match method {
+        ProjectionMethod::Pca => "explained variance",
+        ProjectionMethod::Factor => "communality share",
+        _ => "R²",
+    };
+    println!(
+        "{:?} on {} samples ({}: X {:.3}, Y {:.3})",
+        method, fit.samples, score_label, fit.score.0, fit.score.1
+    );
+    println!(
+        "{:<10} {:>7} {:>7} {:>7} {:>7} {:>7} {:>7} {:>7}",
+        "axis", "bias", "F1", "F2", "F3", "F4", "F5", "F6"
+    );
+    for (name, axis) in [
+        ("friction", &fit.projection.friction),
+        ("engagement", &fit.projection.engagement),
+    ] {
+        let w = &axis.weights;
+        println!(
+            "{:<10} {:>7.3} {:>7.3} {:>7.3} {:>7.3} {:>7.3} {:>7.3} {:>7.3}",
+            name, axis.bias, w.f1, w.f2, w.f3, w.f4, w.f5, w.f6
+        );
+    }
+
+    params.axes.projection = Some(fit.projection);
+    if let Err(e) = params.save(&out_path) {
+        eprintln!("Error: {}", e);
+        exit(1);
+    }
+    println!("Wrote {:?}", out_path);
+}
+
+fn labels_path(session: &Path) -> PathBuf {
+    let stem = session
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("session");
+    session.with_file_name(format!("{}_labeled.csv", stem))
+}
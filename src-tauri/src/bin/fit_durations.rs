@@ -0,0 +1,129 @@
+// HSMM の状態別滞在時間分布をセッションログから推定する。
+//
+// 使用例:
+//   cargo run --bin fit_durations -- --out engine_params.json
+//   cargo run --bin fit_durations -- --init engine_params.json --family lognormal path/to/gse_*.ndjson
+//
+// 各セッションを再生して Viterbi で状態区間に分割し (decode_session と同じ)、状態ごとの
+// 区間長 [秒] にガンマ分布または対数正規分布を当てはめる。セッションの最初と最後の区間は
+// 記録範囲で打ち切られているため除外する。--init のパラメータに hsmm.durations を書き込み、
+// hsmm.enabled = true として出力する。
+
+use std::path::PathBuf;
+use std::process::exit;
+
+use gse_next_lib::analysis::decoding::decode_session;
+use gse_next_lib::analysis::hsmm::DurationFamily;
+use gse_next_lib::analysis::params::{DurationDistribution, EngineParams};
+use gse_next_lib::analysis::replay::{collect_session_files, replay_file};
+use gse_next_lib::logger::default_session_dir;
+
+const USAGE: &str = "usage: fit_durations [--init PARAMS.json] [--out PARAMS.json] \
+                     [--family gamma|lognormal] [SESSION.ndjson | DIR]...";
+
+fn main() {
+    let mut init_path: Option<PathBuf> = None;
+    let mut out_path = PathBuf::from("engine_params.json");
+    let mut family = DurationFamily::Gamma;
+    let mut inputs: Vec<PathBuf> = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--init" => init_path = args.next().map(PathBuf::from),
+            "--out" => out_path = args.next().map(PathBuf::from).unwrap_or(out_path),
+            "--family" => {
+                family = match args.next().as_deref() {
+                    Some("gamma") => DurationFamily::Gamma,
+                    Some("lognormal") => DurationFamily::LogNormal,
+                    _ => {
+                        eprintln!("Error: --family expects gamma or lognormal. {}", USAGE);
+                        exit(1);
+                    }
+                }
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+
+    if inputs.is_empty() {
+        inputs.push(default_session_dir());
+    }
+
+    let mut params = match init_path {
+        Some(p) => EngineParams::load(&p).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            exit(1);
+        }),
+        None => EngineParams::default(),
+    };
+
+    let n_states = params.hmm.n_states();
+    let mut samples: Vec<Vec<f64>> = vec![Vec::new(); n_states];
+    for file in collect_session_files(&inputs) {
+        let session = match replay_file(&file, Some(&params)) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("[warn] {}", e);
+                continue;
+            }
+        };
+        let decoded = decode_session(&session, &params.hmm);
+        let n = decoded.segments.len();
+        println!("  {:?}: {} segments", file, n);
+        if n <= 2 {
+            continue;
+        }
+        for seg in &decoded.segments[1..n - 1] {
+            samples[seg.state.index()].push(seg.duration_ms() as f64 / 1000.0);
+        }
+    }
+
+    // 状態を増やしたパラメータで durations が足りない場合は平均 60s のガンマ分布で補う
+    params.hsmm.durations.resize(
+        n_states,
+        DurationDistribution::Gamma {
+            shape: 2.0,
+            scale: 30.0,
+        },
+    );
+    let mut fitted_any = false;
+    for (i, xs) in samples.iter().enumerate() {
+        let label = params.hmm.state_label(i).to_string();
+        match DurationDistribution::fit(family, xs) {
+            Some(dist) => {
+                println!(
+                    "{:<10} n = {:>4}  {:?}  (mean {:.1}s)",
+                    label,
+                    xs.len(),
+                    dist,
+                    dist.mean()
+                );
+                params.hsmm.durations[i] = dist;
+                fitted_any = true;
+            }
+            None => println!(
+                "{:<10} n = {:>4}  not enough segments, keeping {:?}",
+                label,
+                xs.len(),
+                params.hsmm.durations[i]
+            ),
+        }
+    }
+
+    if !fitted_any {
+        eprintln!("No complete segments found. {}", USAGE);
+        exit(1);
+    }
+
+    params.hsmm.enabled = true;
+    if let Err(e) = params.save(&out_path) {
+        eprintln!("Error: {}", e);
+        exit(1);
+    }
+    println!("Wrote {:?}", out_path);
+}
@@ -0,0 +1,126 @@
+// Forward-Backward による平滑化事後確率の算出。
+//
+// 使用例:
+//   cargo run --bin smooth_session -- path/to/gse_20260221_143022.ndjson
+//   cargo run --bin smooth_session -- --params engine_params.json --max-lag 60 Documents/GSE-sessions/
+//
+// 各セッションについて <入力名>_smoothed.csv を入力と同じディレクトリに書き出す。
+// 1 行 = ログの feat 1 行で、ライブの display_probs (live_*)、前向きフィルタ (filt_*)、
+// 平滑化事後確率 P(s_t | o_1..o_T) (smooth_*) を並べる。
+// あわせてライブ推定が平滑化値に対して何ティック遅れているかを表示する。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use gse_next_lib::analysis::decoding::{lag_report, smooth_session, PosteriorTick};
+use gse_next_lib::analysis::params::{EngineParams, HmmParams};
+use gse_next_lib::analysis::replay::{collect_session_files, replay_file};
+use gse_next_lib::logger::default_session_dir;
+
+const USAGE: &str =
+    "usage: smooth_session [--params PARAMS.json] [--max-lag TICKS] [SESSION.ndjson | DIR]...";
+
+fn main() {
+    let mut params_path: Option<PathBuf> = None;
+    let mut max_lag: usize = 30;
+    let mut inputs: Vec<PathBuf> = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--params" => params_path = args.next().map(PathBuf::from),
+            "--max-lag" => match args.next().as_deref().map(str::parse) {
+                Some(Ok(v)) => max_lag = v,
+                _ => {
+                    eprintln!("Error: --max-lag expects a value. {}", USAGE);
+                    exit(1);
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+
+    if inputs.is_empty() {
+        inputs.push(default_session_dir());
+    }
+
+    let params = match params_path {
+        Some(p) => EngineParams::load(&p).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            exit(1);
+        }),
+        None => EngineParams::default(),
+    };
+
+    let files = collect_session_files(&inputs);
+    if files.is_empty() {
+        eprintln!("No session files found. {}", USAGE);
+        exit(1);
+    }
+
+    for file in &files {
+        let session = match replay_file(file, Some(&params)) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("[warn] {}", e);
+                continue;
+            }
+        };
+
+        let ticks = smooth_session(&session, &params.hmm);
+        let report = lag_report(&ticks, max_lag);
+        println!("{:?}: {} ticks", file, ticks.len());
+        println!(
+            "  live vs smoothed: mean TV = {:.3}, argmax agreement = {:.1}%",
+            report.mean_tv,
+            100.0 * report.argmax_agreement
+        );
+        println!(
+            "  best alignment at lag {} ticks (mean TV = {:.3})",
+            report.best_lag_ticks, report.best_lag_tv
+        );
+
+        let out_path = output_path(file);
+        match write_csv(&ticks, &params.hmm, &out_path) {
+            Ok(()) => println!("  Wrote {:?}", out_path),
+            Err(e) => eprintln!("[warn] {}", e),
+        }
+    }
+}
+
+fn output_path(input: &Path) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("session");
+    input.with_file_name(format!("{}_smoothed.csv", stem))
+}
+
+fn write_csv(ticks: &[PosteriorTick], hmm: &HmmParams, path: &Path) -> Result<(), String> {
+    // 列は t, live_<状態名>..., filt_<状態名>..., smooth_<状態名>...
+    let mut out = String::from("t");
+    for prefix in ["live", "filt", "smooth"] {
+        for state in &hmm.states {
+            out.push_str(&format!(",{}_{}", prefix, state.name));
+        }
+    }
+    out.push('\n');
+    for tick in ticks {
+        out.push_str(&tick.timestamp.to_string());
+        for p in tick
+            .live
+            .iter()
+            .chain(tick.filtered.iter())
+            .chain(tick.smoothed.iter())
+        {
+            out.push_str(&format!(",{:.4}", p));
+        }
+        out.push('\n');
+    }
+    fs::write(path, out).map_err(|e| format!("failed to write {:?}: {}", path, e))
+}
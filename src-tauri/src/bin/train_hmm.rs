@@ -0,0 +1,148 @@
+// Baum-Welch による HMM パラメータのオフライン学習。
+//
+// 使用例:
+//   cargo run --bin train_hmm -- --out engine_params.json
+//   cargo run --bin train_hmm -- --init engine_params.json --iters 50 path/to/gse_*.ndjson
+//   cargo run --bin train_hmm -- --model gaussian --out engine_params.json
+//   cargo run --bin train_hmm -- --transitions continuous --out engine_params.json
+//
+// セッションファイルを省略した場合は Documents/GSE-sessions/ 以下の
+// gse_*.ndjson をすべて使用する。--init で指定したパラメータファイルの
+// 特徴量・軸設定で再生し、HMM 部分 (A・B・π、またはガウス放射) だけを学習結果で
+// 置き換えて書き出す。--model gaussian を指定すると連続 (X, Y) 上のガウス放射を学習し、
+// 出力ファイルの emission_model も gaussian になる。--transitions continuous を指定すると
+// A の代わりに連続時間の生成行列 Q を学習する (transition_model = continuous_time)。
+
+use std::path::PathBuf;
+use std::process::exit;
+
+use gse_next_lib::analysis::params::{EmissionModel, EngineParams, TransitionModel};
+use gse_next_lib::analysis::replay::{collect_session_files, replay_file};
+use gse_next_lib::analysis::training::{baum_welch, BaumWelchConfig};
+use gse_next_lib::logger::default_session_dir;
+
+const USAGE: &str = "usage: train_hmm [--init PARAMS.json] [--out PARAMS.json] \
+                     [--model discrete|gaussian] \
+                     [--transitions discrete|continuous] [--iters N] [--tol X] [SESSION.ndjson | DIR]...";
+
+fn main() {
+    let mut init_path: Option<PathBuf> = None;
+    let mut model: Option<EmissionModel> = None;
+    let mut transition_model: Option<TransitionModel> = None;
+    let mut out_path = PathBuf::from("engine_params.json");
+    let mut config = BaumWelchConfig::default();
+    let mut inputs: Vec<PathBuf> = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--init" => init_path = args.next().map(PathBuf::from),
+            "--out" => out_path = args.next().map(PathBuf::from).unwrap_or(out_path),
+            "--model" => {
+                model = match args.next().as_deref() {
+                    Some("discrete") => Some(EmissionModel::Discrete),
+                    Some("gaussian") => Some(EmissionModel::Gaussian),
+                    _ => {
+                        eprintln!("Error: --model expects discrete or gaussian. {}", USAGE);
+                        exit(1);
+                    }
+                }
+            }
+            "--transitions" => {
+                transition_model = match args.next().as_deref() {
+                    Some("discrete") => Some(TransitionModel::Discrete),
+                    Some("continuous") => Some(TransitionModel::ContinuousTime),
+                    _ => {
+                        eprintln!(
+                            "Error: --transitions expects discrete or continuous. {}",
+                            USAGE
+                        );
+                        exit(1);
+                    }
+                }
+            }
+            "--iters" => config.max_iters = parse_or_exit(args.next(), "--iters"),
+            "--tol" => config.tolerance = parse_or_exit(args.next(), "--tol"),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+
+    if inputs.is_empty() {
+        inputs.push(default_session_dir());
+    }
+
+    let mut params = match init_path {
+        Some(p) => EngineParams::load(&p).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            exit(1);
+        }),
+        None => EngineParams::default(),
+    };
+    if let Some(m) = model {
+        params.hmm.emission_model = m;
+    }
+    if let Some(m) = transition_model {
+        params.hmm.transition_model = m;
+    }
+
+    let files = collect_session_files(&inputs);
+    let mut sequences = Vec::new();
+    for file in &files {
+        match replay_file(file, Some(&params)) {
+            Ok(session) => {
+                let seq = session.observations();
+                println!("  {:?}: {} observations", file, seq.len());
+                sequences.push(seq);
+            }
+            Err(e) => eprintln!("[warn] {}", e),
+        }
+    }
+
+    let total: usize = sequences.iter().map(|s| s.len()).sum();
+    if total == 0 {
+        eprintln!("No observations found. {}", USAGE);
+        exit(1);
+    }
+    println!(
+        "Training {:?}/{:?} model on {} sessions ({} observations) ...",
+        params.hmm.emission_model,
+        params.hmm.transition_model,
+        sequences.len(),
+        total
+    );
+
+    let report = baum_welch(&params.hmm, &sequences, &config);
+    for (i, ll) in report.log_likelihoods.iter().enumerate() {
+        println!("  iter {:>3}: log-likelihood = {:.3}", i + 1, ll);
+    }
+    println!(
+        "{} after {} iterations",
+        if report.converged {
+            "Converged"
+        } else {
+            "Stopped"
+        },
+        report.iterations
+    );
+
+    params.hmm = report.params;
+    if let Err(e) = params.save(&out_path) {
+        eprintln!("Error: {}", e);
+        exit(1);
+    }
+    println!("Wrote {:?}", out_path);
+}
+
+fn parse_or_exit<T: std::str::FromStr>(value: Option<String>, flag: &str) -> T {
+    match value.as_deref().map(str::parse) {
+        Some(Ok(v)) => v,
+        _ => {
+            eprintln!("Error: {} expects a value. {}", flag, USAGE);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use windows::Win32::Foundation::BOOL;
+use windows::Win32::System::StationsAndDesktops::{
+    CloseDesktop, OpenInputDesktop, DESKTOP_CONTROL_FLAGS, DESKTOP_SWITCHDESKTOP,
+};
+use windows::Win32::System::SystemInformation::GetTickCount;
+use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+
+/// Seconds since the last keyboard or mouse input anywhere in the session.
+///
+/// Uses GetLastInputInfo, which covers mouse movement that the keyboard hook never sees,
+/// so reading or scrolling at the desk does not count as absence.
+/// Returns None if the call fails.
+pub fn system_idle_secs() -> Option<f64> {
+    unsafe {
+        let mut info = LASTINPUTINFO {
+            cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+            dwTime: 0,
+        };
+        if !GetLastInputInfo(&mut info).as_bool() {
+            return None;
+        }
+        // Both are GetTickCount values; wrapping_sub handles the 49.7-day rollover
+        let idle_ms = GetTickCount().wrapping_sub(info.dwTime);
+        Some(idle_ms as f64 / 1000.0)
+    }
+}
+
+/// Returns true while the workstation is locked (or the secure desktop is shown).
+///
+/// When the session is locked the input desktop is Winlogon's, which a normal
+/// process cannot open, so OpenInputDesktop fails.
+pub fn is_session_locked() -> bool {
+    unsafe {
+        match OpenInputDesktop(
+            DESKTOP_CONTROL_FLAGS(0),
+            BOOL::from(false),
+            DESKTOP_SWITCHDESKTOP,
+        ) {
+            Ok(desktop) => {
+                let _ = CloseDesktop(desktop);
+                false
+            }
+            Err(_) => true,
+        }
+    }
+}
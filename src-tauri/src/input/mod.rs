@@ -1,2 +1,3 @@
 pub mod hook;
+pub mod idle;
 pub mod ime;
@@ -4,8 +4,16 @@ pub mod logger;
 pub mod sensors;
 
 use crate::analysis::{
-    engine::{CognitiveState, CognitiveStateEngine},
-    features::FeatureExtractor,
+    engine::{CognitiveStateEngine, StateTransition, StateUncertainty, UpdateOutcome},
+    calibration::{CalibrationProfile, CalibrationStatus},
+    explain::UpdateExplanation,
+    features::{FeatureExtractor, InputEvent},
+    hsmm::DwellEstimate,
+    params::{self, EngineParams, PersistenceParams, StateDef},
+    persistence::{self, ExtractorCheckpoint, PersistedState},
+    presence::{
+        AwayReason, Presence, PresenceChange, PresenceSignals, PresenceStatus, PresenceTracker,
+    },
 };
 use crate::logger::{LogEntry, SessionLogger};
 use crate::sensors::SensorManager;
@@ -15,7 +23,7 @@ use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
-use tauri::{Manager, State};
+use tauri::{Emitter, Manager, State};
 
 // ---------------------------------------------------------------------------
 // ログ状態 (quit_app からも参照できるよう Tauri state で管理)
@@ -26,6 +34,11 @@ struct LogState {
     path: String,
 }
 
+// 終了時の状態保存要求 (quit_app → 分析スレッド)。保存が終わると応答チャネルに通知する
+struct CheckpointState {
+    tx: Sender<Sender<()>>,
+}
+
 // ---------------------------------------------------------------------------
 // Tauri コマンド
 // ---------------------------------------------------------------------------
@@ -35,19 +48,67 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
+/// 状態名 (`states[].name`) → display_probs
 #[tauri::command]
 fn get_cognitive_state(state: State<CognitiveStateEngine>) -> HashMap<String, f64> {
-    let probs = state.get_current_state();
-    let mut map = HashMap::new();
-    for (k, v) in probs {
-        let key_str = match k {
-            CognitiveState::Flow => "flow",
-            CognitiveState::Incubation => "incubation",
-            CognitiveState::Stuck => "stuck",
-        };
-        map.insert(key_str.to_string(), v);
-    }
-    map
+    state.get_current_state().into_iter().collect()
+}
+
+/// 状態の定義 (名前・GT ラベル)。並び順が確率ベクトルのインデックス
+#[tauri::command]
+fn get_state_definitions(state: State<CognitiveStateEngine>) -> Vec<StateDef> {
+    state.params().hmm.states.clone()
+}
+
+/// 在席状態 (離席中は状態確率が離席前の値のまま止まる)
+#[tauri::command]
+fn get_presence(state: State<CognitiveStateEngine>) -> PresenceStatus {
+    state.presence()
+}
+
+/// 現在の信念の不確かさ (エントロピー・上位 2 状態の差・raw/display 乖離・証拠不足フラグ)
+#[tauri::command]
+fn get_state_uncertainty(state: State<CognitiveStateEngine>) -> StateUncertainty {
+    state.uncertainty()
+}
+
+/// 直前の更新の判断根拠 (デバッグパネル用)。`debug.explain` 無効時は null
+#[tauri::command]
+fn get_last_explanation(state: State<CognitiveStateEngine>) -> Option<UpdateExplanation> {
+    state.last_explanation()
+}
+
+/// HSMM 有効時の現在状態の滞在時間推定 (期待経過時間・期待残り時間)。無効時は null
+#[tauri::command]
+fn get_dwell_estimate(state: State<CognitiveStateEngine>) -> Option<DwellEstimate> {
+    state.dwell_estimate()
+}
+
+/// 個人ベースラインの学習状況 (キャリブレーション中か・学習済み β)
+#[tauri::command]
+fn get_calibration_status(state: State<CognitiveStateEngine>) -> CalibrationStatus {
+    state.calibration_status()
+}
+
+/// 現在適用中のエンジンパラメータを返す
+#[tauri::command]
+fn get_engine_params(state: State<CognitiveStateEngine>) -> EngineParams {
+    (*state.params()).clone()
+}
+
+/// エンジンパラメータを差し替える (現在の信念は保持)。
+/// 適用したパラメータはセッションログに記録する。
+#[tauri::command]
+fn set_engine_params(
+    params: EngineParams,
+    state: State<CognitiveStateEngine>,
+    log: State<Arc<Mutex<LogState>>>,
+) -> Result<(), String> {
+    state.set_params(params.clone())?;
+    let guard = log.lock().unwrap_or_else(|p| p.into_inner());
+    log_params(&guard.tx, "command", &params);
+    tracing::info!("Engine params updated via command");
+    Ok(())
 }
 
 /// 現在のセッションログファイルのパスを返す (UI表示用)
@@ -64,7 +125,17 @@ fn get_session_file(log: State<Arc<Mutex<LogState>>>) -> String {
 ///   2. behavioral_gt.py でラベリング分析を実行 (Python が PATH にある場合)
 ///   3. セッションフォルダを Explorer で開く
 #[tauri::command]
-fn quit_app(app: tauri::AppHandle, log: State<Arc<Mutex<LogState>>>) {
+fn quit_app(
+    app: tauri::AppHandle,
+    log: State<Arc<Mutex<LogState>>>,
+    checkpoint: State<CheckpointState>,
+) {
+    // エンジン・FeatureExtractor の状態を保存 (次回起動時のウォームスタート用)
+    let (done_tx, done_rx) = crossbeam_channel::bounded(1);
+    if checkpoint.tx.try_send(done_tx).is_ok() {
+        let _ = done_rx.recv_timeout(Duration::from_millis(1500));
+    }
+
     // ログ終了マーカーを送信
     let session_path = {
         let guard = log.lock().unwrap_or_else(|p| p.into_inner());
@@ -119,6 +190,230 @@ fn quit_app(app: tauri::AppHandle, log: State<Arc<Mutex<LogState>>>) {
     });
 }
 
+// ---------------------------------------------------------------------------
+// パラメータのホットリロード
+// ---------------------------------------------------------------------------
+
+/// 適用したパラメータをセッションログに記録する (再生時の再現用)
+fn log_params(tx: &Sender<LogEntry>, source: &'static str, params: &EngineParams) {
+    match serde_json::to_string(params) {
+        Ok(params_json) => {
+            let _ = tx.try_send(LogEntry::Params {
+                timestamp: logger::now_ms(),
+                source,
+                params_json,
+            });
+        }
+        Err(e) => tracing::warn!("Failed to serialize engine params: {}", e),
+    }
+}
+
+/// パラメータファイルの更新時刻を 1 秒ごとに監視し、変更されたら再読み込みして適用する。
+/// 不正なファイルは警告を出して無視する (直前のパラメータを維持)。
+fn spawn_params_watcher(path: PathBuf, engine: CognitiveStateEngine, log_tx: Sender<LogEntry>) {
+    thread::spawn(move || {
+        tracing::info!("Params watcher started: {:?}", path);
+        let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+        let mut last_modified = modified(&path);
+
+        loop {
+            thread::sleep(Duration::from_secs(1));
+
+            let current = modified(&path);
+            if current.is_none() || current == last_modified {
+                continue;
+            }
+            last_modified = current;
+
+            match EngineParams::load(&path).and_then(|p| engine.set_params(p.clone()).map(|_| p)) {
+                Ok(p) => {
+                    tracing::info!("Engine params reloaded from {:?}", path);
+                    log_params(&log_tx, "file", &p);
+                }
+                Err(e) => tracing::warn!("Params reload skipped: {}", e),
+            }
+        }
+    });
+}
+
+// ---------------------------------------------------------------------------
+// 状態の保存とウォームスタート
+// ---------------------------------------------------------------------------
+
+/// 保存済みの状態をエンジンへ復元し、FeatureExtractor 用のチェックポイントを返す。
+/// ファイルが無い・不正・古すぎる場合は何もしない (コールドスタート)。
+fn warm_start(
+    engine: &CognitiveStateEngine,
+    path: &Path,
+    params: &PersistenceParams,
+) -> Option<ExtractorCheckpoint> {
+    if !path.exists() {
+        return None;
+    }
+    let saved = match PersistedState::load(path) {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::warn!("Warm start skipped: {}", e);
+            return None;
+        }
+    };
+    let now = logger::now_ms();
+    let Some(weight) = saved.warm_start_weight(params, now) else {
+        tracing::info!("Warm start skipped: saved state is too old");
+        return None;
+    };
+    let checkpoint = saved
+        .engine
+        .decay_toward(&engine.prior_checkpoint(), weight);
+    if saved.engine.states != checkpoint.states {
+        // 状態の定義が変わった: 信念は捨て、打鍵速度のベースラインだけ引き継ぐ
+        tracing::warn!(
+            "Warm start: saved states [{}] differ from the current states, belief not restored",
+            saved.engine.states.join(", ")
+        );
+        return Some(saved.extractor);
+    }
+    if let Err(e) = engine.restore(&checkpoint) {
+        tracing::warn!("Warm start skipped: {}", e);
+        return Some(saved.extractor);
+    }
+    tracing::info!(
+        "Warm start from {:?} (gap {} s, weight {:.2})",
+        path,
+        now.saturating_sub(saved.saved_at) / 1000,
+        weight
+    );
+    Some(saved.extractor)
+}
+
+/// ウォームスタートで復元した状態をセッションログに記録する (再生時の再現用)
+fn log_warm_start(
+    tx: &Sender<LogEntry>,
+    engine: &CognitiveStateEngine,
+    extractor: &ExtractorCheckpoint,
+) {
+    let state = PersistedState {
+        saved_at: logger::now_ms(),
+        engine: engine.checkpoint(),
+        extractor: extractor.clone(),
+    };
+    match serde_json::to_string(&state) {
+        Ok(state_json) => {
+            let _ = tx.try_send(LogEntry::WarmStart {
+                timestamp: state.saved_at,
+                state_json,
+            });
+        }
+        Err(e) => tracing::warn!("Failed to serialize warm-start state: {}", e),
+    }
+}
+
+/// エンジン・FeatureExtractor の現在の状態を保存する
+fn save_checkpoint(engine: &CognitiveStateEngine, extractor: &FeatureExtractor, path: &Path) {
+    let state = PersistedState {
+        saved_at: logger::now_ms(),
+        engine: engine.checkpoint(),
+        extractor: extractor.checkpoint(),
+    };
+    match state.save(path) {
+        Ok(()) => tracing::info!("Engine state saved to {:?}", path),
+        Err(e) => tracing::warn!("Engine state not saved: {}", e),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// 更新結果 (状態遷移イベント・判断根拠) の配信
+// ---------------------------------------------------------------------------
+
+/// `update()` の結果をセッションログに記録し、状態遷移はフロントエンド転送用チャネルへも送る
+fn publish_outcome(
+    outcome: UpdateOutcome,
+    log_tx: &Sender<LogEntry>,
+    event_tx: &Sender<StateTransition>,
+) {
+    if let Some(explanation) = outcome.explanation {
+        match serde_json::to_string(&explanation) {
+            Ok(explanation_json) => {
+                let _ = log_tx.try_send(LogEntry::Explain {
+                    timestamp: explanation.timestamp,
+                    explanation_json,
+                });
+            }
+            Err(e) => tracing::warn!("Failed to serialize update explanation: {}", e),
+        }
+    }
+    if let Some(transition) = outcome.transition {
+        publish_transition(transition, log_tx, event_tx);
+    }
+}
+
+/// 観測に使った個人ベースライン β を `feat` 行用の JSON にする (母集団 β の場合は None)
+fn beta_json(outcome: &UpdateOutcome) -> Option<String> {
+    let beta = outcome.personal_beta.as_ref()?;
+    match serde_json::to_string(beta) {
+        Ok(json) => Some(json),
+        Err(e) => {
+            tracing::warn!("Failed to serialize personal baseline: {}", e);
+            None
+        }
+    }
+}
+
+/// 確定した状態遷移をセッションログに記録し、フロントエンド転送用チャネルへ送る
+fn publish_transition(
+    transition: StateTransition,
+    log_tx: &Sender<LogEntry>,
+    event_tx: &Sender<StateTransition>,
+) {
+    tracing::info!(
+        "State transition: {} -> {} (dwell {} ms, p={:.2})",
+        transition.from,
+        transition.to,
+        transition.dwell_ms,
+        transition.confidence
+    );
+    let _ = log_tx.try_send(LogEntry::Transition {
+        timestamp: transition.at,
+        from: transition.from.clone(),
+        to: transition.to.clone(),
+        dwell_ms: transition.dwell_ms,
+        confidence: transition.confidence,
+    });
+    let _ = event_tx.try_send(transition);
+}
+
+/// 離席・復帰をセッションログに記録し、フロントエンド転送用チャネルへ送る
+fn publish_presence(
+    change: PresenceChange,
+    log_tx: &Sender<LogEntry>,
+    event_tx: &Sender<PresenceChange>,
+) {
+    let reason = change.reason.map(|r| match r {
+        AwayReason::Idle => "idle",
+        AwayReason::Locked => "locked",
+    });
+    match change.presence {
+        Presence::Away => tracing::info!("Presence: away ({})", reason.unwrap_or("")),
+        Presence::Present => tracing::info!(
+            "Presence: back after {} s (belief weight {:.2})",
+            change.away_ms / 1000,
+            change.weight
+        ),
+    }
+    let _ = log_tx.try_send(LogEntry::Presence {
+        timestamp: change.at,
+        state: match change.presence {
+            Presence::Present => "present",
+            Presence::Away => "away",
+        },
+        reason,
+        away_ms: change.away_ms,
+        weight: change.weight,
+        axes_ewma: change.axes_ewma,
+    });
+    let _ = event_tx.try_send(change);
+}
+
 // ---------------------------------------------------------------------------
 // behavioral_gt.py の場所を探す
 // ---------------------------------------------------------------------------
@@ -173,32 +468,107 @@ pub fn run() {
     }));
 
     // キーストローク入力チャネル
-    let (tx, rx) = crossbeam_channel::bounded(64);
+    let (tx, rx) = crossbeam_channel::bounded::<InputEvent>(64);
+
+    // 状態遷移イベントチャネル (分析スレッド → setup 内の emit スレッド)
+    let (transition_tx, transition_rx) = crossbeam_channel::bounded::<StateTransition>(64);
+
+    // 離席・復帰イベントチャネル (分析スレッド → setup 内の emit スレッド)
+    let (presence_tx, presence_rx) = crossbeam_channel::bounded::<PresenceChange>(16);
+
+    // パラメータ読み込み (ファイルが無い・不正な場合は既定値)
+    let params_path = params::default_params_path();
+    let engine_params = EngineParams::load_or_default(&params_path);
+    log_params(&log_tx, "startup", &engine_params);
 
     // エンジン初期化
-    let engine = CognitiveStateEngine::new();
+    let persistence_params = engine_params.persistence.clone();
+    let calibration = CalibrationProfile::load_or_new(
+        &engine_params.calibration.profile,
+        &engine_params.axes.beta,
+    );
+    let engine = CognitiveStateEngine::from_params(engine_params);
+    engine.load_calibration(calibration);
+
+    // 前回終了時の状態を復元 (ウォームスタート)。経過時間に応じて事前分布へ減衰させる
+    let state_path = persistence::default_state_path();
+    let warm_extractor = if persistence_params.enabled {
+        warm_start(&engine, &state_path, &persistence_params)
+    } else {
+        None
+    };
+    if let Some(extractor) = &warm_extractor {
+        log_warm_start(&log_tx, &engine, extractor);
+    }
+    let (checkpoint_tx, checkpoint_rx) = crossbeam_channel::bounded::<Sender<()>>(1);
     let engine_for_thread = engine.clone();
     let engine_for_monitor = engine.clone();
-    let log_tx_analysis = log_tx;
+    let log_tx_analysis = log_tx.clone();
+    let log_tx_monitor = log_tx.clone();
+
+    // パラメータファイル監視 (ホットリロード)
+    spawn_params_watcher(params_path, engine.clone(), log_tx);
 
     // 分析スレッド
-    // イベント駆動 (rx.recv) の代わりに recv_timeout を使い、
+    // イベント駆動 (rx.recv) の代わりに 1 秒のタイムアウト付きで待ち、
     // 無入力期間中もタイマーでHMMを継続更新する。
     // これにより長時間ポーズ (Incubation/Stuck) を検出できる。
     thread::spawn(move || {
         tracing::info!("Analysis thread started");
-        let mut extractor = FeatureExtractor::new(600);
+        let mut applied_params = engine_for_thread.params();
+        let mut extractor = FeatureExtractor::with_params(600, applied_params.features.clone());
+        if let Some(checkpoint) = &warm_extractor {
+            extractor.restore(checkpoint);
+        }
         let mut last_event_time = Instant::now();
+        // 離席の検出。起動時 (ウォームスタート後) の信念を最初の巻き戻し先にする
+        let mut presence = PresenceTracker::default();
+        presence.record(&engine_for_thread);
 
         loop {
-            match rx.recv_timeout(Duration::from_millis(1000)) {
+            let recv = crossbeam_channel::select! {
+                recv(rx) -> event => event.map_err(|_| RecvTimeoutError::Disconnected),
+                recv(checkpoint_rx) -> done => {
+                    let Ok(done) = done else { break };
+                    if engine_for_thread.params().persistence.enabled {
+                        save_checkpoint(&engine_for_thread, &extractor, &state_path);
+                    }
+                    // 個人ベースラインはウォームスタートの有無に関わらず保存する
+                    engine_for_thread.save_calibration();
+                    let _ = done.send(());
+                    continue;
+                }
+                default(Duration::from_millis(1000)) => Err(RecvTimeoutError::Timeout),
+            };
+
+            // ホットリロードされた特徴量パラメータを FeatureExtractor に反映
+            let latest_params = engine_for_thread.params();
+            if !Arc::ptr_eq(&latest_params, &applied_params) {
+                extractor.set_params(latest_params.features.clone());
+                applied_params = latest_params;
+            }
+
+            match recv {
                 Ok(event) => {
+                    // 離席中の入力は復帰 (沈黙時間はこの打鍵から数え直す)
+                    if let Some(change) = presence.on_key(
+                        &engine_for_thread,
+                        &applied_params.presence,
+                        event.timestamp,
+                    ) {
+                        publish_presence(change, &log_tx_analysis, &presence_tx);
+                    }
                     if engine_for_thread.get_paused() {
                         continue;
                     }
 
                     last_event_time = Instant::now();
                     extractor.process_event(event);
+                    engine_for_thread.observe_key(
+                        event.vk_code,
+                        event.is_press,
+                        event.timestamp,
+                    );
 
                     // キーイベントをログ記録
                     let _ = log_tx_analysis.try_send(LogEntry::Key {
@@ -209,22 +579,17 @@ pub fn run() {
 
                     if event.is_press {
                         let features = extractor.calculate_features();
-                        engine_for_thread.update(&features, Some(event.vk_code));
+                        let outcome = engine_for_thread.update(
+                            &features,
+                            Some(event.vk_code),
+                            event.timestamp,
+                        );
+                        let beta_json = beta_json(&outcome);
+                        publish_outcome(outcome, &log_tx_analysis, &transition_tx);
+                        presence.record(&engine_for_thread);
 
                         // 特徴量 + 状態確率をログ記録
-                        let state_probs = engine_for_thread.get_current_state();
-                        let p_flow = state_probs
-                            .get(&CognitiveState::Flow)
-                            .copied()
-                            .unwrap_or(0.0);
-                        let p_inc = state_probs
-                            .get(&CognitiveState::Incubation)
-                            .copied()
-                            .unwrap_or(0.0);
-                        let p_stuck = state_probs
-                            .get(&CognitiveState::Stuck)
-                            .copied()
-                            .unwrap_or(0.0);
+                        let probs = engine_for_thread.get_current_state();
 
                         let _ = log_tx_analysis.try_send(LogEntry::Feat {
                             timestamp: event.timestamp,
@@ -234,40 +599,52 @@ pub fn run() {
                             f4: features.f4_burst_length,
                             f5: features.f5_pause_count,
                             f6: features.f6_pause_after_del_rate,
-                            p_flow,
-                            p_inc,
-                            p_stuck,
+                            f7: features.f7_hold_time_median,
+                            f8: features.f8_hold_time_variance,
+                            f9: features.f9_digraph_slowdown,
+                            probs,
+                            beta_json,
                         });
                     }
                 }
 
                 Err(RecvTimeoutError::Timeout) => {
-                    // 無入力期間の検出: サイレンス特徴量でHMMを更新する
-                    if engine_for_thread.get_paused() {
+                    let now_ts = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis() as u64;
+
+                    // 離席の検出: キーボード・マウスの無入力、画面ロック、加速度センサー
+                    let signals = PresenceSignals {
+                        keyboard_idle_secs: last_event_time.elapsed().as_secs_f64(),
+                        system_idle_secs: input::idle::system_idle_secs(),
+                        motion_idle_secs: sensors::motion_idle_secs(),
+                        locked: input::idle::is_session_locked(),
+                    };
+                    if let Some(change) = presence.poll(
+                        &engine_for_thread,
+                        &applied_params.presence,
+                        &signals,
+                        now_ts,
+                    ) {
+                        if change.presence == Presence::Present {
+                            // マウス操作・ロック解除による復帰: 沈黙時間を数え直す
+                            last_event_time = Instant::now();
+                        }
+                        publish_presence(change, &log_tx_analysis, &presence_tx);
+                    }
+
+                    // 無入力期間の検出: サイレンス特徴量でHMMを更新する (離席中は止める)
+                    if engine_for_thread.get_paused() || presence.is_away() {
                         continue;
                     }
                     let silence_secs = last_event_time.elapsed().as_secs_f64();
                     if let Some(sf) = extractor.make_silence_observation(silence_secs) {
-                        engine_for_thread.update(&sf, None);
-
-                        let state_probs = engine_for_thread.get_current_state();
-                        let p_flow = state_probs
-                            .get(&CognitiveState::Flow)
-                            .copied()
-                            .unwrap_or(0.0);
-                        let p_inc = state_probs
-                            .get(&CognitiveState::Incubation)
-                            .copied()
-                            .unwrap_or(0.0);
-                        let p_stuck = state_probs
-                            .get(&CognitiveState::Stuck)
-                            .copied()
-                            .unwrap_or(0.0);
-
-                        let now_ts = SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap_or_default()
-                            .as_millis() as u64;
+                        let outcome = engine_for_thread.update(&sf, None, now_ts);
+                        let beta_json = beta_json(&outcome);
+                        publish_outcome(outcome, &log_tx_analysis, &transition_tx);
+
+                        let probs = engine_for_thread.get_current_state();
 
                         let _ = log_tx_analysis.try_send(LogEntry::Feat {
                             timestamp: now_ts,
@@ -277,9 +654,11 @@ pub fn run() {
                             f4: sf.f4_burst_length,
                             f5: sf.f5_pause_count,
                             f6: sf.f6_pause_after_del_rate,
-                            p_flow,
-                            p_inc,
-                            p_stuck,
+                            f7: sf.f7_hold_time_median,
+                            f8: sf.f8_hold_time_variance,
+                            f9: sf.f9_digraph_slowdown,
+                            probs,
+                            beta_json,
                         });
                     }
                 }
@@ -293,12 +672,22 @@ pub fn run() {
     thread::spawn(move || {
         tracing::info!("IME Monitor thread started");
         let monitor = input::ime::ImeMonitor::new();
+        let mut was_active = false;
         loop {
             let active = monitor.is_candidate_window_open();
             engine_for_monitor.set_paused(active);
             if active {
                 engine_for_monitor.force_flow_state();
             }
+            // 一時停止の開始・終了を記録する。再生は終了時の EWMA から再開する
+            if active != was_active {
+                was_active = active;
+                let _ = log_tx_monitor.try_send(LogEntry::Ime {
+                    timestamp: logger::now_ms(),
+                    active,
+                    axes_ewma: engine_for_monitor.snapshot().axes_ewma,
+                });
+            }
             thread::sleep(std::time::Duration::from_millis(100));
         }
     });
@@ -308,7 +697,23 @@ pub fn run() {
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .setup(|app| {
+        .setup(move |app| {
+            // 状態遷移イベントを "state-transition" としてフロントエンドへ送る
+            let handle = app.handle().clone();
+            thread::spawn(move || {
+                for transition in transition_rx {
+                    let _ = handle.emit("state-transition", transition);
+                }
+            });
+
+            // 離席・復帰を "presence-changed" としてフロントエンドへ送る
+            let handle = app.handle().clone();
+            thread::spawn(move || {
+                for change in presence_rx {
+                    let _ = handle.emit("presence-changed", change);
+                }
+            });
+
             let sensor_manager = SensorManager::new(app.handle().clone());
             app.manage(sensor_manager);
             let sensor_state: State<SensorManager<tauri::Wry>> = app.state();
@@ -317,11 +722,20 @@ pub fn run() {
         })
         .manage(engine)
         .manage(log_state)
+        .manage(CheckpointState { tx: checkpoint_tx })
         .invoke_handler(tauri::generate_handler![
             greet,
             get_cognitive_state,
+            get_state_definitions,
+            get_state_uncertainty,
+            get_presence,
+            get_last_explanation,
+            get_dwell_estimate,
+            get_calibration_status,
             quit_app,
             get_session_file,
+            get_engine_params,
+            set_engine_params,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
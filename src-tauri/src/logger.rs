@@ -24,9 +24,67 @@ pub enum LogEntry {
         f4: f64,
         f5: f64,
         f6: f64,
-        p_flow: f64,
-        p_inc: f64,
-        p_stuck: f64,
+        f7: f64,
+        f8: f64,
+        f9: f64,
+        /// (状態名, display_probs) の組。`states` の並び
+        probs: Vec<(String, f64)>,
+        /// 個人ベースラインで観測を作った場合の β (`BaselineParams` の JSON)
+        beta_json: Option<String>,
+    },
+    /// エンジンパラメータの適用 (起動時・ホットリロード時)。
+    /// 再生時に同じパラメータを復元できるよう、全パラメータを JSON で記録する。
+    Params {
+        timestamp: u64,
+        /// "startup" | "command" | "file"
+        source: &'static str,
+        /// `EngineParams` を serde_json でシリアライズした文字列
+        params_json: String,
+    },
+    /// ウォームスタートで復元した状態 (起動時のみ)。
+    /// 再生時に FeatureExtractor の履歴と EWMA を同じ値から始められるよう記録する。
+    WarmStart {
+        timestamp: u64,
+        /// 復元後の `PersistedState` を serde_json でシリアライズした文字列
+        state_json: String,
+    },
+    /// 表示状態の確定した遷移 (`StateTransition`)
+    Transition {
+        /// 遷移先の状態が argmax になった時刻
+        timestamp: u64,
+        /// 状態名 (`states[].name`)
+        from: String,
+        to: String,
+        dwell_ms: u64,
+        confidence: f64,
+    },
+    /// 離席・復帰 (`PresenceChange`)
+    Presence {
+        timestamp: u64,
+        /// "present" | "away"
+        state: &'static str,
+        /// 離席時の理由 "idle" | "locked" (復帰時は None)
+        reason: Option<&'static str>,
+        /// 復帰時: 離席していた時間 [ms]
+        away_ms: u64,
+        /// 復帰時: 離席前の信念に残した重み
+        weight: f64,
+        /// 巻き戻し・減衰後のエンジンの EWMA
+        axes_ewma: (f64, f64),
+    },
+    /// IME 変換による一時停止の開始・終了 (`force_flow_state` による強制リセット)
+    Ime {
+        timestamp: u64,
+        /// true: 変換開始 (一時停止)、false: 変換終了 (再開)
+        active: bool,
+        /// その時点のエンジンの EWMA。再開時は次の `update()` がこの値から始める
+        axes_ewma: (f64, f64),
+    },
+    /// 更新ごとの判断根拠 (`debug.explain` 有効時のみ)
+    Explain {
+        timestamp: u64,
+        /// `UpdateExplanation` を serde_json でシリアライズした文字列
+        explanation_json: String,
     },
     /// セッション終了マーカー
     End,
@@ -96,14 +154,98 @@ impl SessionLogger {
                         f4,
                         f5,
                         f6,
-                        p_flow,
-                        p_inc,
-                        p_stuck,
+                        f7,
+                        f8,
+                        f9,
+                        probs,
+                        beta_json,
+                    } => {
+                        // 状態名は [a-z0-9_] に検証済みのためエスケープ不要
+                        let probs = probs
+                            .iter()
+                            .map(|(name, p)| format!(r#""{}":{:.4}"#, name, p))
+                            .collect::<Vec<_>>()
+                            .join(",");
+                        let beta = beta_json
+                            .map(|b| format!(r#","beta":{}"#, b))
+                            .unwrap_or_default();
+                        let _ = writeln!(
+                            writer,
+                            r#"{{"type":"feat","t":{},"f1":{:.2},"f2":{:.2},"f3":{:.4},"f4":{:.2},"f5":{:.1},"f6":{:.4},"f7":{:.1},"f8":{:.1},"f9":{:.3},"probs":{{{}}}{}}}"#,
+                            timestamp, f1, f2, f3, f4, f5, f6, f7, f8, f9, probs, beta,
+                        );
+                    }
+                    LogEntry::Params {
+                        timestamp,
+                        source,
+                        params_json,
+                    } => {
+                        let _ = writeln!(
+                            writer,
+                            r#"{{"type":"params","t":{},"source":"{}","params":{}}}"#,
+                            timestamp, source, params_json,
+                        );
+                    }
+                    LogEntry::WarmStart {
+                        timestamp,
+                        state_json,
+                    } => {
+                        let _ = writeln!(
+                            writer,
+                            r#"{{"type":"warm_start","t":{},"state":{}}}"#,
+                            timestamp, state_json,
+                        );
+                    }
+                    LogEntry::Transition {
+                        timestamp,
+                        from,
+                        to,
+                        dwell_ms,
+                        confidence,
+                    } => {
+                        let _ = writeln!(
+                            writer,
+                            r#"{{"type":"transition","t":{},"from":"{}","to":"{}","dwell_ms":{},"confidence":{:.4}}}"#,
+                            timestamp, from, to, dwell_ms, confidence,
+                        );
+                    }
+                    LogEntry::Presence {
+                        timestamp,
+                        state,
+                        reason,
+                        away_ms,
+                        weight,
+                        axes_ewma: (x, y),
                     } => {
+                        let reason = match reason {
+                            Some(r) => format!(r#""{}""#, r),
+                            None => "null".to_string(),
+                        };
                         let _ = writeln!(
                             writer,
-                            r#"{{"type":"feat","t":{},"f1":{:.2},"f2":{:.2},"f3":{:.4},"f4":{:.2},"f5":{:.1},"f6":{:.4},"p_flow":{:.4},"p_inc":{:.4},"p_stuck":{:.4}}}"#,
-                            timestamp, f1, f2, f3, f4, f5, f6, p_flow, p_inc, p_stuck,
+                            r#"{{"type":"presence","t":{},"state":"{}","reason":{},"away_ms":{},"weight":{:.4},"axes_ewma":[{},{}]}}"#,
+                            timestamp, state, reason, away_ms, weight, x, y,
+                        );
+                    }
+                    LogEntry::Ime {
+                        timestamp,
+                        active,
+                        axes_ewma: (x, y),
+                    } => {
+                        let _ = writeln!(
+                            writer,
+                            r#"{{"type":"ime","t":{},"active":{},"axes_ewma":[{},{}]}}"#,
+                            timestamp, active, x, y,
+                        );
+                    }
+                    LogEntry::Explain {
+                        timestamp,
+                        explanation_json,
+                    } => {
+                        let _ = writeln!(
+                            writer,
+                            r#"{{"type":"explain","t":{},"explanation":{}}}"#,
+                            timestamp, explanation_json,
                         );
                     }
                     LogEntry::End => {
@@ -134,23 +276,28 @@ impl SessionLogger {
 }
 
 /// UNIX時刻をミリ秒で返す
-fn now_ms() -> u64 {
+pub fn now_ms() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or_default()
         .as_millis() as u64
 }
 
-/// Documents/GSE-sessions/gse_YYYYMMDD_HHMMSS.ndjson のパスを生成する
-pub fn default_log_path() -> PathBuf {
+/// セッションログの保存先 Documents/GSE-sessions を返す
+pub fn default_session_dir() -> PathBuf {
     // Tauri の path API を使わず標準環境変数で取得 (lib.rs の setup 前に呼べるように)
     let base = std::env::var("USERPROFILE")
         .or_else(|_| std::env::var("HOME"))
         .unwrap_or_else(|_| ".".to_string());
 
-    let dir = PathBuf::from(base)
+    PathBuf::from(base)
         .join("Documents")
-        .join("GSE-sessions");
+        .join("GSE-sessions")
+}
+
+/// Documents/GSE-sessions/gse_YYYYMMDD_HHMMSS.ndjson のパスを生成する
+pub fn default_log_path() -> PathBuf {
+    let dir = default_session_dir();
 
     // タイムスタンプ付きファイル名
     let ts = chrono_like_filename();
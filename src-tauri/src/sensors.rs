@@ -1,11 +1,29 @@
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tauri::{AppHandle, Emitter, Runtime};
 use windows::{
     Devices::{Geolocation::Geolocator, Sensors::Accelerometer},
     Foundation::TypedEventHandler,
 };
 
+/// Unix ms of the last accelerometer reading that counted as movement (0 = none yet).
+pub static LAST_MOTION_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Seconds since the accelerometer last reported movement.
+/// None if there is no accelerometer or it has not reported movement yet.
+pub fn motion_idle_secs() -> Option<f64> {
+    let last = LAST_MOTION_MS.load(Ordering::Relaxed);
+    if last == 0 {
+        return None;
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    Some(now.saturating_sub(last) as f64 / 1000.0)
+}
+
 pub struct SensorManager<R: Runtime> {
     app: AppHandle<R>,
 }
@@ -61,6 +79,13 @@ impl<R: Runtime> SensorManager<R> {
 
                                         // 1.0 is gravity. Significant movement > 1.2 or < 0.8
                                         if (magnitude - 1.0).abs() > 0.2 {
+                                            // Presence detection: recent movement means someone is handling the device
+                                            let now = SystemTime::now()
+                                                .duration_since(UNIX_EPOCH)
+                                                .unwrap_or_default()
+                                                .as_millis()
+                                                as u64;
+                                            LAST_MOTION_MS.store(now, Ordering::Relaxed);
                                             let _ = app_clone.emit("sensor-accelerometer", "move");
                                         }
                                     }
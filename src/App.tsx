@@ -4,21 +4,13 @@ import { getCurrentWindow } from "@tauri-apps/api/window";
 import { listen } from "@tauri-apps/api/event";
 import Dashboard from "./components/Dashboard";
 import Overlay from "./components/Overlay";
+import type { CognitiveStateRaw, StateDef } from "./types";
 import "./App.css";
 
-interface CognitiveStateRaw {
-  flow: number;
-  incubation: number;
-  stuck: number;
-}
-
 function App() {
   const [windowLabel, setWindowLabel] = useState<string>("");
-  const [cognitiveState, setCognitiveState] = useState<CognitiveStateRaw>({
-    flow: 0.1,
-    incubation: 0.1,
-    stuck: 0.0, // Default to neutral/low stuck
-  });
+  const [states, setStates] = useState<StateDef[]>([]);
+  const [cognitiveState, setCognitiveState] = useState<CognitiveStateRaw>({});
   const [isWallActive, setIsWallActive] = useState(false);
 
   // D-1: Memoize getCurrentWindow (Performance Optimization)
@@ -32,10 +24,19 @@ function App() {
   }, [appWindow]);
 
   // 2. Poll Cognitive State (Every 500ms)
+  // The state list can change on a params reload, so re-fetch the definitions
+  // whenever the returned keys no longer match them.
   useEffect(() => {
+    let names: string[] = [];
     const interval = setInterval(async () => {
       try {
         const state = await invoke<CognitiveStateRaw>("get_cognitive_state");
+        const keys = Object.keys(state);
+        if (keys.length !== names.length || keys.some((k) => !names.includes(k))) {
+          const defs = await invoke<StateDef[]>("get_state_definitions");
+          names = defs.map((d) => d.name);
+          setStates(defs);
+        }
         setCognitiveState(state);
       } catch (e) {
         console.error("Failed to fetch state:", e);
@@ -45,6 +46,9 @@ function App() {
     return () => clearInterval(interval);
   }, []);
 
+  // Intervention targets the "stuck" state; configurations without it never trigger it
+  const stuckProb = cognitiveState.stuck ?? 0;
+
   // 3. Intervention Logic (D-2/D-3)
   // Lv2 (Mist): Stuck > 0.7 for 30s.
   // Note: 'isWallActive' currently toggles the Overlay component which handles the Mist effect.
@@ -52,7 +56,7 @@ function App() {
     let timer: ReturnType<typeof setTimeout>;
     
     // Check if Stuck state is dominant/high
-    if (cognitiveState.stuck > 0.7) {
+    if (stuckProb > 0.7) {
       if (!isWallActive) {
         // If Stuck stays high for 30 seconds, activate Mist (Lv2)
         timer = setTimeout(() => {
@@ -67,7 +71,7 @@ function App() {
         }
     }
     return () => clearTimeout(timer);
-  }, [cognitiveState.stuck, isWallActive]);
+  }, [stuckProb, isWallActive]);
 
   // 4. Sensor Integration (Unlock Logic)
   useEffect(() => {
@@ -87,7 +91,7 @@ function App() {
   if (windowLabel === "overlay") {
     return (
       <Overlay
-        stuckProb={cognitiveState.stuck}
+        stuckProb={stuckProb}
         isWallActive={isWallActive}
       />
     );
@@ -95,7 +99,7 @@ function App() {
 
   // Default to Main Dashboard
   return (
-    <Dashboard cognitiveState={cognitiveState} />
+    <Dashboard states={states} cognitiveState={cognitiveState} />
   );
 }
 
@@ -1,25 +1,26 @@
 import React from 'react';
 import { getCurrentWindow } from '@tauri-apps/api/window';
 import { invoke } from '@tauri-apps/api/core';
+import type { CognitiveStateRaw, StateDef } from '../types';
 
 interface DashboardProps {
-  cognitiveState: {
-    flow: number;
-    incubation: number;
-    stuck: number;
-  };
+  // Configured states, in the engine's order (rendered one bar each)
+  states: StateDef[];
+  cognitiveState: CognitiveStateRaw;
 }
 
-const Dashboard: React.FC<DashboardProps> = ({ cognitiveState }) => {
-  // Determine dominant state for color coding
-  const getMaxState = () => {
-    const { flow, incubation, stuck } = cognitiveState;
-    if (flow >= incubation && flow >= stuck) return 'Flow';
-    if (incubation >= flow && incubation >= stuck) return 'Incubation';
-    return 'Stuck';
-  };
+// "incubation" → "Incubation"
+const displayName = (name: string) =>
+  name.split('_').map((w) => w.charAt(0).toUpperCase() + w.slice(1)).join(' ');
+
+const Dashboard: React.FC<DashboardProps> = ({ states, cognitiveState }) => {
+  const probOf = (name: string) => cognitiveState[name] ?? 0;
 
-  const dominant = getMaxState();
+  // Determine dominant state for color coding (first state wins ties, as before)
+  const dominant = states.reduce<string | null>(
+    (best, s) => (best === null || probOf(s.name) > probOf(best) ? s.name : best),
+    null,
+  );
 
   // Window controls
   const appWindow = getCurrentWindow();
@@ -38,7 +39,7 @@ const Dashboard: React.FC<DashboardProps> = ({ cognitiveState }) => {
   React.useEffect(() => {
     let timer: number | undefined;
 
-    if (dominant === 'Stuck') {
+    if (dominant === 'stuck') {
       // User requested "about 1 minute". 
       // For testing purposes, we might want this shorter, but let's stick to specification or slightly less for verify.
       // Let's use 30 seconds for now to be "about 1 minute" but verifiable.
@@ -53,7 +54,7 @@ const Dashboard: React.FC<DashboardProps> = ({ cognitiveState }) => {
   }, [dominant]);
 
   return (
-    <div className={`dashboard-container state-${dominant.toLowerCase()}`}>
+    <div className={`dashboard-container state-${dominant ?? 'none'}`}>
       {/* Mist Effect Overlay */}
       {mistActive && <div className="mist-overlay" />}
 
@@ -87,42 +88,22 @@ const Dashboard: React.FC<DashboardProps> = ({ cognitiveState }) => {
         <h2>Cognitive State Engine</h2>
 
         <div className="state-card">
-          <h3>Current State: <span className="dominant-state">{dominant}</span></h3>
+          <h3>Current State: <span className="dominant-state">{dominant ? displayName(dominant) : '—'}</span></h3>
         </div>
 
         <div className="metrics-container">
-          <div className="metric-row">
-            <label>Flow</label>
-            <div className="progress-bar-bg">
-              <div
-                className="progress-bar-fill flow"
-                style={{ width: `${cognitiveState.flow * 100}%` }}
-              ></div>
+          {states.map((s) => (
+            <div className="metric-row" key={s.name}>
+              <label>{displayName(s.name)}</label>
+              <div className="progress-bar-bg">
+                <div
+                  className={`progress-bar-fill ${s.name}`}
+                  style={{ width: `${probOf(s.name) * 100}%` }}
+                ></div>
+              </div>
+              <span>{(probOf(s.name) * 100).toFixed(1)}%</span>
             </div>
-            <span>{(cognitiveState.flow * 100).toFixed(1)}%</span>
-          </div>
-
-          <div className="metric-row">
-            <label>Incubation</label>
-            <div className="progress-bar-bg">
-              <div
-                className="progress-bar-fill incubation"
-                style={{ width: `${cognitiveState.incubation * 100}%` }}
-              ></div>
-            </div>
-            <span>{(cognitiveState.incubation * 100).toFixed(1)}%</span>
-          </div>
-
-          <div className="metric-row">
-            <label>Stuck</label>
-            <div className="progress-bar-bg">
-              <div
-                className="progress-bar-fill stuck"
-                style={{ width: `${cognitiveState.stuck * 100}%` }}
-              ></div>
-            </div>
-            <span>{(cognitiveState.stuck * 100).toFixed(1)}%</span>
-          </div>
+          ))}
         </div>
 
         <div className="info-box">
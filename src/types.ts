@@ -0,0 +1,8 @@
+// Configured states (`params.hmm.states`), in probability-vector order
+export interface StateDef {
+  name: string;
+  label: string;
+}
+
+// State name → display probability (keys follow `StateDef.name`)
+export type CognitiveStateRaw = Record<string, number>;